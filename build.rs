@@ -1,6 +1,19 @@
-// TODO: read https://doc.rust-lang.org/book/ch14-01-release-profiles.html
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("proto/serial_terminal.proto")?;
-    Ok(())
-}
\ No newline at end of file
+// TODO: read https://doc.rust-lang.org/book/ch14-01-release-profiles.html
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // prost-build runs protoc, the vendored one being used if none is given.
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+
+    // The descriptors of the protos, served by the reflection service of the server.
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR")?);
+
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("serial_terminal_descriptor.bin"))
+        .compile(
+            &["proto/serial_terminal.proto"],
+            &["proto"],
+        )?;
+    Ok(())
+}
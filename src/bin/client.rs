@@ -0,0 +1,210 @@
+//! Command-line client of the server, for scripts and manual tests.
+//!
+//! Prints the content of each reply, on stdout on success, on stderr otherwise,
+//! exiting with 1 on failure.
+
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig};
+use tonic::{Request, Status};
+
+// Brings into scope the module created by tonic.
+pub mod serial_terminal {
+    tonic::include_proto!("serial_terminal");
+}
+
+use serial_terminal::serial_com_service_client::SerialComServiceClient;
+use serial_terminal::{read_once_rep, send_once_rep};
+use serial_terminal::{
+    ClosePortReq, OpenPortReq, PortListReq, ReadFormat, ReadOnceReq, SendOnceReq, SerialPingReq,
+};
+
+#[derive(StructOpt)]
+struct Cli {
+    #[structopt(
+        long,
+        help = "URL of the server.",
+        default_value = "http://127.0.0.1:3333"
+    )]
+    addr: String,
+
+    #[structopt(
+        long,
+        help = "Token of the server started with --auth-token, given in the authorization metadata. Also set by the SERIAL_RPC_AUTH_TOKEN env var."
+    )]
+    auth_token: Option<String>,
+
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "PEM certificate of the CA (or the self-signed certificate) of a server serving over TLS, for an https:// --addr."
+    )]
+    tls_ca: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Pings the server.
+    Ping,
+    /// Lists the available ports.
+    List,
+    /// Opens a serial port.
+    Open { port: String, baudrate: u32 },
+    /// Closes the open port.
+    Close,
+    /// Sends a message, with its hex escapes (0x0A for ex.) parsed.
+    Send { message: String },
+    /// Reads once from the open port.
+    Read {
+        #[structopt(long, help = "Prints a hexdump of the bytes read.")]
+        hexdump: bool,
+    },
+}
+
+/// Gives the token of `--auth-token` in the `authorization` metadata of every request.
+#[derive(Clone)]
+struct Authorization(Option<MetadataValue<Ascii>>);
+
+impl Interceptor for Authorization {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = &self.0 {
+            request
+                .metadata_mut()
+                .insert("authorization", token.clone());
+        }
+        Ok(request)
+    }
+}
+
+type Client = SerialComServiceClient<InterceptedService<Channel, Authorization>>;
+
+/// Connects to `addr`, over TLS trusting `tls_ca` if given, authenticating with
+/// `auth_token` if given.
+async fn connect(
+    addr: &str,
+    auth_token: Option<&str>,
+    tls_ca: Option<&Path>,
+) -> Result<Client, String> {
+    let authorization = match auth_token {
+        Some(token) => Some(
+            format!("Bearer {}", token)
+                .parse()
+                .map_err(|_| "Invalid auth token".to_string())?,
+        ),
+        None => None,
+    };
+
+    let mut endpoint = Channel::from_shared(addr.to_string()).map_err(|e| e.to_string())?;
+    if let Some(tls_ca) = tls_ca {
+        let ca = std::fs::read(tls_ca)
+            .map_err(|e| format!("Could not read {}: {}", tls_ca.display(), e))?;
+        endpoint = endpoint
+            .tls_config(ClientTlsConfig::new().ca_certificate(Certificate::from_pem(ca)))
+            .map_err(|e| e.to_string())?;
+    }
+    let channel = endpoint.connect().await.map_err(|e| e.to_string())?;
+
+    Ok(SerialComServiceClient::with_interceptor(
+        channel,
+        Authorization(authorization),
+    ))
+}
+
+/// Runs `command`, returning if it succeeded and the content of the reply.
+async fn run(client: &mut Client, command: Command) -> Result<(bool, String), tonic::Status> {
+    Ok(match command {
+        Command::Ping => {
+            let reply = client.ping(SerialPingReq {}).await?.into_inner();
+            (true, reply.content)
+        }
+        Command::List => {
+            let request = PortListReq {
+                force_refresh: false,
+            };
+            let reply = client.get_port_list(request).await?.into_inner();
+            (true, reply.ports.join("\n"))
+        }
+        Command::Open { port, baudrate } => {
+            let request = OpenPortReq {
+                port,
+                baudrate,
+                post_open_delay_ms: 0,
+                flow_control: 0,
+            };
+            let reply = client.open_port(request).await?.into_inner();
+            (reply.success, reply.content)
+        }
+        Command::Close => {
+            let reply = client.close_port(ClosePortReq {}).await?.into_inner();
+            (reply.success, reply.content)
+        }
+        Command::Send { message } => {
+            let request = SendOnceReq {
+                content: message,
+                chunk_size: 0,
+                dry_run: false,
+                timeout_ms: 0,
+            };
+            match client.send_once(request).await?.into_inner().outcome {
+                Some(send_once_rep::Outcome::Success(sent)) => (true, sent.content),
+                Some(send_once_rep::Outcome::Error(e)) => (false, e.message),
+                None => (false, "Empty reply".to_string()),
+            }
+        }
+        Command::Read { hexdump } => {
+            let format = if hexdump {
+                ReadFormat::Hexdump
+            } else {
+                ReadFormat::Text
+            };
+            let request = ReadOnceReq {
+                max_bytes: 0,
+                timeout_ms: 0,
+                format: format as i32,
+            };
+            match client.read_once(request).await?.into_inner().outcome {
+                Some(read_once_rep::Outcome::Success(read)) => (true, read.content),
+                Some(read_once_rep::Outcome::Error(e)) => (false, e.message),
+                None => (false, "Empty reply".to_string()),
+            }
+        }
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Cli::from_args();
+
+    let auth_token = args
+        .auth_token
+        .clone()
+        .or_else(|| std::env::var("SERIAL_RPC_AUTH_TOKEN").ok());
+
+    let connected = connect(&args.addr, auth_token.as_deref(), args.tls_ca.as_deref()).await;
+    let mut client = match connected {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Could not connect to {}: {}", args.addr, e);
+            std::process::exit(1);
+        }
+    };
+
+    match run(&mut client, args.command).await {
+        Ok((true, content)) => println!("{}", content),
+        Ok((false, content)) => {
+            eprintln!("{}", content);
+            std::process::exit(1);
+        }
+        Err(status) => {
+            eprintln!("Request failed: {}", status);
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,181 +1,2674 @@
-use tonic::{transport::Server, Request, Response, Status};
-
-use serial_term_rpc::serial_port::SerialPort;
-
-// Brings into scope the module created by tonic.
-pub mod serial_terminal {
-    tonic::include_proto!("serial_terminal");
-}
-
-// Created when building the proto with tonic.
-use serial_terminal::serial_com_service_server::{SerialComService, SerialComServiceServer};
-use serial_terminal::{SerialPingReq, SerialPingRep, 
-    PortListReq, PortListRep, 
-    OpenPortReq, OpenPortRep,
-    ClosePortReq, ClosePortRep,
-    SendOnceReq, SendOnceRep,
-    ReadOnceReq, ReadOnceRep
-};
-
-use std::sync::{Arc, Mutex};
-
-pub struct MySerialComService {
-    // Arc and Mutex to be able to safely share the port accross threads.
-    port: Arc<Mutex<SerialPort>>,
-}
-
-#[tonic::async_trait]
-impl SerialComService for MySerialComService {
-
-    async fn ping(
-            &self,
-            _request: Request<SerialPingReq>,
-        ) -> Result<Response<SerialPingRep>, Status> {
-
-            println!("Got a ping request.");
-
-            let reply = SerialPingRep {
-                content: format!("Pong!").into(),
-            };
-
-            Ok(Response::new(reply))
-    }
-
-    async fn get_port_list(
-            &self,
-            _request: Request<PortListReq>,
-        ) -> Result<Response<PortListRep>, Status> {
-
-            println!("Got a GetPortList request.");
-
-            let port_names = SerialPort::get_available_port_names();
-
-            let reply = PortListRep {
-                ports: port_names,
-            };
-
-            Ok(Response::new(reply))
-    }
-
-    async fn open_port(
-            &self,
-            request: Request<OpenPortReq>,
-        ) -> Result<Response<OpenPortRep>, Status> {
-
-            println!("Got a OpenPort request.");
-
-            let request = request.into_inner();
-
-            let port = Arc::clone(&self.port);
-            let mut guard_port = port.lock().unwrap();
-            let unlocked_port = &mut *guard_port;
-
-            let resp = unlocked_port.open_port(&request.port, request.baudrate);
-
-            let reply = OpenPortRep {
-                success: resp.success,
-                content: resp.content.into(),
-            };
-
-            Ok(Response::new(reply))
-    }
-
-    async fn close_port(
-            &self,
-            _request: Request<ClosePortReq>,
-        ) -> Result<Response<ClosePortRep>, Status> {
-
-            println!("Got a ClosePort request.");
-
-            let port = Arc::clone(&self.port);
-            let mut guard_port = port.lock().unwrap();
-            let unlocked_port = &mut *guard_port;
-
-            let resp = unlocked_port.close_port();
-
-            let reply = ClosePortRep {
-                success: resp.success,
-                content: resp.content.into(),
-            };
-
-            Ok(Response::new(reply))
-    }
-
-    async fn send_once(
-            &self,
-            request: Request<SendOnceReq>,
-        ) -> Result<Response<SendOnceRep>, Status> {
-
-            println!("Got a SendOnce request.");
-
-            let request = request.into_inner();
-
-            let port = Arc::clone(&self.port);
-            let mut guard_port = port.lock().unwrap();
-            let unlocked_port = &mut *guard_port;
-
-            let resp = unlocked_port.send_once(&request.content);
-
-            let reply = SendOnceRep {
-                success: resp.success,
-                content: resp.content.into(),
-            };
-
-            Ok(Response::new(reply))
-    }
-
-    async fn read_once(
-            &self,
-            _request: Request<ReadOnceReq>,
-        ) -> Result<Response<ReadOnceRep>, Status> {
-
-            println!("Got a ReadOnce request.");
-
-            let port = Arc::clone(&self.port);
-            let mut guard_port = port.lock().unwrap();
-            let unlocked_port = &mut *guard_port;
-
-            let resp = unlocked_port.read_once();
-
-            let reply = ReadOnceRep {
-                success: resp.success,
-                content: resp.content.into(),
-            };
-
-            Ok(Response::new(reply))
-    }
-
-}
-
-use structopt::StructOpt;
-use std::net::SocketAddr;
-
-#[derive(StructOpt)]
-struct Cli {
-    #[structopt(help="Addr with port: 127.0.0.1:3333 for ex.", default_value="127.0.0.1:3333")]
-    addr: String,
-}
-
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Cli::from_args();
-    let addr = match args.addr.parse::<SocketAddr>() {
-        Ok(addr) => addr,
-        Err(e) => {
-            println!("Error with address '{}': {}", args.addr, e);
-            return Err(e.into());
-        }
-    };
-
-    let port = Arc::new(Mutex::new(SerialPort::new()));
-    let serial_com_service = MySerialComService { port: port };
-
-    println!("Running the RPC server on {} ...", args.addr);
-
-    Server::builder()
-        .add_service(SerialComServiceServer::new(serial_com_service))
-        .serve(addr)
-        .await?;
-
-    Ok(())
-}
\ No newline at end of file
+use tonic::{codec::CompressionEncoding, service::interceptor::InterceptedService, transport::{Identity, NamedService, Server, ServerTlsConfig}, Request, Response, Status};
+use tonic_health::ServingStatus;
+use tonic_reflection::server::{ServerReflection, ServerReflectionServer};
+
+use serial_term_rpc::serial_port::{error::SerialError, hexdump, SerialPort, SerialPortResponse};
+use serial_term_rpc::serial_port::{allowlist, autobaud, capability, coalesce, config, delimited, events, modbus, monitor, payloads, samples, settings, trace, usb};
+
+// Brings into scope the module created by tonic.
+pub mod serial_terminal {
+    tonic::include_proto!("serial_terminal");
+}
+
+// Created when building the proto with tonic.
+use serial_terminal::serial_com_service_server::{SerialComService, SerialComServiceServer};
+use serial_terminal::{SerialPingReq, SerialPingRep, 
+    PortListReq, PortListRep, PortDescription,
+    OpenPortReq, OpenPortRep,
+    ClosePortReq, ClosePortRep,
+    SendOnceReq, SendOnceRep, SendSuccess,
+    ReadOnceReq, ReadOnceRep, ReadSuccess,
+    Error, ErrorCode,
+    SendModbusRtuReq, SendModbusRtuRep, ModbusStatus,
+    GetTimeoutsReq, GetTimeoutsRep,
+    SetRepeaterReq, SetRepeaterRep,
+    SendWithAcksReq, SendWithAcksRep,
+    AutoBaudReq, AutoBaudRep, AutoBaudMethod,
+    ReadLinesReq, ReadLinesRep,
+    ReadUntilReq, ReadUntilRep, OverflowPolicy,
+    SetCodecsReq, SetCodecsRep,
+    ResetStatsReq, ResetStatsRep,
+    QueryCapabilitiesReq, QueryCapabilitiesRep, Capability,
+    SetByteTraceReq, SetByteTraceRep,
+    GetByteTraceReq, GetByteTraceRep, TracedByte, ByteDirection,
+    SwapPortReq, SwapPortRep,
+    WatchEventsReq, PortEventRep, PortEventKind,
+    CancelWriteReq, CancelWriteRep,
+    ReadKeyValueReq, ReadKeyValueRep,
+    SetExclusiveReq, SetExclusiveRep,
+    SetWriteCoalescingReq, SetWriteCoalescingRep,
+    FlushReq, FlushRep,
+    OpenByUsbIndexReq, OpenByUsbIndexRep,
+    GetLastPayloadsReq, GetLastPayloadsRep, Payload,
+    ReadSamplesReq, ReadSamplesRep, SampleType, Endianness,
+    WriteThenReadReq, WriteThenReadRep,
+    GetPortSettingsReq, GetPortSettingsRep, SetPortSettingsReq, SetPortSettingsRep,
+    PortSettings, SettingChange, Parity, StopBits, FlowControl,
+    StreamReadReq, SessionReq, SessionRep,
+    SetConfigReq, SetConfigRep,
+    SendBytesReq, SendBytesRep, ReadBytesReq, ReadBytesRep,
+    GetStatsReq, GetStatsRep, ReadFormat
+};
+
+use serial_terminal::{read_once_rep, send_once_rep, session_rep};
+
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tracing::level_filters::LevelFilter;
+use tracing::{error, info, warn, Level};
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Descriptors of the protos of the server, written by build.rs.
+const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/serial_terminal_descriptor.bin"));
+
+/// The gRPC server reflection, for grpcurl and the like to be used without the
+/// protos. It describes the serial, the health and the reflection services.
+fn reflection_server() -> Result<ServerReflectionServer<impl ServerReflection>, tonic_reflection::server::Error> {
+    tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build()
+}
+
+/// Formats `bytes` as `0x..` hex, separated by spaces.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("0x{:02X}", b)).collect::<Vec<String>>().join(" ")
+}
+
+fn to_payload(payload: &payloads::Payload) -> Payload {
+    Payload {
+        bytes: payload.bytes.clone(),
+        truncated: payload.truncated,
+        timestamp_ms: payload.at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+    }
+}
+
+fn to_port_description(description: usb::PortDescription) -> PortDescription {
+    PortDescription {
+        name: description.name,
+        usb: description.vid.is_some(),
+        vid: description.vid.unwrap_or_default() as u32,
+        pid: description.pid.unwrap_or_default() as u32,
+        serial_number: description.serial_number.unwrap_or_default(),
+        manufacturer: description.manufacturer.unwrap_or_default(),
+        product: description.product.unwrap_or_default(),
+    }
+}
+
+/// Returns the timeout the client has set on the call, from its `grpc-timeout` header.
+fn call_timeout<T>(request: &Request<T>) -> Option<Duration> {
+    let timeout = request.metadata().get("grpc-timeout")?.to_str().ok()?;
+    let (value, unit) = timeout.split_at(timeout.len().checked_sub(1)?);
+    let value: u64 = value.parse().ok()?;
+
+    match unit {
+        "H" => Some(Duration::from_secs(value.saturating_mul(3600))),
+        "M" => Some(Duration::from_secs(value.saturating_mul(60))),
+        "S" => Some(Duration::from_secs(value)),
+        "m" => Some(Duration::from_millis(value)),
+        "u" => Some(Duration::from_micros(value)),
+        "n" => Some(Duration::from_nanos(value)),
+        _ => None,
+    }
+}
+
+fn to_port_settings(settings: settings::PortSettings) -> PortSettings {
+    PortSettings {
+        port: settings.port_path,
+        baudrate: settings.baudrate,
+        data_bits: match settings.data_bits {
+            serialport::DataBits::Five => 5,
+            serialport::DataBits::Six => 6,
+            serialport::DataBits::Seven => 7,
+            serialport::DataBits::Eight => 8,
+        },
+        parity: match settings.parity {
+            serialport::Parity::None => Parity::None,
+            serialport::Parity::Odd => Parity::Odd,
+            serialport::Parity::Even => Parity::Even,
+        } as i32,
+        stop_bits: match settings.stop_bits {
+            serialport::StopBits::One => StopBits::One,
+            serialport::StopBits::Two => StopBits::Two,
+        } as i32,
+        flow_control: match settings.flow_control {
+            serialport::FlowControl::None => FlowControl::None,
+            serialport::FlowControl::Software => FlowControl::Software,
+            serialport::FlowControl::Hardware => FlowControl::Hardware,
+        } as i32,
+        read_timeout_ms: settings.read_timeout.as_millis() as u64,
+        write_timeout_ms: settings.write_timeout.as_millis() as u64,
+        exclusive: settings.exclusive,
+        byte_escapes: settings.byte_escapes,
+    }
+}
+
+/// Returns the settings, or the invalid field.
+fn from_port_settings(settings: PortSettings) -> Result<settings::PortSettings, &'static str> {
+    Ok(settings::PortSettings {
+        port_path: settings.port,
+        baudrate: settings.baudrate,
+        data_bits: from_data_bits(settings.data_bits)?,
+        parity: from_parity(settings.parity)?,
+        stop_bits: from_stop_bits(settings.stop_bits)?,
+        flow_control: from_flow_control(settings.flow_control)?,
+        read_timeout: Duration::from_millis(settings.read_timeout_ms),
+        write_timeout: Duration::from_millis(settings.write_timeout_ms),
+        exclusive: settings.exclusive,
+        byte_escapes: settings.byte_escapes,
+    })
+}
+
+fn from_data_bits(data_bits: u32) -> Result<serialport::DataBits, &'static str> {
+    match data_bits {
+        5 => Ok(serialport::DataBits::Five),
+        6 => Ok(serialport::DataBits::Six),
+        7 => Ok(serialport::DataBits::Seven),
+        8 => Ok(serialport::DataBits::Eight),
+        _ => Err("Data bits must be from 5 to 8"),
+    }
+}
+
+fn from_parity(parity: i32) -> Result<serialport::Parity, &'static str> {
+    match Parity::from_i32(parity) {
+        Some(Parity::None) => Ok(serialport::Parity::None),
+        Some(Parity::Odd) => Ok(serialport::Parity::Odd),
+        Some(Parity::Even) => Ok(serialport::Parity::Even),
+        None => Err("Unknown parity"),
+    }
+}
+
+fn from_stop_bits(stop_bits: i32) -> Result<serialport::StopBits, &'static str> {
+    match StopBits::from_i32(stop_bits) {
+        Some(StopBits::One) => Ok(serialport::StopBits::One),
+        Some(StopBits::Two) => Ok(serialport::StopBits::Two),
+        None => Err("Unknown stop bits"),
+    }
+}
+
+fn from_flow_control(flow_control: i32) -> Result<serialport::FlowControl, &'static str> {
+    match FlowControl::from_i32(flow_control) {
+        Some(FlowControl::None) => Ok(serialport::FlowControl::None),
+        Some(FlowControl::Software) => Ok(serialport::FlowControl::Software),
+        Some(FlowControl::Hardware) => Ok(serialport::FlowControl::Hardware),
+        None => Err("Unknown flow control"),
+    }
+}
+
+/// Reads the changes of a `SetConfig` request, its zero and unset fields being left out.
+fn from_set_config(request: SetConfigReq) -> Result<settings::ConfigChange, &'static str> {
+    Ok(settings::ConfigChange {
+        baudrate: match request.baudrate {
+            0 => None,
+            baudrate => Some(baudrate),
+        },
+        data_bits: match request.data_bits {
+            0 => None,
+            data_bits => Some(from_data_bits(data_bits)?),
+        },
+        parity: request.parity.map(|parity| from_parity(parity.value)).transpose()?,
+        stop_bits: request.stop_bits.map(|stop_bits| from_stop_bits(stop_bits.value)).transpose()?,
+        flow_control: request.flow_control.map(|flow_control| from_flow_control(flow_control.value)).transpose()?,
+    })
+}
+
+/// The `Error` of a typed `SerialError`, without looking at its message.
+fn from_serial_error(e: SerialError) -> Error {
+    let code = match &e {
+        SerialError::NoPortOpen => ErrorCode::ErrorNoPortOpen,
+        SerialError::Timeout(_) => ErrorCode::ErrorTimeout,
+        SerialError::Io(_) => ErrorCode::ErrorIo,
+        SerialError::AlreadyOpen(_) | SerialError::InvalidInput(_) => ErrorCode::ErrorUnknown,
+    };
+
+    Error {
+        code: code as i32,
+        message: e.to_string(),
+    }
+}
+
+/// Time `CancelWrite` waits for the write to reach a chunk boundary.
+const CANCEL_WRITE_WAIT: Duration = Duration::from_secs(5);
+
+/// Shared by the chunked `SendOnce` calls and `CancelWrite`.
+#[derive(Default)]
+struct WriteCancel {
+    // Number of chunked writes running.
+    in_flight: usize,
+    // Set by `CancelWrite`, answered by the first write to stop.
+    requested: Option<oneshot::Sender<()>>,
+}
+
+/// Counts a chunked write as running while alive, even if the request is dropped.
+struct ChunkedWrite<'a>(&'a Mutex<WriteCancel>);
+
+impl<'a> ChunkedWrite<'a> {
+    fn start(write_cancel: &'a Mutex<WriteCancel>) -> ChunkedWrite<'a> {
+        write_cancel.lock().unwrap().in_flight += 1;
+        ChunkedWrite(write_cancel)
+    }
+
+    /// Acknowledges a pending cancel, returning if there was one.
+    fn acknowledge_cancel(&self) -> bool {
+        match self.0.lock().unwrap().requested.take() {
+            Some(requested) => requested.send(()).is_ok(),
+            None => false,
+        }
+    }
+}
+
+impl<'a> Drop for ChunkedWrite<'a> {
+    fn drop(&mut self) {
+        let mut write_cancel = self.0.lock().unwrap();
+        write_cancel.in_flight -= 1;
+        if write_cancel.in_flight == 0 {
+            // Tells a late `CancelWrite` that there is nothing left to stop.
+            write_cancel.requested = None;
+        }
+    }
+}
+
+/// Sends `message` in chunks of `chunk_size` bytes, releasing the port between chunks.
+///
+/// Reads can then interleave with a long write on a slow link, but so can other
+/// writes: protocols needing atomic writes must not use it. `CancelWrite` stops the
+/// write at the next chunk boundary.
+///
+/// # Returns
+///
+/// The informative message or the error, and the time the writes took.
+async fn send_in_chunks(
+    port: &SharedPort,
+    write_cancel: &Mutex<WriteCancel>,
+    message: &str,
+    chunk_size: usize,
+    timeout: Duration,
+) -> (Result<String, Error>, Duration) {
+    let write = ChunkedWrite::start(write_cancel);
+
+    // Encoded as a whole, the codecs (CRC, framing, ...) applying to the full message.
+    let message = message.to_string();
+    let output = port.lock().await.encode_message(&message);
+    let mut elapsed = Duration::from_millis(0);
+
+    for (i, chunk) in output.chunks(chunk_size).enumerate() {
+        if write.acknowledge_cancel() {
+            let discarded = port.lock().await.discard_output();
+            let error = Error {
+                code: ErrorCode::ErrorUnknown as i32,
+                message: format!("Write cancelled (after {} of {} bytes), {}", i * chunk_size, output.len(), discarded.content),
+            };
+            return (Err(error), elapsed);
+        }
+
+        let chunk = chunk.to_vec();
+        let (result, chunk_elapsed) = {
+            let mut unlocked_port = port.lock().await;
+            let result = unlocked_port.try_send_encoded_within(&chunk, timeout).await;
+            (result, unlocked_port.last_io_elapsed())
+        };
+        elapsed += chunk_elapsed;
+
+        if let Err(e) = result {
+            // A chunk stuck until the write timeout also stops the write.
+            write.acknowledge_cancel();
+            let mut error = from_serial_error(e);
+            error.message = format!("{} (after {} of {} bytes)", error.message, i * chunk_size, output.len());
+            return (Err(error), elapsed);
+        }
+
+        // Lets the requests waiting for the port in.
+        let _ = tokio::task::yield_now().await;
+    }
+
+    (Ok(format!("Request sent in {} chunks", output.len().div_ceil(chunk_size))), elapsed)
+}
+
+// The port, locked by a request for as long as it uses it, the awaits of the
+// reads and writes included: the other requests are served meanwhile.
+type SharedPort = Arc<tokio::sync::Mutex<SerialPort>>;
+
+// When the ports have been listed, their names and their descriptions.
+type PortList = (Instant, Vec<String>, Vec<usb::PortDescription>);
+
+pub struct MySerialComService {
+    // Arc and Mutex to be able to safely share the port accross tasks.
+    port: SharedPort,
+    // Last enumeration of the ports, with when it has been done.
+    port_list_cache: Mutex<Option<PortList>>,
+    port_list_ttl: Duration,
+    event_watchers: EventWatchers,
+    write_cancel: Mutex<WriteCancel>,
+    // The ports the clients may open, any if `None`.
+    allowlist: Option<allowlist::PortAllowlist>,
+}
+
+impl MySerialComService {
+    /// Returns the error refusing a port not allowed by `--allow-port` and `--allow-usb`.
+    fn refuse_port(&self, port_path: &str) -> Option<Status> {
+        refuse_port(self.allowlist.as_ref(), port_path)
+    }
+}
+
+/// Returns the error refusing a port not in `allowlist`, if any.
+fn refuse_port(allowlist: Option<&allowlist::PortAllowlist>, port_path: &str) -> Option<Status> {
+    match allowlist {
+        Some(allowlist) if !allowlist.allows(port_path) => {
+            warn!(port = %port_path, "Refused to open the port, not allowed");
+            Some(Status::permission_denied(format!("The port '{}' is not allowed", port_path)))
+        }
+        _ => None,
+    }
+}
+
+/// Events queued for a slow `WatchEvents` client, above which new ones are dropped.
+const EVENTS_CHANNEL_SIZE: usize = 64;
+
+// One sender per `WatchEvents` call, fed by the event hook of the port.
+type EventWatchers = Arc<Mutex<Vec<mpsc::Sender<Result<PortEventRep, Status>>>>>;
+
+/// Reads queued for a slow `StreamRead` client, above which the reads wait.
+const STREAM_READ_CHANNEL_SIZE: usize = 16;
+
+/// Pause between two reads of a `StreamRead`, for the other requests to get the port lock.
+const STREAM_READ_PAUSE_MS: u64 = 1;
+
+/// Reads from `port` for a `StreamRead` client until the port is closed, a read
+/// fails, or the client goes away.
+async fn stream_reads(port: &SharedPort, max_bytes: u32, reads: &mpsc::Sender<Result<ReadOnceRep, Status>>) {
+    while !reads.is_closed() {
+        let (outcome, elapsed_us) = {
+            let mut unlocked_port = port.lock().await;
+
+            let max_bytes = match max_bytes {
+                0 => unlocked_port.read_buffer_size(),
+                max_bytes => max_bytes as usize,
+            };
+
+            (unlocked_port.try_read_n_bytes(max_bytes).await, unlocked_port.last_io_elapsed().as_micros() as u64)
+        };
+
+        let (outcome, last) = match outcome {
+            Ok(read) => (read_once_rep::Outcome::Success(ReadSuccess {
+                content: String::from_utf8_lossy(&read).to_string(),
+                bytes: read,
+            }), false),
+            Err(SerialError::Timeout(_)) => {
+                tokio::time::sleep(Duration::from_millis(STREAM_READ_PAUSE_MS)).await;
+                continue;
+            }
+            Err(SerialError::NoPortOpen) => return,
+            Err(e) => (read_once_rep::Outcome::Error(from_serial_error(e)), true),
+        };
+
+        let reply = ReadOnceRep {
+            outcome: Some(outcome),
+            elapsed_us,
+        };
+        if reads.send(Ok(reply)).await.is_err() || last {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(STREAM_READ_PAUSE_MS)).await;
+    }
+}
+
+/// Replies queued for a slow `Session` client, above which its requests are no
+/// longer taken, nor the port read.
+const SESSION_CHANNEL_SIZE: usize = 16;
+
+/// Interval at which a `Session` checks for bytes to read.
+const SESSION_POLL_MS: u64 = 5;
+
+/// Writes the requests of a `Session` client, and replies the bytes read in between,
+/// until the client ends its stream or goes away.
+///
+/// The port is only locked for a write, or for a read of bytes already received,
+/// leaving it to the other requests the rest of the time.
+async fn run_session(
+    port: SharedPort,
+    mut requests: impl tokio_stream::Stream<Item = Result<SessionReq, Status>> + Unpin,
+    replies: mpsc::Sender<Result<SessionRep, Status>>,
+) {
+    use tokio_stream::StreamExt;
+
+    let mut polls = tokio::time::interval(Duration::from_millis(SESSION_POLL_MS));
+
+    loop {
+        let event = tokio::select! {
+            request = requests.next() => match request {
+                Some(Ok(request)) => session_rep::Event::Sent(session_write(&port, &request.content).await),
+                _ => return,
+            },
+            _ = polls.tick() => match session_read(&port).await {
+                Some(reply) => session_rep::Event::Read(reply),
+                None => continue,
+            },
+        };
+
+        if replies.send(Ok(SessionRep { event: Some(event) })).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn session_write(port: &SharedPort, content: &str) -> SendOnceRep {
+    let mut unlocked_port = port.lock().await;
+
+    let outcome = match unlocked_port.try_send_once(content).await {
+        Ok(content) => send_once_rep::Outcome::Success(SendSuccess { content, bytes: vec![] }),
+        Err(e) => send_once_rep::Outcome::Error(from_serial_error(e)),
+    };
+
+    SendOnceRep {
+        outcome: Some(outcome),
+        elapsed_us: unlocked_port.last_io_elapsed().as_micros() as u64,
+    }
+}
+
+/// Reads the bytes already received, if any, without waiting for more.
+async fn session_read(port: &SharedPort) -> Option<ReadOnceRep> {
+    let mut unlocked_port = port.lock().await;
+
+    let available = unlocked_port.bytes_available();
+    if !available.success || available.content == "0" {
+        return None;
+    }
+
+    let max_bytes = unlocked_port.read_buffer_size();
+    let outcome = match unlocked_port.try_read_n_bytes(max_bytes).await {
+        Ok(read) => read_once_rep::Outcome::Success(ReadSuccess {
+            content: String::from_utf8_lossy(&read).to_string(),
+            bytes: read,
+        }),
+        Err(e) => read_once_rep::Outcome::Error(from_serial_error(e)),
+    };
+
+    Some(ReadOnceRep {
+        outcome: Some(outcome),
+        elapsed_us: unlocked_port.last_io_elapsed().as_micros() as u64,
+    })
+}
+
+/// Forwards the events of the port to every `WatchEvents` client.
+fn watch_port_events(port: &mut SerialPort, event_watchers: &EventWatchers) {
+    let event_watchers = Arc::clone(event_watchers);
+
+    port.on_event(Box::new(move |event| {
+        let reply = PortEventRep {
+            kind: match event.kind {
+                events::PortEventKind::Opened => PortEventKind::PortOpened,
+                events::PortEventKind::Closed => PortEventKind::PortClosed,
+                events::PortEventKind::Disconnected => PortEventKind::PortDisconnected,
+            } as i32,
+            path: event.path,
+            reason: event.reason,
+        };
+
+        broadcast(&event_watchers, reply);
+    }));
+}
+
+/// Reports `serving` for the whole server, the empty service name, and for
+/// `SerialComService`.
+async fn set_serving(health_reporter: &mut tonic_health::server::HealthReporter, serving: bool) {
+    let status = if serving { ServingStatus::Serving } else { ServingStatus::NotServing };
+
+    health_reporter.set_service_status("", status).await;
+    health_reporter.set_service_status(
+        <SerialComServiceServer<MySerialComService> as NamedService>::NAME,
+        status,
+    ).await;
+}
+
+/// Queues the health changes of the port: not serving once its device is gone,
+/// serving again once a port is opened. A port closed on request changes nothing.
+fn watch_port_health(port: &mut SerialPort, health_changes: mpsc::UnboundedSender<bool>) {
+    port.on_event(Box::new(move |event| {
+        let serving = match event.kind {
+            events::PortEventKind::Opened => true,
+            events::PortEventKind::Disconnected => false,
+            events::PortEventKind::Closed => return,
+        };
+
+        // Never waits, the hook being called with the port locked.
+        let _ = health_changes.send(serving);
+    }));
+}
+
+/// Queues `reply` for every `WatchEvents` client, forgetting the ones that went away.
+fn broadcast(event_watchers: &EventWatchers, reply: PortEventRep) {
+    event_watchers.lock().unwrap().retain(|watcher| {
+        !matches!(watcher.try_send(Ok(reply.clone())), Err(mpsc::error::TrySendError::Closed(_)))
+    });
+}
+
+/// Sends a keepalive to every `WatchEvents` client each `interval`.
+///
+/// A stream silently dropped by a NAT or a load balancer then fails to be written,
+/// and is closed instead of being kept forever.
+async fn send_keepalives(event_watchers: EventWatchers, interval: Duration) {
+    let mut ticks = tokio::time::interval(interval);
+
+    loop {
+        ticks.tick().await;
+
+        let keepalive = PortEventRep {
+            kind: PortEventKind::PortKeepalive as i32,
+            path: String::new(),
+            reason: String::new(),
+        };
+        broadcast(&event_watchers, keepalive);
+    }
+}
+
+#[tonic::async_trait]
+impl SerialComService for MySerialComService {
+
+    async fn ping(
+            &self,
+            _request: Request<SerialPingReq>,
+        ) -> Result<Response<SerialPingRep>, Status> {
+
+            info!("Got a ping request.");
+
+            let reply = SerialPingRep {
+                content: "Pong!".to_string(),
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn get_port_list(
+            &self,
+            request: Request<PortListReq>,
+        ) -> Result<Response<PortListRep>, Status> {
+
+            info!("Got a GetPortList request.");
+
+            let request = request.into_inner();
+
+            // The enumeration can be slow, and GUIs tend to poll it.
+            let cached = match &*self.port_list_cache.lock().unwrap() {
+                Some((listed_at, port_names, descriptions))
+                    if !request.force_refresh && listed_at.elapsed() < self.port_list_ttl =>
+                {
+                    Some((port_names.clone(), descriptions.clone()))
+                }
+                _ => None,
+            };
+            let (port_names, descriptions) = match cached {
+                Some(list) => list,
+                None => {
+                    // Enumerated off the runtime workers, and without the cache locked.
+                    let (port_names, descriptions) = tokio::task::spawn_blocking(|| {
+                        (SerialPort::get_available_port_names(), SerialPort::get_available_ports_detailed())
+                    })
+                    .await
+                    .map_err(|e| Status::internal(format!("Could not list the ports: {}", e)))?;
+                    *self.port_list_cache.lock().unwrap() = Some((Instant::now(), port_names.clone(), descriptions.clone()));
+                    (port_names, descriptions)
+                }
+            };
+
+            let reply = PortListRep {
+                ports: port_names,
+                descriptions: descriptions.into_iter().map(to_port_description).collect(),
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn open_port(
+            &self,
+            request: Request<OpenPortReq>,
+        ) -> Result<Response<OpenPortRep>, Status> {
+
+            let request = request.into_inner();
+            info!(port = %request.port, baudrate = request.baudrate, "Got a OpenPort request.");
+
+            if let Some(status) = self.refuse_port(&request.port) {
+                return Err(status);
+            }
+            let flow_control = from_flow_control(request.flow_control).map_err(Status::invalid_argument)?;
+
+            // Locked for the whole open, the settle delay included: a concurrent open
+            // waits, then sees the port already open.
+            let port_path = request.port.clone();
+            let resp = {
+                let mut unlocked_port = self.port.lock().await;
+                let config = config::SerialConfig { flow_control, ..config::SerialConfig::new(request.baudrate) };
+                unlocked_port.open_configured(
+                    &request.port,
+                    &config,
+                    Duration::from_millis(request.post_open_delay_ms as u64),
+                ).await
+            };
+            if !resp.success {
+                warn!(port = %port_path, "Could not open the port: {}", resp.content);
+            }
+
+            let reply = OpenPortRep {
+                success: resp.success,
+                content: resp.content,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn close_port(
+            &self,
+            _request: Request<ClosePortReq>,
+        ) -> Result<Response<ClosePortRep>, Status> {
+
+            info!("Got a ClosePort request.");
+
+            let resp = self.port.lock().await.close_port().await;
+
+            let reply = ClosePortRep {
+                success: resp.success,
+                content: resp.content,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn send_once(
+            &self,
+            request: Request<SendOnceReq>,
+        ) -> Result<Response<SendOnceRep>, Status> {
+
+            info!("Got a SendOnce request.");
+
+            let request = request.into_inner();
+
+            if request.dry_run {
+                let output = self.port.lock().await.encode_message(&request.content);
+
+                let reply = SendOnceRep {
+                    outcome: Some(send_once_rep::Outcome::Success(SendSuccess {
+                        content: format!("Dry run, would send {} bytes: {}", output.len(), to_hex(&output)),
+                        bytes: output,
+                    })),
+                    elapsed_us: 0,
+                };
+
+                return Ok(Response::new(reply));
+            }
+
+            let timeout = Duration::from_millis(request.timeout_ms as u64);
+            let (result, elapsed) = if request.chunk_size == 0 {
+                {
+                    let mut unlocked_port = self.port.lock().await;
+                    let result = unlocked_port.try_send_once_within(&request.content, timeout).await.map_err(from_serial_error);
+                    (result, unlocked_port.last_io_elapsed())
+                }
+            } else {
+                send_in_chunks(&self.port, &self.write_cancel, &request.content, request.chunk_size as usize, timeout).await
+            };
+
+            let outcome = match result {
+                Ok(content) => send_once_rep::Outcome::Success(SendSuccess { content, bytes: vec![] }),
+                Err(error) => {
+                    warn!("SendOnce failed: {}", error.message);
+                    send_once_rep::Outcome::Error(error)
+                }
+            };
+
+            let reply = SendOnceRep {
+                outcome: Some(outcome),
+                elapsed_us: elapsed.as_micros() as u64,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn read_once(
+            &self,
+            request: Request<ReadOnceReq>,
+        ) -> Result<Response<ReadOnceRep>, Status> {
+
+            info!("Got a ReadOnce request.");
+
+            let request = request.into_inner();
+            let format = match ReadFormat::from_i32(request.format) {
+                Some(format) => format,
+                None => return Err(Status::invalid_argument("Unknown read format")),
+            };
+
+            let reply = {
+                let mut unlocked_port = self.port.lock().await;
+                let max_bytes = match request.max_bytes {
+                    0 => unlocked_port.read_buffer_size(),
+                    max_bytes => max_bytes as usize,
+                };
+
+                let timeout = Duration::from_millis(request.timeout_ms as u64);
+                let outcome = match unlocked_port.try_read_n_bytes_within(max_bytes, timeout).await {
+                    Ok(read) => read_once_rep::Outcome::Success(ReadSuccess {
+                        content: match format {
+                            ReadFormat::Text => String::from_utf8_lossy(&read).to_string(),
+                            ReadFormat::Hexdump => hexdump(&read),
+                        },
+                        bytes: read,
+                    }),
+                    Err(e) => {
+                        // Nothing to read is routine for a polling client.
+                        if !matches!(e, SerialError::Timeout(_)) {
+                            warn!("ReadOnce failed: {}", e);
+                        }
+                        read_once_rep::Outcome::Error(from_serial_error(e))
+                    }
+                };
+
+                ReadOnceRep {
+                    outcome: Some(outcome),
+                    elapsed_us: unlocked_port.last_io_elapsed().as_micros() as u64,
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn send_modbus_rtu(
+            &self,
+            request: Request<SendModbusRtuReq>,
+        ) -> Result<Response<SendModbusRtuRep>, Status> {
+
+            info!("Got a SendModbusRtu request.");
+
+            let request = request.into_inner();
+
+            let resp = self.port.lock().await.send_modbus_rtu(&request.frame).await;
+
+            let status = match resp.status {
+                modbus::ModbusStatus::Ok => ModbusStatus::ModbusOk,
+                modbus::ModbusStatus::NoPortOpen => ModbusStatus::ModbusNoPortOpen,
+                modbus::ModbusStatus::InvalidFrame => ModbusStatus::ModbusInvalidFrame,
+                modbus::ModbusStatus::Timeout => ModbusStatus::ModbusTimeout,
+                modbus::ModbusStatus::CrcMismatch => ModbusStatus::ModbusCrcMismatch,
+                modbus::ModbusStatus::IoError => ModbusStatus::ModbusIoError,
+            };
+
+            let reply = SendModbusRtuRep {
+                success: resp.status == modbus::ModbusStatus::Ok,
+                content: resp.content,
+                status: status as i32,
+                payload: resp.payload,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn get_timeouts(
+            &self,
+            _request: Request<GetTimeoutsReq>,
+        ) -> Result<Response<GetTimeoutsRep>, Status> {
+
+            info!("Got a GetTimeouts request.");
+
+            let reply = {
+                let unlocked_port = self.port.lock().await;
+                match unlocked_port.timeouts() {
+                    Some(timeouts) => GetTimeoutsRep {
+                        success: true,
+                        content: "Timeouts of the open port".to_string(),
+                        open_timeout_ms: timeouts.open.as_millis() as u64,
+                        read_timeout_ms: timeouts.read.as_millis() as u64,
+                        write_timeout_ms: timeouts.write.as_millis() as u64,
+                    },
+                    None => GetTimeoutsRep {
+                        success: false,
+                        content: "No port is currently open".to_string(),
+                        ..Default::default()
+                    },
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn set_repeater(
+            &self,
+            request: Request<SetRepeaterReq>,
+        ) -> Result<Response<SetRepeaterRep>, Status> {
+
+            info!("Got a SetRepeater request.");
+
+            let request = request.into_inner();
+
+            let resp = self.port.lock().await.set_repeater(request.enabled).await;
+
+            let reply = SetRepeaterRep {
+                success: resp.success,
+                content: resp.content,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn send_with_acks(
+            &self,
+            request: Request<SendWithAcksReq>,
+        ) -> Result<Response<SendWithAcksRep>, Status> {
+
+            info!("Got a SendWithAcks request.");
+
+            let request = request.into_inner();
+
+            let resp = {
+                let mut unlocked_port = self.port.lock().await;
+                unlocked_port.send_with_acks(
+                    &request.payload,
+                    request.expected_ack as u8,
+                    Duration::from_millis(request.ack_timeout_ms),
+                ).await
+            };
+
+            let reply = SendWithAcksRep {
+                success: resp.success,
+                content: resp.content,
+                acked: resp.acked as u64,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn auto_baud(
+            &self,
+            request: Request<AutoBaudReq>,
+        ) -> Result<Response<AutoBaudRep>, Status> {
+
+            info!("Got a AutoBaud request.");
+
+            let request = request.into_inner();
+
+            let method = match AutoBaudMethod::from_i32(request.method) {
+                Some(AutoBaudMethod::AutobaudHeuristic) => autobaud::AutoBaudMethod::Heuristic,
+                Some(AutoBaudMethod::AutobaudTiming) => autobaud::AutoBaudMethod::Timing,
+                None => return Err(Status::invalid_argument("Unknown autobaud method")),
+            };
+
+            let resp = self.port.lock().await.auto_baud(method, Duration::from_millis(request.window_ms)).await;
+
+            let reply = AutoBaudRep {
+                success: resp.success,
+                content: resp.content,
+                baudrate: resp.baudrate,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn read_lines(
+            &self,
+            request: Request<ReadLinesReq>,
+        ) -> Result<Response<ReadLinesRep>, Status> {
+
+            info!("Got a ReadLines request.");
+
+            let request = request.into_inner();
+
+            let resp = {
+                let mut unlocked_port = self.port.lock().await;
+                unlocked_port.read_lines(
+                    request.max_lines as usize,
+                    Duration::from_millis(request.timeout_ms),
+                ).await
+            };
+
+            let reply = ReadLinesRep {
+                success: resp.success,
+                content: resp.content,
+                lines: resp.lines,
+                partial_line: resp.partial_line,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn read_until(
+            &self,
+            request: Request<ReadUntilReq>,
+        ) -> Result<Response<ReadUntilRep>, Status> {
+
+            info!("Got a ReadUntil request.");
+
+            let request = request.into_inner();
+
+            let overflow = match OverflowPolicy::from_i32(request.overflow) {
+                Some(OverflowPolicy::OverflowTruncate) => delimited::OverflowPolicy::Truncate,
+                Some(OverflowPolicy::OverflowError) => delimited::OverflowPolicy::Error,
+                Some(OverflowPolicy::OverflowDiscard) => delimited::OverflowPolicy::Discard,
+                None => return Err(Status::invalid_argument("Unknown overflow policy")),
+            };
+
+            let resp = {
+                let mut unlocked_port = self.port.lock().await;
+                unlocked_port.read_until_with(
+                    request.delimiter as u8,
+                    request.max_len as usize,
+                    overflow,
+                ).await
+            };
+
+            let reply = ReadUntilRep {
+                success: resp.success,
+                content: resp.content,
+                truncated: resp.truncated,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn set_codecs(
+            &self,
+            request: Request<SetCodecsReq>,
+        ) -> Result<Response<SetCodecsRep>, Status> {
+
+            info!("Got a SetCodecs request.");
+
+            let request = request.into_inner();
+
+            let reply = {
+                let mut unlocked_port = self.port.lock().await;
+                let resp = unlocked_port.set_codecs(&request.codecs);
+
+                SetCodecsRep {
+                    success: resp.success,
+                    content: resp.content,
+                    codecs: unlocked_port.codec_names(),
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn reset_stats(
+            &self,
+            _request: Request<ResetStatsReq>,
+        ) -> Result<Response<ResetStatsRep>, Status> {
+
+            info!("Got a ResetStats request.");
+
+            let cleared = self.port.lock().await.reset_stats();
+
+            let reply = ResetStatsRep {
+                success: true,
+                content: "Statistics reset".to_string(),
+                bytes_sent: cleared.bytes_sent,
+                bytes_received: cleared.bytes_received,
+                read_errors: cleared.read_errors,
+                write_errors: cleared.write_errors,
+                timeouts: cleared.timeouts,
+                errors: cleared.errors,
+                window_ms: cleared.since.elapsed().as_millis() as u64,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn query_capabilities(
+            &self,
+            _request: Request<QueryCapabilitiesReq>,
+        ) -> Result<Response<QueryCapabilitiesRep>, Status> {
+
+            info!("Got a QueryCapabilities request.");
+
+            let reply = {
+                let unlocked_port = self.port.lock().await;
+                let supported: Vec<i32> = unlocked_port
+                    .capabilities()
+                    .into_iter()
+                    .map(|supported| match supported {
+                        capability::Capability::CustomBaudrate => Capability::CustomBaudrate,
+                        capability::Capability::HardwareFlowControl => Capability::HardwareFlowControl,
+                        capability::Capability::ExclusiveLock => Capability::ExclusiveLock,
+                        capability::Capability::Break => Capability::Break,
+                    } as i32)
+                    .collect();
+
+                QueryCapabilitiesRep {
+                    success: true,
+                    content: format!("{} capabilities supported", supported.len()),
+                    supported,
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn set_byte_trace(
+            &self,
+            request: Request<SetByteTraceReq>,
+        ) -> Result<Response<SetByteTraceRep>, Status> {
+
+            info!("Got a SetByteTrace request.");
+
+            let request = request.into_inner();
+
+            let reply = {
+                let mut unlocked_port = self.port.lock().await;
+                let content = if request.enabled {
+                    unlocked_port.start_byte_trace(request.capacity as usize);
+                    "Byte trace started".to_string()
+                } else {
+                    unlocked_port.stop_byte_trace();
+                    "Byte trace stopped".to_string()
+                };
+
+                SetByteTraceRep {
+                    success: true,
+                    content,
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn get_byte_trace(
+            &self,
+            request: Request<GetByteTraceReq>,
+        ) -> Result<Response<GetByteTraceRep>, Status> {
+
+            info!("Got a GetByteTrace request.");
+
+            let request = request.into_inner();
+
+            let reply = {
+                let mut unlocked_port = self.port.lock().await;
+                if !unlocked_port.is_tracing_bytes() {
+                    let reply = GetByteTraceRep {
+                        success: false,
+                        content: "No byte trace is running".to_string(),
+                        bytes: vec![],
+                        dropped: 0,
+                    };
+
+                    return Ok(Response::new(reply));
+                }
+
+                let (bytes, dropped) = unlocked_port.byte_trace(request.clear);
+
+                GetByteTraceRep {
+                    success: true,
+                    content: format!("{} bytes traced", bytes.len()),
+                    bytes: bytes
+                        .into_iter()
+                        .map(|traced| TracedByte {
+                            timestamp_us: traced.timestamp.as_micros() as u64,
+                            byte: traced.byte as u32,
+                            direction: match traced.direction {
+                                trace::Direction::Sent => ByteDirection::ByteSent,
+                                trace::Direction::Received => ByteDirection::ByteReceived,
+                            } as i32,
+                        })
+                        .collect(),
+                    dropped,
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn swap_port(
+            &self,
+            request: Request<SwapPortReq>,
+        ) -> Result<Response<SwapPortRep>, Status> {
+
+            info!("Got a SwapPort request.");
+
+            let request = request.into_inner();
+            if let Some(status) = self.refuse_port(&request.port) {
+                return Err(status);
+            }
+
+            let resp = self.port.lock().await.swap_port(&request.port, request.baudrate).await;
+
+            let reply = SwapPortRep {
+                success: resp.success,
+                content: resp.content,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    type WatchEventsStream = ReceiverStream<Result<PortEventRep, Status>>;
+
+    async fn watch_events(
+            &self,
+            _request: Request<WatchEventsReq>,
+        ) -> Result<Response<Self::WatchEventsStream>, Status> {
+
+            info!("Got a WatchEvents request.");
+
+            let (watcher, events) = mpsc::channel(EVENTS_CHANNEL_SIZE);
+            self.event_watchers.lock().unwrap().push(watcher);
+
+            Ok(Response::new(ReceiverStream::new(events)))
+    }
+
+    async fn cancel_write(
+            &self,
+            _request: Request<CancelWriteReq>,
+        ) -> Result<Response<CancelWriteRep>, Status> {
+
+            info!("Got a CancelWrite request.");
+
+            // Without taking the port lock, held by a stuck write.
+            let stopped = {
+                let mut write_cancel = self.write_cancel.lock().unwrap();
+                if write_cancel.in_flight == 0 {
+                    None
+                } else {
+                    let (requested, stopped) = oneshot::channel();
+                    write_cancel.requested = Some(requested);
+                    Some(stopped)
+                }
+            };
+
+            let (success, content) = match stopped {
+                None => (false, "No chunked write in progress, a write not sent in chunks can not be cancelled".to_string()),
+                Some(stopped) => match tokio::time::timeout(CANCEL_WRITE_WAIT, stopped).await {
+                    Ok(Ok(())) => (true, "Write cancelled".to_string()),
+                    Ok(Err(_)) => (false, "The write ended before reaching a chunk boundary".to_string()),
+                    Err(_) => (false, format!("The write did not reach a chunk boundary within {}ms", CANCEL_WRITE_WAIT.as_millis())),
+                },
+            };
+
+            let reply = CancelWriteRep {
+                success,
+                content,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn read_key_value(
+            &self,
+            request: Request<ReadKeyValueReq>,
+        ) -> Result<Response<ReadKeyValueRep>, Status> {
+
+            info!("Got a ReadKeyValue request.");
+
+            let request = request.into_inner();
+
+            let resp = {
+                let mut unlocked_port = self.port.lock().await;
+                unlocked_port.read_kv(
+                    &request.terminator,
+                    &request.pair_sep,
+                    &request.kv_sep,
+                    Duration::from_millis(request.timeout_ms),
+                ).await
+            };
+
+            let reply = ReadKeyValueRep {
+                success: resp.success,
+                content: resp.content,
+                pairs: resp.pairs,
+                skipped: resp.skipped as u32,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn set_exclusive(
+            &self,
+            request: Request<SetExclusiveReq>,
+        ) -> Result<Response<SetExclusiveRep>, Status> {
+
+            info!("Got a SetExclusive request.");
+
+            let request = request.into_inner();
+
+            let reply = {
+                let mut unlocked_port = self.port.lock().await;
+                let resp = unlocked_port.set_exclusive(request.exclusive);
+
+                SetExclusiveRep {
+                    success: resp.success,
+                    content: resp.content,
+                    exclusive: unlocked_port.is_exclusive(),
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn set_write_coalescing(
+            &self,
+            request: Request<SetWriteCoalescingReq>,
+        ) -> Result<Response<SetWriteCoalescingRep>, Status> {
+
+            info!("Got a SetWriteCoalescing request.");
+
+            let request = request.into_inner();
+
+            let reply = {
+                let mut unlocked_port = self.port.lock().await;
+                let resp = unlocked_port.set_write_coalescing(
+                    request.max_writes as usize,
+                    Duration::from_millis(request.max_delay_ms),
+                ).await;
+
+                SetWriteCoalescingRep {
+                    success: resp.success,
+                    content: resp.content,
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn flush(
+            &self,
+            _request: Request<FlushReq>,
+        ) -> Result<Response<FlushRep>, Status> {
+
+            info!("Got a Flush request.");
+
+            let resp = self.port.lock().await.flush().await;
+
+            let reply = FlushRep {
+                success: resp.success,
+                content: resp.content,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn open_by_usb_index(
+            &self,
+            request: Request<OpenByUsbIndexReq>,
+        ) -> Result<Response<OpenByUsbIndexRep>, Status> {
+
+            info!("Got a OpenByUsbIndex request.");
+
+            let request = request.into_inner();
+
+            // Resolved once, for the port checked to be the one opened. The enumeration
+            // reads sysfs, off the runtime workers.
+            let (vid, pid, index) = (request.vid as u16, request.pid as u16, request.index as usize);
+            let port_path = tokio::task::spawn_blocking(move || usb::usb_port_at(vid, pid, index))
+                .await
+                .map_err(|e| Status::internal(format!("Could not list the ports: {}", e)))?;
+            let resp = match port_path {
+                Ok(port_path) => {
+                    if let Some(status) = self.refuse_port(&port_path) {
+                        return Err(status);
+                    }
+                    self.port.lock().await.open_port(&port_path, request.baudrate).await
+                }
+                Err(content) => SerialPortResponse { success: false, content },
+            };
+
+            let reply = OpenByUsbIndexRep {
+                success: resp.success,
+                content: resp.content,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn get_last_payloads(
+            &self,
+            _request: Request<GetLastPayloadsReq>,
+        ) -> Result<Response<GetLastPayloadsRep>, Status> {
+
+            info!("Got a GetLastPayloads request.");
+
+            let reply = {
+                let unlocked_port = self.port.lock().await;
+                let sent = unlocked_port.last_sent().map(to_payload);
+                let received = unlocked_port.last_received().map(to_payload);
+
+                GetLastPayloadsRep {
+                    success: true,
+                    content: format!(
+                        "Last sent {} bytes, last received {} bytes",
+                        sent.as_ref().map_or(0, |payload| payload.bytes.len()),
+                        received.as_ref().map_or(0, |payload| payload.bytes.len()),
+                    ),
+                    sent,
+                    received,
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn read_samples(
+            &self,
+            request: Request<ReadSamplesReq>,
+        ) -> Result<Response<ReadSamplesRep>, Status> {
+
+            info!("Got a ReadSamples request.");
+
+            let request = request.into_inner();
+
+            let sample_type = match SampleType::from_i32(request.sample_type) {
+                Some(SampleType::SampleU8) => samples::SampleType::U8,
+                Some(SampleType::SampleI8) => samples::SampleType::I8,
+                Some(SampleType::SampleU16) => samples::SampleType::U16,
+                Some(SampleType::SampleI16) => samples::SampleType::I16,
+                Some(SampleType::SampleU32) => samples::SampleType::U32,
+                Some(SampleType::SampleI32) => samples::SampleType::I32,
+                None => return Err(Status::invalid_argument("Unknown sample type")),
+            };
+            let endianness = match Endianness::from_i32(request.endianness) {
+                Some(Endianness::EndianLittle) => samples::Endianness::Little,
+                Some(Endianness::EndianBig) => samples::Endianness::Big,
+                None => return Err(Status::invalid_argument("Unknown endianness")),
+            };
+
+            let resp = {
+                let mut unlocked_port = self.port.lock().await;
+                unlocked_port.read_samples(
+                    sample_type,
+                    endianness,
+                    request.count as usize,
+                    Duration::from_millis(request.timeout_ms),
+                ).await
+            };
+
+            let reply = ReadSamplesRep {
+                success: resp.success,
+                content: resp.content,
+                samples: resp.samples,
+                partial_sample: resp.partial_sample,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn write_then_read(
+            &self,
+            request: Request<WriteThenReadReq>,
+        ) -> Result<Response<WriteThenReadRep>, Status> {
+
+            info!("Got a WriteThenRead request.");
+
+            // The exchange can not be interrupted once started: it is bounded by the
+            // deadline of the call instead, for the client to not wait past it.
+            let call_timeout = call_timeout(&request);
+            let request = request.into_inner();
+
+            let budget = match (Duration::from_millis(request.budget_ms), call_timeout) {
+                (budget, Some(timeout)) if budget.as_nanos() == 0 || timeout < budget => {
+                    timeout.max(Duration::from_millis(1))
+                }
+                (budget, _) => budget,
+            };
+
+            let resp = self.port.lock().await.write_then_read(&request.content, budget).await;
+
+            let reply = WriteThenReadRep {
+                success: resp.success,
+                content: resp.content,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn get_port_settings(
+            &self,
+            _request: Request<GetPortSettingsReq>,
+        ) -> Result<Response<GetPortSettingsRep>, Status> {
+
+            info!("Got a GetPortSettings request.");
+
+            let reply = {
+                let unlocked_port = self.port.lock().await;
+                match unlocked_port.port_settings() {
+                    Some(settings) => GetPortSettingsRep {
+                        success: true,
+                        content: format!("Settings of {}", settings.port_path),
+                        settings: Some(to_port_settings(settings)),
+                    },
+                    None => GetPortSettingsRep {
+                        success: false,
+                        content: "No port is currently open".to_string(),
+                        settings: None,
+                    },
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn set_port_settings(
+            &self,
+            request: Request<SetPortSettingsReq>,
+        ) -> Result<Response<SetPortSettingsRep>, Status> {
+
+            info!("Got a SetPortSettings request.");
+
+            let request = request.into_inner();
+
+            let settings = match request.settings {
+                Some(settings) => from_port_settings(settings).map_err(Status::invalid_argument)?,
+                None => return Err(Status::invalid_argument("The settings are missing")),
+            };
+
+            // Same restriction as SwapPort for a new port, checked under the lock of the
+            // port it replaces.
+            let resp = {
+                let mut unlocked_port = self.port.lock().await;
+                let is_new_port = unlocked_port.port_settings()
+                    .is_some_and(|current| current.port_path != settings.port_path);
+                if is_new_port {
+                    if let Some(status) = self.refuse_port(&settings.port_path) {
+                        return Err(status);
+                    }
+                }
+
+                unlocked_port.apply_port_settings(&settings).await
+            };
+
+            let reply = SetPortSettingsRep {
+                success: resp.success,
+                content: resp.content,
+                changes: resp.changes
+                    .into_iter()
+                    .map(|change| SettingChange {
+                        field: change.field.to_string(),
+                        from: change.from,
+                        to: change.to,
+                    })
+                    .collect(),
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    type StreamReadStream = ReceiverStream<Result<ReadOnceRep, Status>>;
+
+    async fn stream_read(
+            &self,
+            request: Request<StreamReadReq>,
+        ) -> Result<Response<Self::StreamReadStream>, Status> {
+
+            info!("Got a StreamRead request.");
+
+            let request = request.into_inner();
+
+            // The reads wait on the device, and take the port lock between two requests
+            // of the other clients.
+            let port = Arc::clone(&self.port);
+            let (reads_sender, reads) = mpsc::channel(STREAM_READ_CHANNEL_SIZE);
+            tokio::spawn(async move { stream_reads(&port, request.max_bytes, &reads_sender).await });
+
+            Ok(Response::new(ReceiverStream::new(reads)))
+    }
+
+    type SessionStream = ReceiverStream<Result<SessionRep, Status>>;
+
+    async fn session(
+            &self,
+            request: Request<tonic::Streaming<SessionReq>>,
+        ) -> Result<Response<Self::SessionStream>, Status> {
+
+            info!("Got a Session request.");
+
+            let (replies_sender, replies) = mpsc::channel(SESSION_CHANNEL_SIZE);
+            tokio::spawn(run_session(Arc::clone(&self.port), request.into_inner(), replies_sender));
+
+            Ok(Response::new(ReceiverStream::new(replies)))
+    }
+
+    async fn set_config(
+            &self,
+            request: Request<SetConfigReq>,
+        ) -> Result<Response<SetConfigRep>, Status> {
+
+            info!("Got a SetConfig request.");
+
+            let request = request.into_inner();
+
+            let change = from_set_config(request).map_err(Status::invalid_argument)?;
+
+            let resp = self.port.lock().await.set_config(&change);
+
+            let reply = SetConfigRep {
+                success: resp.success,
+                content: resp.content,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn send_bytes(
+            &self,
+            request: Request<SendBytesReq>,
+        ) -> Result<Response<SendBytesRep>, Status> {
+
+            info!("Got a SendBytes request.");
+
+            let request = request.into_inner();
+
+            let resp = self.port.lock().await.send_bytes(&request.data).await;
+
+            let reply = SendBytesRep {
+                success: resp.success,
+                content: resp.content,
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn read_bytes(
+            &self,
+            request: Request<ReadBytesReq>,
+        ) -> Result<Response<ReadBytesRep>, Status> {
+
+            info!("Got a ReadBytes request.");
+
+            let request = request.into_inner();
+
+            let reply = {
+                let mut unlocked_port = self.port.lock().await;
+                let max_bytes = match request.max_bytes {
+                    0 => unlocked_port.read_buffer_size(),
+                    max_bytes => max_bytes as usize,
+                };
+
+                match unlocked_port.try_read_n_bytes(max_bytes).await {
+                    Ok(data) => ReadBytesRep { success: true, content: String::new(), data },
+                    Err(e) => ReadBytesRep { success: false, content: e.to_string(), data: vec![] },
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+    async fn get_stats(
+            &self,
+            _request: Request<GetStatsReq>,
+        ) -> Result<Response<GetStatsRep>, Status> {
+
+            info!("Got a GetStats request.");
+
+            let reply = {
+                let unlocked_port = self.port.lock().await;
+                let stats = unlocked_port.stats();
+
+                GetStatsRep {
+                    bytes_sent: stats.bytes_sent,
+                    bytes_received: stats.bytes_received,
+                    read_errors: stats.read_errors,
+                    write_errors: stats.write_errors,
+                    timeouts: stats.timeouts,
+                    errors: stats.errors.clone(),
+                    window_ms: stats.since.elapsed().as_millis() as u64,
+                }
+            };
+
+            Ok(Response::new(reply))
+    }
+
+}
+
+use structopt::StructOpt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+#[derive(StructOpt)]
+struct Cli {
+    #[structopt(help="Addr with port: 127.0.0.1:3333 for ex. Also set by the SERIAL_RPC_ADDR env var, 127.0.0.1:3333 by default.")]
+    addr: Option<String>,
+
+    #[structopt(short, long, help="Suppresses the routine output, keeping only the warnings and errors. Also set by the SERIAL_RPC_QUIET env var.")]
+    quiet: bool,
+
+    #[structopt(long, help="Serial port to open at startup. The health service reports NOT_SERVING if it can't be opened.")]
+    open: Option<String>,
+
+    #[structopt(long, help="Baudrate of the serial port opened at startup.", default_value="9600")]
+    baudrate: u32,
+
+    #[structopt(long, help="How long GetPortList returns the same enumeration, in ms. 0 disables the cache.", default_value="500")]
+    port_list_ttl_ms: u64,
+
+    #[structopt(long, help="Interval of the keepalives sent on the WatchEvents streams, and of the HTTP/2 pings, in ms. 0 disables them.", default_value="15000")]
+    keepalive_ms: u64,
+
+    #[structopt(long, help="TCP port, on the address of the server, mirroring the serial traffic read-only to any client (nc, telnet).")]
+    monitor_port: Option<u16>,
+
+    #[structopt(long, help="Maximum number of bytes returned by a ReadOnce request not giving max_bytes.", default_value="32")]
+    read_buffer_size: usize,
+
+    #[structopt(long, help="Open, read and write timeout of the serial ports, in ms.", default_value="1000")]
+    port_timeout_ms: u64,
+
+    #[structopt(long, number_of_values=1, help="Port path the clients may open, repeatable. With --allow-usb, any other port is refused with PERMISSION_DENIED; without either, any port can be opened.")]
+    allow_port: Vec<String>,
+
+    #[structopt(long, number_of_values=1, help="USB device whose ports the clients may open, as hex VID:PID or VID:*, repeatable. See --allow-port.")]
+    allow_usb: Vec<String>,
+
+    #[structopt(long, parse(from_os_str), help="PEM certificate of the server, for it to serve over TLS with --tls-key. Without either, the server serves in plaintext.")]
+    tls_cert: Option<PathBuf>,
+
+    #[structopt(long, parse(from_os_str), help="PEM private key of --tls-cert.")]
+    tls_key: Option<PathBuf>,
+
+    #[structopt(long, help="Token the clients must give in the authorization metadata, as is or as 'Bearer <token>'. Also set by the SERIAL_RPC_AUTH_TOKEN env var. Without it, the clients are not authenticated.")]
+    auth_token: Option<String>,
+
+    #[structopt(long, help="Maximum size of the messages the server decodes and encodes, in bytes. Also set by the SERIAL_RPC_MAX_MESSAGE_SIZE env var, 4194304 (4 MiB) by default.")]
+    max_message_size: Option<usize>,
+
+    #[structopt(long, help="Accepts the gzip compressed requests, and compresses the replies of the clients accepting it. Also set by the SERIAL_RPC_GZIP env var.")]
+    gzip: bool,
+}
+
+/// Completes on the first Ctrl-C (SIGINT), or SIGTERM on Unix. A signal that can
+/// not be listened for is logged, and never completes.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Could not listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                error!("Could not listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Waits for `signal`, then closes the open port, if any, for the server to stop
+/// without leaving a write half done or the port handle to the OS.
+///
+/// The lock is taken as any request does: an ongoing write or read ends first.
+async fn close_on_shutdown(port: SharedPort, signal: impl std::future::Future<Output = ()>) {
+    signal.await;
+    info!("Shutting down ...");
+
+    let mut unlocked_port = port.lock().await;
+    if unlocked_port.is_open() {
+        let resp = unlocked_port.close_port().await;
+        info!("{}", resp.content);
+    }
+}
+
+/// Address the server listens on when neither the argument nor SERIAL_RPC_ADDR give one.
+const DEFAULT_ADDR: &str = "127.0.0.1:3333";
+
+/// Parses the address to listen on, an IP with a port.
+fn parse_addr(addr: &str) -> Result<SocketAddr, String> {
+    addr.trim().parse::<SocketAddr>().map_err(|e| format!("Error with address '{}': {}", addr, e))
+}
+
+/// Loads the identity of the server from its certificate and key, given together.
+///
+/// # Returns
+///
+/// The TLS config, `None` to serve in plaintext when neither is given.
+fn tls_config(cert: Option<&Path>, key: Option<&Path>) -> Result<Option<ServerTlsConfig>, String> {
+    let (cert, key) = match (cert, key) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) => return Ok(None),
+        _ => return Err("--tls-cert and --tls-key must be given together".to_string()),
+    };
+
+    let read = |path: &Path| std::fs::read(path).map_err(|e| format!("Could not read {}: {}", path.display(), e));
+    let identity = Identity::from_pem(read(cert)?, read(key)?);
+
+    Ok(Some(ServerTlsConfig::new().identity(identity)))
+}
+
+/// Checks the `authorization` metadata of `request` against `token`, given as is
+/// or as `Bearer <token>`.
+// The `Status` error is the one of the tonic interceptors.
+#[allow(clippy::result_large_err)]
+fn check_auth_token(token: &str, request: Request<()>) -> Result<Request<()>, Status> {
+    let given = match request.metadata().get("authorization").map(|value| value.to_str()) {
+        Some(Ok(given)) => given,
+        Some(Err(_)) => return Err(Status::unauthenticated("Invalid authorization metadata")),
+        None => return Err(Status::unauthenticated("Missing authorization metadata")),
+    };
+    let given = given.strip_prefix("Bearer ").unwrap_or(given);
+
+    // Compares all the bytes, for the time taken not to tell how much of the token matched.
+    let matches = given.len() == token.len()
+        && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0;
+    if matches {
+        Ok(request)
+    } else {
+        Err(Status::unauthenticated("Invalid authorization token"))
+    }
+}
+
+/// Interceptor authenticating the requests with `token`, letting them all through
+/// without one.
+#[allow(clippy::result_large_err)]
+fn auth_interceptor(token: Option<String>) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone {
+    move |request| match &token {
+        Some(token) => check_auth_token(token, request),
+        None => Ok(request),
+    }
+}
+
+/// Message size limit when neither the argument nor SERIAL_RPC_MAX_MESSAGE_SIZE
+/// give one, the default of tonic.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Returns the message size limit given by the argument, else by the env var,
+/// `DEFAULT_MAX_MESSAGE_SIZE` by default.
+fn max_message_size(arg: Option<usize>, env: Option<&str>) -> Result<usize, String> {
+    let size = match (arg, env) {
+        (Some(size), _) => size,
+        (None, Some(size)) => size.trim().parse()
+            .map_err(|_| format!("Error with the max message size '{}', expected a number of bytes", size))?,
+        (None, None) => DEFAULT_MAX_MESSAGE_SIZE,
+    };
+
+    if size == 0 {
+        return Err("Error with the max message size, it must be at least 1 byte".to_string());
+    }
+    Ok(size)
+}
+
+/// The `SerialComService` server of `service`, decoding and encoding messages up
+/// to `max_message_size` bytes. With `gzip`, it accepts the gzip compressed
+/// requests, and compresses the replies of the clients accepting it.
+fn serial_com_server(service: MySerialComService, max_message_size: usize, gzip: bool) -> SerialComServiceServer<MySerialComService> {
+    // A larger message fails its call with OUT_OF_RANGE.
+    let server = SerialComServiceServer::new(service)
+        .max_decoding_message_size(max_message_size)
+        .max_encoding_message_size(max_message_size);
+
+    if gzip {
+        server.accept_compressed(CompressionEncoding::Gzip).send_compressed(CompressionEncoding::Gzip)
+    } else {
+        server
+    }
+}
+
+/// Checks if a boolean env var is set to something else than "", "0" or "false".
+fn env_flag(name: &str) -> bool {
+    match std::env::var(name) {
+        Ok(value) => !(value.is_empty() || value == "0" || value.eq_ignore_ascii_case("false")),
+        Err(_) => false,
+    }
+}
+
+/// Filters the logs as RUST_LOG tells, with the usual directives (`debug`, or
+/// `warn,serial_term_rpc=debug` for ex.), up to `info` by default.
+fn log_filter(rust_log: &str) -> Result<EnvFilter, String> {
+    EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .parse(rust_log.trim())
+        .map_err(|e| format!("Invalid RUST_LOG '{}': {}", rust_log, e))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Cli::from_args();
+    let quiet = args.quiet || env_flag("SERIAL_RPC_QUIET");
+
+    let rust_log = std::env::var(EnvFilter::DEFAULT_ENV).unwrap_or_default();
+    let (filter, invalid_rust_log) = match log_filter(&rust_log) {
+        Ok(filter) => (filter, None),
+        Err(e) => (log_filter("")?, Some(e)),
+    };
+    // The warnings and errors on stderr, the rest on stdout. Quiet, nothing under
+    // the warnings is logged.
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr.with_max_level(Level::WARN).or_else(std::io::stdout))
+        .finish()
+        .with(if quiet { Some(LevelFilter::WARN) } else { None })
+        .try_init()?;
+    if let Some(e) = invalid_rust_log {
+        warn!("{}, using the default level", e);
+    }
+
+    let addr = args.addr.clone()
+        .or_else(|| std::env::var("SERIAL_RPC_ADDR").ok())
+        .unwrap_or_else(|| DEFAULT_ADDR.to_string());
+    let addr = match parse_addr(&addr) {
+        Ok(addr) => addr,
+        Err(e) => {
+            error!("{}", e);
+            return Err(e.into());
+        }
+    };
+
+    let gzip = args.gzip || env_flag("SERIAL_RPC_GZIP");
+    let max_message_size = match max_message_size(args.max_message_size, std::env::var("SERIAL_RPC_MAX_MESSAGE_SIZE").ok().as_deref()) {
+        Ok(max_message_size) => max_message_size,
+        Err(e) => {
+            error!("{}", e);
+            return Err(e.into());
+        }
+    };
+    let auth_token = args.auth_token.clone()
+        .or_else(|| std::env::var("SERIAL_RPC_AUTH_TOKEN").ok())
+        .filter(|token| !token.is_empty());
+
+    let tls = match tls_config(args.tls_cert.as_deref(), args.tls_key.as_deref()) {
+        Ok(tls) => tls,
+        Err(e) => {
+            error!("Error with the TLS setup: {}", e);
+            return Err(e.into());
+        }
+    };
+
+    let allowlist = if args.allow_port.is_empty() && args.allow_usb.is_empty() {
+        None
+    } else {
+        match allowlist::PortAllowlist::new(&args.allow_port, &args.allow_usb) {
+            Ok(allowlist) => Some(allowlist),
+            Err(e) => {
+                error!("Error with the allowed ports: {}", e);
+                return Err(e.into());
+            }
+        }
+    };
+
+    let event_watchers: EventWatchers = Arc::new(Mutex::new(vec![]));
+    let mut port = SerialPort::with_buffer_size(args.read_buffer_size);
+    let resp = port.set_timeout(Duration::from_millis(args.port_timeout_ms));
+    if !resp.success {
+        error!("Error with the port timeout: {}", resp.content);
+        return Err(resp.content.into());
+    }
+    watch_port_events(&mut port, &event_watchers);
+    let (health_changes, mut health_updates) = mpsc::unbounded_channel();
+    watch_port_health(&mut port, health_changes);
+
+    if let Some(monitor_port) = args.monitor_port {
+        let monitor = match monitor::TcpMonitor::bind((addr.ip(), monitor_port)) {
+            Ok(monitor) => monitor,
+            Err(e) => {
+                error!("Error with the monitor port {}: {}", monitor_port, e);
+                return Err(e.into());
+            }
+        };
+
+        port.on_traffic(monitor.hook());
+        info!("Mirroring the serial traffic on {} ...", monitor.local_addr());
+    }
+    let port = Arc::new(tokio::sync::Mutex::new(port));
+
+    // Writes the coalesced writes queued for too long, see `SetWriteCoalescing`.
+    let coalesced_port = Arc::clone(&port);
+    tokio::spawn(async move {
+        let mut checks = tokio::time::interval(Duration::from_millis(coalesce::COALESCING_CHECK_INTERVAL_MS));
+
+        loop {
+            checks.tick().await;
+
+            if let Some(resp) = coalesced_port.lock().await.flush_if_due().await {
+                if !resp.success {
+                    error!("Error writing the coalesced writes: {}", resp.content);
+                }
+            }
+        }
+    });
+
+    let keepalive = if args.keepalive_ms == 0 {
+        None
+    } else {
+        let interval = Duration::from_millis(args.keepalive_ms);
+        tokio::spawn(send_keepalives(Arc::clone(&event_watchers), interval));
+        Some(interval)
+    };
+
+    let mut serving = true;
+    if let Some(port_path) = &args.open {
+        let resp = port.lock().await.open_port(port_path, args.baudrate).await;
+
+        if resp.success {
+            info!("{}", resp.content);
+        } else {
+            error!("Error opening '{}' at startup: {}", port_path, resp.content);
+            serving = false;
+        }
+    }
+
+    let serial_com_service = MySerialComService {
+        port,
+        port_list_cache: Mutex::new(None),
+        port_list_ttl: Duration::from_millis(args.port_list_ttl_ms),
+        event_watchers,
+        write_cancel: Mutex::new(WriteCancel::default()),
+        allowlist,
+    };
+
+    // Standard grpc.health.v1.Health service, for probes and load balancers.
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    set_serving(&mut health_reporter, serving).await;
+    tokio::spawn(async move {
+        while let Some(serving) = health_updates.recv().await {
+            set_serving(&mut health_reporter, serving).await;
+        }
+    });
+
+    let mut server = Server::builder();
+    if let Some(tls) = tls {
+        server = match server.tls_config(tls) {
+            Ok(server) => server,
+            Err(e) => {
+                error!("Error with the TLS certificate or key: {}", e);
+                return Err(e.into());
+            }
+        };
+        info!("Running the RPC server on {} over TLS ...", addr);
+    } else {
+        info!("Running the RPC server on {} ...", addr);
+    }
+
+    let reflection = match reflection_server() {
+        Ok(reflection) => reflection,
+        Err(e) => {
+            error!("Error with the reflection service: {}", e);
+            return Err(e.into());
+        }
+    };
+    let port = Arc::clone(&serial_com_service.port);
+
+    server
+        // Closes the connections whose peer stopped answering, and their streams.
+        .http2_keepalive_interval(keepalive)
+        .add_service(health_service)
+        // The health service stays open, for the probes.
+        .add_service(InterceptedService::new(serial_com_server(serial_com_service, max_message_size, gzip), auth_interceptor(auth_token.clone())))
+        .add_service(InterceptedService::new(reflection, auth_interceptor(auth_token)))
+        .serve_with_shutdown(addr, close_on_shutdown(port, shutdown_signal()))
+        .await?;
+
+    info!("Server stopped.");
+
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_service() -> MySerialComService {
+        MySerialComService {
+            port: Arc::new(tokio::sync::Mutex::new(SerialPort::new())),
+            port_list_cache: Mutex::new(None),
+            port_list_ttl: Duration::from_millis(500),
+            event_watchers: Arc::new(Mutex::new(vec![])),
+            write_cancel: Mutex::new(WriteCancel::default()),
+            allowlist: None,
+        }
+    }
+
+    // Guards against refactors serializing the requests that do not need the port
+    // behind its lock, or keeping the lock after a reply.
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn port_lock_does_not_block_other_requests() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let service = Arc::new(test_service());
+        assert!(service.port.lock().await.open_port(&slave_path, 9600).await.success);
+
+        let reader = Arc::clone(&service);
+        let slow_read = tokio::spawn(async move {
+            reader.read_lines(Request::new(ReadLinesReq { max_lines: 0, timeout_ms: 500 })).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(service.port.try_lock().is_err(), "the read should hold the port");
+
+        let started = Instant::now();
+        service.ping(Request::new(SerialPingReq {})).await.unwrap();
+        service.get_port_list(Request::new(PortListReq { force_refresh: true })).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(200), "blocked for {:?}", started.elapsed());
+
+        assert!(slow_read.await.unwrap().unwrap().into_inner().success);
+        assert!(service.port.try_lock().is_ok());
+
+        service.send_once(Request::new(SendOnceReq { content: "ok".to_string(), chunk_size: 1, dry_run: false, timeout_ms: 0 })).await.unwrap();
+        assert!(service.port.try_lock().is_ok());
+    }
+
+    // With a single worker, a read waiting on the device must not hold the runtime.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn long_read_does_not_block_the_runtime() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let service = Arc::new(test_service());
+        {
+            let mut port = service.port.lock().await;
+            assert!(port.open_port(&slave_path, 9600).await.success);
+            assert!(port.set_read_timeout(Duration::from_millis(500)).success);
+        }
+
+        let reader = Arc::clone(&service);
+        let slow_read = tokio::spawn(async move {
+            reader.read_once(Request::new(ReadOnceReq { max_bytes: 0, timeout_ms: 0, format: 0 })).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(service.port.try_lock().is_err(), "the read should hold the port");
+
+        // Waiting for the lock must not hold the runtime either.
+        let sender = Arc::clone(&service);
+        let waiting_send = tokio::spawn(async move {
+            sender.send_once(Request::new(SendOnceReq { content: "ok".to_string(), chunk_size: 0, dry_run: false, timeout_ms: 0 })).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting_send.is_finished(), "the send should wait for the read");
+
+        let started = Instant::now();
+        service.ping(Request::new(SerialPingReq {})).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(200), "blocked for {:?}", started.elapsed());
+
+        let reply = slow_read.await.unwrap().unwrap().into_inner();
+        assert!(matches!(reply.outcome, Some(read_once_rep::Outcome::Error(_))));
+        let reply = waiting_send.await.unwrap().unwrap().into_inner();
+        assert!(matches!(reply.outcome, Some(send_once_rep::Outcome::Success(_))));
+        assert!(service.port.try_lock().is_ok());
+    }
+
+    // Same over the wire: a client is answered while another one waits on a read.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn ping_during_a_long_read_over_the_wire() {
+        use serial_terminal::serial_com_service_client::SerialComServiceClient;
+
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let service = test_service();
+        {
+            let mut port = service.port.lock().await;
+            assert!(port.open_port(&slave_path, 9600).await.success);
+            assert!(port.set_read_timeout(Duration::from_millis(1000)).success);
+        }
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        tokio::spawn(Server::builder().add_service(SerialComServiceServer::new(service)).serve(addr));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let channel = tonic::transport::Channel::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap();
+
+        let mut reader = SerialComServiceClient::new(channel.clone());
+        let slow_read = tokio::spawn(async move {
+            reader.read_once(ReadOnceReq { max_bytes: 0, timeout_ms: 0, format: 0 }).await
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let started = Instant::now();
+        SerialComServiceClient::new(channel).ping(SerialPingReq {}).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(300), "blocked for {:?}", started.elapsed());
+        assert!(!slow_read.is_finished(), "the read should still wait on the device");
+
+        let reply = slow_read.await.unwrap().unwrap().into_inner();
+        assert!(matches!(reply.outcome, Some(read_once_rep::Outcome::Error(_))));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn shutdown_closes_the_port() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let port = Arc::new(tokio::sync::Mutex::new(SerialPort::new()));
+        assert!(port.lock().await.open_port(&slave_path, 9600).await.success);
+
+        let (signal, received) = oneshot::channel::<()>();
+        let shutdown = tokio::spawn(close_on_shutdown(Arc::clone(&port), async move {
+            received.await.unwrap();
+        }));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(port.lock().await.is_open());
+
+        signal.send(()).unwrap();
+        shutdown.await.unwrap();
+        assert!(!port.lock().await.is_open());
+
+        // Without a port open, there is nothing to close.
+        close_on_shutdown(Arc::clone(&port), async {}).await;
+    }
+
+    #[tokio::test]
+    async fn health_follows_set_serving() {
+        use tonic_health::pb::{health_check_response, health_client::HealthClient, HealthCheckRequest};
+
+        let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+        set_serving(&mut health_reporter, true).await;
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        tokio::spawn(Server::builder().add_service(health_service).serve(addr));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let channel = tonic::transport::Channel::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap();
+        let mut client = HealthClient::new(channel);
+
+        for serving in [false, true].iter() {
+            set_serving(&mut health_reporter, *serving).await;
+
+            let expected = if *serving {
+                health_check_response::ServingStatus::Serving
+            } else {
+                health_check_response::ServingStatus::NotServing
+            };
+            for service in ["", "serial_terminal.SerialComService"].iter() {
+                let request = HealthCheckRequest { service: service.to_string() };
+                let status = client.check(request).await.unwrap().into_inner().status;
+                assert_eq!(expected as i32, status, "{}", service);
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn port_health_changes() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let (health_changes, mut health_updates) = mpsc::unbounded_channel();
+        let mut port = SerialPort::new();
+        watch_port_health(&mut port, health_changes);
+
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(port.close_port().await.success);
+        drop(port);
+
+        assert_eq!(Some(true), health_updates.recv().await);
+        assert_eq!(None, health_updates.recv().await);
+    }
+
+    #[tokio::test]
+    async fn reflection_over_the_wire() {
+        use tonic_reflection::pb::server_reflection_client::ServerReflectionClient;
+        use tonic_reflection::pb::server_reflection_request::MessageRequest;
+        use tonic_reflection::pb::server_reflection_response::MessageResponse;
+        use tonic_reflection::pb::ServerReflectionRequest;
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        tokio::spawn(Server::builder().add_service(reflection_server().unwrap()).serve(addr));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let channel = tonic::transport::Channel::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap();
+        let mut client = ServerReflectionClient::new(channel);
+
+        let request = |message_request| ServerReflectionRequest { host: String::new(), message_request: Some(message_request) };
+        let mut requests = vec![request(MessageRequest::ListServices(String::new()))];
+        let symbols = ["serial_terminal.SerialComService", "serial_terminal.SerialComService.Ping", "serial_terminal.OpenPortReq", "serial_terminal.Parity"];
+        requests.extend(symbols.iter().map(|symbol| request(MessageRequest::FileContainingSymbol(symbol.to_string()))));
+        requests.push(request(MessageRequest::FileContainingSymbol("serial_terminal.Nothing".to_string())));
+        let mut replies = client.server_reflection_info(tokio_stream::iter(requests)).await.unwrap().into_inner();
+
+        match replies.message().await.unwrap().unwrap().message_response {
+            Some(MessageResponse::ListServicesResponse(list)) => {
+                let mut names: Vec<String> = list.service.into_iter().map(|service| service.name).collect();
+                names.sort();
+                assert_eq!(vec!["grpc.health.v1.Health", "grpc.reflection.v1alpha.ServerReflection", "serial_terminal.SerialComService"], names);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        for symbol in symbols.iter() {
+            match replies.message().await.unwrap().unwrap().message_response {
+                Some(MessageResponse::FileDescriptorResponse(files)) => {
+                    let file: prost_types::FileDescriptorProto = prost::Message::decode(&files.file_descriptor_proto[0][..]).unwrap();
+                    assert_eq!("serial_terminal.proto", file.name(), "{}", symbol);
+                }
+                other => panic!("{}: {:?}", symbol, other),
+            }
+        }
+
+        // An unknown symbol ends the stream.
+        assert_eq!(tonic::Code::NotFound, replies.message().await.unwrap_err().code());
+    }
+
+    #[test]
+    fn tls_config_of_the_flags() {
+        let fixture = |name: &str| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+
+        assert!(tls_config(None, None).unwrap().is_none());
+        assert!(tls_config(Some(&fixture("localhost.crt")), Some(&fixture("localhost.key"))).unwrap().is_some());
+        for (cert, key) in [(Some(fixture("localhost.crt")), None), (None, Some(fixture("localhost.key")))].iter() {
+            assert_eq!(Err("--tls-cert and --tls-key must be given together".to_string()), tls_config(cert.as_deref(), key.as_deref()).map(|_| ()));
+        }
+        let e = tls_config(Some(&fixture("missing.crt")), Some(&fixture("localhost.key"))).unwrap_err();
+        assert!(e.starts_with("Could not read ") && e.contains("missing.crt"), "{}", e);
+
+        // A key not matching its PEM label is only refused by the server.
+        let swapped = tls_config(Some(&fixture("localhost.key")), Some(&fixture("localhost.crt"))).unwrap().unwrap();
+        assert!(Server::builder().tls_config(swapped).is_err());
+    }
+
+    #[tokio::test]
+    async fn ping_over_tls() {
+        use serial_terminal::serial_com_service_client::SerialComServiceClient;
+        use tonic::transport::{Certificate, Channel, ClientTlsConfig};
+
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let tls = tls_config(Some(&fixtures.join("localhost.crt")), Some(&fixtures.join("localhost.key"))).unwrap().unwrap();
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        tokio::spawn(Server::builder().tls_config(tls).unwrap().add_service(SerialComServiceServer::new(test_service())).serve(addr));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Trusting the self-signed certificate.
+        let ca = Certificate::from_pem(std::fs::read(fixtures.join("localhost.crt")).unwrap());
+        let channel = Channel::from_shared(format!("https://localhost:{}", addr.port())).unwrap()
+            .tls_config(ClientTlsConfig::new().ca_certificate(ca).domain_name("localhost")).unwrap()
+            .connect().await.unwrap();
+        let reply = SerialComServiceClient::new(channel).ping(SerialPingReq {}).await.unwrap().into_inner();
+        assert!(!reply.content.is_empty());
+
+        // A plaintext client can not talk to it.
+        let mut plaintext = SerialComServiceClient::connect(format!("http://{}", addr)).await.unwrap();
+        assert!(plaintext.ping(SerialPingReq {}).await.is_err());
+    }
+
+    #[test]
+    fn auth_tokens() {
+        let request = |authorization: Option<&str>| {
+            let mut request = Request::new(());
+            if let Some(authorization) = authorization {
+                request.metadata_mut().insert("authorization", authorization.parse().unwrap());
+            }
+            request
+        };
+        let check = auth_interceptor(Some("s3cret".to_string()));
+
+        for (authorization, message) in [(None, "Missing authorization metadata"), (Some("wrong"), "Invalid authorization token"), (Some("s3cre"), "Invalid authorization token"), (Some("Bearer s3cret2"), "Invalid authorization token")].iter() {
+            let status = check(request(*authorization)).unwrap_err();
+            assert_eq!(tonic::Code::Unauthenticated, status.code(), "{:?}", authorization);
+            assert_eq!(*message, status.message());
+        }
+        assert!(check(request(Some("s3cret"))).is_ok());
+        assert!(check(request(Some("Bearer s3cret"))).is_ok());
+
+        // Without token, nothing is checked.
+        assert!(auth_interceptor(None)(request(None)).is_ok());
+    }
+
+    #[allow(clippy::result_large_err)]
+    #[tokio::test]
+    async fn auth_token_over_the_wire() {
+        use serial_terminal::serial_com_service_client::SerialComServiceClient;
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let service = SerialComServiceServer::with_interceptor(test_service(), auth_interceptor(Some("s3cret".to_string())));
+        tokio::spawn(Server::builder().add_service(service).serve(addr));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let channel = tonic::transport::Channel::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap();
+
+        let status = SerialComServiceClient::new(channel.clone()).ping(SerialPingReq {}).await.unwrap_err();
+        assert_eq!(tonic::Code::Unauthenticated, status.code());
+
+        let authorized = |mut request: Request<()>| {
+            request.metadata_mut().insert("authorization", "Bearer s3cret".parse().unwrap());
+            Ok(request)
+        };
+        let mut client = SerialComServiceClient::with_interceptor(channel, authorized);
+        assert!(client.ping(SerialPingReq {}).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn messages_over_4mib_over_the_wire() {
+        use serial_terminal::serial_com_service_client::SerialComServiceClient;
+
+        // The dry run reply, in hex, is about 6 times as large as the request.
+        let raised = 32 * 1024 * 1024;
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        tokio::spawn(Server::builder().add_service(serial_com_server(test_service(), raised, false)).serve(addr));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut client = SerialComServiceClient::connect(format!("http://{}", addr)).await.unwrap()
+            .max_decoding_message_size(raised)
+            .max_encoding_message_size(raised);
+
+        // Just over the default 4 MiB limit, both ways.
+        let content = "a".repeat(DEFAULT_MAX_MESSAGE_SIZE + 1);
+        let request = SendOnceReq { content: content.clone(), chunk_size: 0, dry_run: true, timeout_ms: 0 };
+        match client.send_once(request).await.unwrap().into_inner().outcome {
+            Some(send_once_rep::Outcome::Success(sent)) => assert_eq!(content.as_bytes(), &sent.bytes[..]),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn messages_over_the_limit_are_refused() {
+        use serial_terminal::serial_com_service_client::SerialComServiceClient;
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        tokio::spawn(Server::builder().add_service(serial_com_server(test_service(), DEFAULT_MAX_MESSAGE_SIZE, false)).serve(addr));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut client = SerialComServiceClient::connect(format!("http://{}", addr)).await.unwrap()
+            .max_decoding_message_size(usize::MAX)
+            .max_encoding_message_size(usize::MAX);
+
+        // A request over the limit.
+        let request = SendOnceReq { content: "a".repeat(DEFAULT_MAX_MESSAGE_SIZE + 1), chunk_size: 0, dry_run: true, timeout_ms: 0 };
+        assert_eq!(tonic::Code::OutOfRange, client.send_once(request).await.unwrap_err().code());
+
+        // A request under the limit whose reply is over it.
+        let request = SendOnceReq { content: "a".repeat(DEFAULT_MAX_MESSAGE_SIZE / 2), chunk_size: 0, dry_run: true, timeout_ms: 0 };
+        assert_eq!(tonic::Code::OutOfRange, client.send_once(request).await.unwrap_err().code());
+
+        let request = SendOnceReq { content: "ok".to_string(), chunk_size: 0, dry_run: true, timeout_ms: 0 };
+        assert!(client.send_once(request).await.is_ok());
+    }
+
+    #[test]
+    fn max_message_sizes() {
+        assert_eq!(Ok(DEFAULT_MAX_MESSAGE_SIZE), max_message_size(None, None));
+        assert_eq!(Ok(1024), max_message_size(Some(1024), Some("2048")));
+        assert_eq!(Ok(2048), max_message_size(None, Some(" 2048 ")));
+        assert!(max_message_size(None, Some("4M")).unwrap_err().starts_with("Error with the max message size"));
+        assert!(max_message_size(Some(0), None).unwrap_err().starts_with("Error with the max message size"));
+    }
+
+    #[tokio::test]
+    async fn gzip_round_trip() {
+        use serial_terminal::serial_com_service_client::SerialComServiceClient;
+
+        let content = "compressible ".repeat(1000);
+        for gzip in [true, false].iter() {
+            let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+            tokio::spawn(Server::builder().add_service(serial_com_server(test_service(), DEFAULT_MAX_MESSAGE_SIZE, *gzip)).serve(addr));
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let mut client = SerialComServiceClient::connect(format!("http://{}", addr)).await.unwrap()
+                .send_compressed(CompressionEncoding::Gzip)
+                .accept_compressed(CompressionEncoding::Gzip);
+
+            let request = SendOnceReq { content: content.clone(), chunk_size: 0, dry_run: true, timeout_ms: 0 };
+            let reply = client.send_once(request).await;
+            if *gzip {
+                let reply = reply.unwrap();
+                assert_eq!(Some("gzip"), reply.metadata().get("grpc-encoding").map(|encoding| encoding.to_str().unwrap()));
+                match reply.into_inner().outcome {
+                    Some(send_once_rep::Outcome::Success(sent)) => assert_eq!(content.as_bytes(), &sent.bytes[..]),
+                    other => panic!("{:?}", other),
+                }
+            } else {
+                // Without the flag, the compressed requests are refused.
+                assert_eq!(tonic::Code::Unimplemented, reply.unwrap_err().code());
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn set_config_toggles_the_baudrate() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let service = test_service();
+        assert!(service.port.lock().await.open_port(&slave_path, 9600).await.success);
+
+        for baudrate in [115200, 9600].iter() {
+            let request = SetConfigReq { baudrate: *baudrate, ..SetConfigReq::default() };
+            let reply = service.set_config(Request::new(request)).await.unwrap().into_inner();
+            assert!(reply.success, "{}", reply.content);
+            assert_eq!(format!("Baudrate set to {}", baudrate), reply.content);
+            assert_eq!(*baudrate, service.port.lock().await.current_config().unwrap().baudrate);
+        }
+
+        // The settings left unset are not touched.
+        let request = SetConfigReq { flow_control: Some(serial_terminal::FlowControlValue { value: FlowControl::Software as i32 }), ..SetConfigReq::default() };
+        let reply = service.set_config(Request::new(request)).await.unwrap().into_inner();
+        assert_eq!("Flow control set to Software", reply.content);
+        assert_eq!(9600, service.port.lock().await.current_config().unwrap().baudrate);
+
+        let request = SetConfigReq { data_bits: 9, ..SetConfigReq::default() };
+        let status = service.set_config(Request::new(request)).await.unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, status.code());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn binary_round_trip() {
+        use std::io::{Read, Write};
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let service = test_service();
+        assert!(service.port.lock().await.open_port(&slave_path, 9600).await.success);
+        let frame = vec![0x00, 0xFF, 0x10];
+
+        let request = SendBytesReq { data: frame.clone() };
+        let reply = service.send_bytes(Request::new(request)).await.unwrap().into_inner();
+        assert!(reply.success, "{}", reply.content);
+        let mut sent = vec![0; frame.len()];
+        master.read_exact(&mut sent).unwrap();
+        assert_eq!(frame, sent);
+
+        master.write_all(&sent).unwrap();
+        let reply = service.read_bytes(Request::new(ReadBytesReq { max_bytes: 0 })).await.unwrap().into_inner();
+        assert!(reply.success, "{}", reply.content);
+        assert_eq!(frame, reply.data);
+
+        let reply = service.read_bytes(Request::new(ReadBytesReq { max_bytes: 0 })).await.unwrap().into_inner();
+        assert!(!reply.success);
+        assert_eq!("Serial read timed out", reply.content);
+    }
+
+    #[test]
+    fn parse_addrs() {
+        assert_eq!(Ok(SocketAddr::from(([127, 0, 0, 1], 3333))), parse_addr(DEFAULT_ADDR));
+        assert_eq!(Ok(SocketAddr::from(([0, 0, 0, 0], 50051))), parse_addr(" 0.0.0.0:50051 "));
+        assert_eq!("[::1]:3333", parse_addr("[::1]:3333").unwrap().to_string());
+
+        for addr in ["", "127.0.0.1", "localhost:3333", "127.0.0.1:70000"].iter() {
+            assert!(parse_addr(addr).unwrap_err().starts_with("Error with address"), "{}", addr);
+        }
+    }
+
+    #[test]
+    fn log_filters() {
+        for rust_log in ["", " debug ", "off", "serial_term_rpc=debug", "warn,server=debug,tonic=info"].iter() {
+            assert!(log_filter(rust_log).is_ok(), "{}", rust_log);
+        }
+        assert!(log_filter("server=loud").unwrap_err().starts_with("Invalid RUST_LOG 'server=loud': "));
+    }
+
+    /// Captures the logs, for the tests to check them.
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn open_port_is_logged() {
+        let logs = CapturedLogs::default();
+        let captured = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(log_filter("off,server=info").unwrap())
+            .with_writer(move || captured.clone())
+            .with_ansi(false)
+            .without_time()
+            .finish();
+        let _logging = tracing::subscriber::set_default(subscriber);
+
+        let service = test_service();
+        let request = OpenPortReq { port: "/dev/does-not-exist".to_string(), baudrate: 9600, post_open_delay_ms: 0, flow_control: 0 };
+        assert!(!service.open_port(Request::new(request)).await.unwrap().into_inner().success);
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = logs.lines().collect();
+        assert_eq!(2, lines.len(), "{}", logs);
+        assert_eq!(" INFO server: Got a OpenPort request. port=/dev/does-not-exist baudrate=9600", lines[0]);
+        assert!(lines[1].starts_with(" WARN server: Could not open the port: "), "{}", lines[1]);
+        assert!(lines[1].ends_with(" port=/dev/does-not-exist"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn timeout_of_the_call() {
+        let mut request = Request::new(SerialPingReq {});
+        assert_eq!(None, call_timeout(&request));
+
+        request.metadata_mut().insert("grpc-timeout", "250m".parse().unwrap());
+        assert_eq!(Some(Duration::from_millis(250)), call_timeout(&request));
+        request.metadata_mut().insert("grpc-timeout", "2S".parse().unwrap());
+        assert_eq!(Some(Duration::from_secs(2)), call_timeout(&request));
+        request.metadata_mut().insert("grpc-timeout", "x".parse().unwrap());
+        assert_eq!(None, call_timeout(&request));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn open_only_allowed_ports() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut service = test_service();
+        service.allowlist = Some(allowlist::PortAllowlist::new(std::slice::from_ref(&slave_path), &[]).unwrap());
+
+        let request = OpenPortReq { port: "/dev/ttyS0".to_string(), baudrate: 9600, post_open_delay_ms: 0, flow_control: 0 };
+        let status = service.open_port(Request::new(request)).await.unwrap_err();
+        assert_eq!(tonic::Code::PermissionDenied, status.code());
+
+        let request = OpenPortReq { port: slave_path, baudrate: 9600, post_open_delay_ms: 0, flow_control: 0 };
+        assert!(service.open_port(Request::new(request)).await.unwrap().into_inner().success);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn concurrent_opens() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let service = Arc::new(test_service());
+
+        // The settle delay widens the window between the check and the end of the open.
+        let opens: Vec<_> = (0..2).map(|_| {
+            let service = Arc::clone(&service);
+            let request = OpenPortReq { port: slave_path.clone(), baudrate: 9600, post_open_delay_ms: 50, flow_control: 0 };
+            tokio::spawn(async move { service.open_port(Request::new(request)).await })
+        }).collect();
+
+        let mut replies = vec![];
+        for open in opens {
+            replies.push(open.await.unwrap().unwrap().into_inner());
+        }
+
+        assert_eq!(1, replies.iter().filter(|reply| reply.success).count());
+        let failed = replies.iter().find(|reply| !reply.success).unwrap();
+        assert!(failed.content.starts_with("A port is already open"), "{}", failed.content);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn stream_reads_until_close() {
+        use std::io::Write;
+        use tokio_stream::StreamExt;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let service = Arc::new(test_service());
+        {
+            let mut port = service.port.lock().await;
+            assert!(port.open_port(&slave_path, 9600).await.success);
+            assert!(port.set_read_timeout(Duration::from_millis(20)).success);
+        }
+
+        let mut reads = service.stream_read(Request::new(StreamReadReq { max_bytes: 0 })).await.unwrap().into_inner();
+
+        // Each write may be streamed in several reads.
+        let mut received = vec![];
+        for (chunk, expected) in [(&b"first"[..], &b"first"[..]), (b" second", b"first second")].iter() {
+            master.write_all(chunk).unwrap();
+            while received != *expected {
+                match reads.next().await.unwrap().unwrap().outcome {
+                    Some(read_once_rep::Outcome::Success(read)) => received.extend(read.bytes),
+                    other => panic!("{:?}", other),
+                }
+            }
+        }
+
+        // Other requests get the port between the reads of the stream.
+        let reply = service.send_once(Request::new(SendOnceReq { content: "ok".to_string(), chunk_size: 0, dry_run: false, timeout_ms: 0 })).await.unwrap();
+        assert!(matches!(reply.into_inner().outcome, Some(send_once_rep::Outcome::Success(_))));
+
+        service.close_port(Request::new(ClosePortReq {})).await.unwrap();
+        let end = tokio::time::timeout(Duration::from_secs(2), reads.next()).await.unwrap();
+        assert!(end.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn session_round_trips() {
+        use std::io::{Read, Write};
+        use tokio_stream::StreamExt;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let service = test_service();
+        assert!(service.port.lock().await.open_port(&slave_path, 9600).await.success);
+
+        let (requests, session_requests) = mpsc::channel(1);
+        let (replies_sender, replies) = mpsc::channel(SESSION_CHANNEL_SIZE);
+        let session = tokio::spawn(run_session(Arc::clone(&service.port), ReceiverStream::new(session_requests), replies_sender));
+        let mut replies = ReceiverStream::new(replies);
+
+        for (command, response) in [("AT", "OK"), ("ATI", "v1.0"), ("AT+X", "ERROR")].iter() {
+            requests.send(Ok(SessionReq { content: command.to_string() })).await.unwrap();
+            match replies.next().await.unwrap().unwrap().event {
+                Some(session_rep::Event::Sent(sent)) => assert!(matches!(sent.outcome, Some(send_once_rep::Outcome::Success(_)))),
+                other => panic!("{:?}", other),
+            }
+            let mut written = vec![0; command.len()];
+            master.read_exact(&mut written).unwrap();
+            assert_eq!(command.as_bytes(), &written[..]);
+
+            master.write_all(response.as_bytes()).unwrap();
+            let mut received = vec![];
+            while received != response.as_bytes() {
+                match replies.next().await.unwrap().unwrap().event {
+                    Some(session_rep::Event::Read(read)) => match read.outcome {
+                        Some(read_once_rep::Outcome::Success(read)) => received.extend(read.bytes),
+                        other => panic!("{:?}", other),
+                    },
+                    other => panic!("{:?}", other),
+                }
+            }
+        }
+
+        // Ending the requests ends the session.
+        drop(requests);
+        tokio::time::timeout(Duration::from_secs(2), session).await.unwrap().unwrap();
+        assert!(replies.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn send_once_error_codes() {
+        let service = test_service();
+
+        for chunk_size in [0, 1].iter() {
+            let request = SendOnceReq { content: "ok".to_string(), chunk_size: *chunk_size, dry_run: false, timeout_ms: 0 };
+            match service.send_once(Request::new(request)).await.unwrap().into_inner().outcome {
+                Some(send_once_rep::Outcome::Error(error)) => {
+                    assert_eq!(ErrorCode::ErrorNoPortOpen as i32, error.code);
+                    assert!(error.message.starts_with("No port is currently open"), "{}", error.message);
+                }
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+}
@@ -1,8 +1,58 @@
 pub mod serial_port {
+    use std::future::Future;
     use std::{io, time};
 
+    use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    pub mod ack;
+    pub mod allowlist;
+    pub mod autobaud;
+    pub mod backend;
+    pub mod capability;
+    pub mod checksum;
+    pub mod coalesce;
+    pub mod codec;
+    pub mod config;
+    pub mod control;
+    pub mod delimited;
+    pub mod error;
+    pub mod events;
+    pub mod exact;
+    pub mod exchange;
+    pub mod exclusive;
+    pub mod framed;
+    pub mod intel_hex;
+    pub mod kv;
+    pub mod lines;
+    pub mod loopback;
+    pub mod manager;
+    pub mod modbus;
+    pub mod monitor;
+    pub mod network;
+    #[cfg(unix)]
+    pub mod owner;
+    pub mod payloads;
+    pub mod reconnect;
+    mod repeater;
+    pub mod rs485;
+    pub mod samples;
+    pub mod settings;
+    pub mod split;
+    pub mod stats;
+    pub mod swap;
+    pub mod timeout;
+    pub mod trace;
+    pub mod transfer;
+    pub mod usb;
+
+    #[cfg(unix)]
+    use std::os::unix::io::RawFd;
+    #[cfg(windows)]
+    use std::os::windows::io::RawHandle;
+
     const SERIAL_READ_BUFFER_SIZE: usize = 32;
-    const SERIAL_OPEN_TIMEOUT_MS: u64 = 10;
+    /// Initial open, read and write timeouts, see `set_timeout()`.
+    const SERIAL_OPEN_TIMEOUT_MS: u64 = 1000;
 
     /// Represents a response given by `SerialPort` methods
     /// in order to make the implementation of the RPC easier.
@@ -12,22 +62,150 @@ pub mod serial_port {
         pub content: String,
     }
 
+    impl SerialPortResponse {
+        /// Converts the response into a `Result`, to be used with `?`.
+        ///
+        /// # Returns
+        ///
+        /// `Ok(content)` if `success` is true, `Err(content)` otherwise.
+        pub fn into_result(self) -> Result<String, String> {
+            if self.success {
+                Ok(self.content)
+            } else {
+                Err(self.content)
+            }
+        }
+    }
+
+    /// Timeouts in effect for an open port.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PortTimeouts {
+        /// Timeout the port has been opened with.
+        pub open: time::Duration,
+        pub read: time::Duration,
+        pub write: time::Duration,
+    }
+
     /// Represents a POSIX or Windows serial port.
     pub struct SerialPort {
-        /// There can be no real port associated to it.
-        port: Option<Box<dyn serialport::SerialPort>>,
+        /// What drives the port, see `with_backend()`. There can be no real port
+        /// associated to it.
+        backend: Box<dyn backend::SerialBackend>,
+        /// Background loopback repeater, see `set_repeater()`.
+        repeater: Option<repeater::Repeater>,
+        /// Bytes read ahead by the delimited reads (`read_lines()`, `read_until_with()`)
+        /// and not returned yet. They are returned first by every read.
+        read_buffer: Vec<u8>,
+        /// Encodings applied on the send and read paths, see `set_codec_pipeline()`.
+        codecs: Vec<Box<dyn codec::SendCodec>>,
+        /// Traffic and error counters, see `stats()`.
+        stats: stats::PortStats,
+        /// See `last_io_elapsed()`.
+        last_io_elapsed: time::Duration,
+        /// Per-byte capture, see `start_byte_trace()`.
+        byte_trace: Option<trace::ByteTrace>,
+        /// Called on the port state changes, see `on_event()`.
+        event_hooks: Vec<events::EventHook>,
+        /// Called with the traffic, see `on_traffic()`.
+        traffic_hooks: Vec<monitor::TrafficHook>,
+        /// Buffer `read_once()` reads into, kept to avoid an allocation per read.
+        read_scratch: Vec<u8>,
+        /// Timeout the ports are opened with.
+        open_timeout: time::Duration,
+        /// Timeout of the reads, the one the backend of the open port is left with
+        /// between operations.
+        read_timeout: time::Duration,
+        /// Timeout of the writes.
+        write_timeout: time::Duration,
+        /// If the local ports are opened in exclusive mode, see `set_exclusive()`.
+        #[cfg(unix)]
+        exclusive: bool,
+        /// Writes queued by the coalescing, see `set_write_coalescing()`.
+        coalescing: Option<coalesce::Coalescing>,
+        /// If the hex escapes are sent as raw bytes, see `set_byte_escapes()`.
+        byte_escapes: bool,
+        /// The RTS direction control of the writes, see `set_rs485_mode()`.
+        rs485: Option<rs485::Rs485Mode>,
+        /// Appended by `send_line()`, see `set_line_ending()`.
+        line_ending: lines::LineEnding,
+        /// See `last_sent()` and `last_received()`.
+        last_sent: Option<payloads::Payload>,
+        last_received: Option<payloads::Payload>,
+        /// Maximum number of bytes read by `read_once()`, see `with_buffer_size()`.
+        read_buffer_size: usize,
+        /// See `enable_auto_reconnect()`.
+        auto_reconnect: Option<reconnect::AutoReconnect>,
+        /// What the last port has been opened with, reopened by the auto reconnect.
+        last_open: Option<reconnect::LastOpen>,
+    }
+
+    impl Default for SerialPort {
+        fn default() -> SerialPort {
+            let timeout = time::Duration::from_millis(SERIAL_OPEN_TIMEOUT_MS);
+
+            SerialPort {
+                backend: Box::new(backend::LocalSerialBackend::new()),
+                repeater: None,
+                read_buffer: vec![],
+                codecs: vec![],
+                stats: stats::PortStats::default(),
+                last_io_elapsed: time::Duration::from_millis(0),
+                byte_trace: None,
+                event_hooks: vec![],
+                traffic_hooks: vec![],
+                read_scratch: vec![],
+                open_timeout: timeout,
+                read_timeout: timeout,
+                write_timeout: timeout,
+                #[cfg(unix)]
+                exclusive: false,
+                coalescing: None,
+                byte_escapes: false,
+                rs485: None,
+                line_ending: lines::LineEnding::default(),
+                last_sent: None,
+                last_received: None,
+                read_buffer_size: SERIAL_READ_BUFFER_SIZE,
+                auto_reconnect: None,
+                last_open: None,
+            }
+        }
     }
 
     impl SerialPort {
         pub fn new() -> SerialPort {
-            SerialPort { port: None }
+            SerialPort::default()
+        }
+
+        /// Creates a port reading up to `buffer_size` bytes at once with `read_once()`,
+        /// for frames larger than the default of 32 bytes. Zero means the default.
+        pub fn with_buffer_size(buffer_size: usize) -> SerialPort {
+            SerialPort {
+                read_buffer_size: if buffer_size == 0 {
+                    SERIAL_READ_BUFFER_SIZE
+                } else {
+                    buffer_size
+                },
+                ..SerialPort::default()
+            }
+        }
+
+        /// Returns the maximum number of bytes read by `read_once()`.
+        pub fn read_buffer_size(&self) -> usize {
+            self.read_buffer_size
         }
 
         /// Opens a serial port.
         ///
+        /// Fails if a port is already open. The check and the open are done by the same
+        /// call, under the `&mut self` borrow: a `SerialPort` shared between tasks
+        /// (behind the mutex of the server for ex.) must stay locked for the whole call,
+        /// for concurrent opens to give one port and clear "already open" failures.
+        ///
         /// # Paramters
         ///
-        /// - `port_path`: The path to the serial port. Can be given by `get_available_port_names()`.
+        /// - `port_path`: The path to the serial port. Can be given by `get_available_port_names()`,
+        ///   or be a `tcp://host:port` or `rfc2217://host:port` network port, see `network`.
         /// - `baudrate`: The baudrate used to configure the serial communication.
         ///
         /// # Returns
@@ -35,54 +213,171 @@ pub mod serial_port {
         /// A `SerialPortResponse` containing:
         /// - `content`: informative message.
         /// - `success`: if the port has been open correctly.
-        pub fn open_port(&mut self, port_path: &str, baudrate: u32) -> SerialPortResponse {
-            if let Some(_port) = &self.port {
-                return SerialPortResponse {
-                    success: false,
-                    content: "A port is already open".to_string(),
-                };
-            }
+        pub async fn open_port(&mut self, port_path: &str, baudrate: u32) -> SerialPortResponse {
+            self.open_port_with_delay(port_path, baudrate, time::Duration::from_millis(0))
+                .await
+        }
+
+        /// Opens a serial port as `open_port()` does, then waits for `post_open_delay`
+        /// before returning, for the device to be ready for the first read or write.
+        ///
+        /// Some USB-serial chips pass no data, or garbage, during a short while after
+        /// the open. Typical delays:
+        /// - CH340: 50ms.
+        /// - CP2102: 20 to 50ms.
+        /// - FTDI: none needed.
+        /// - Boards reset by the DTR assertion of the open (Arduino Uno for ex.): 1500
+        ///   to 2000ms, the time of their bootloader.
+        ///
+        /// # Paramters
+        ///
+        /// - `port_path`: As given to `open_port()`.
+        /// - `baudrate`: As given to `open_port()`.
+        /// - `post_open_delay`: The time to wait after a successful open. Zero means no delay.
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: informative message, with the delay applied.
+        /// - `success`: if the port has been open correctly.
+        pub async fn open_port_with_delay(
+            &mut self,
+            port_path: &str,
+            baudrate: u32,
+            post_open_delay: time::Duration,
+        ) -> SerialPortResponse {
+            self.open_configured(
+                port_path,
+                &config::SerialConfig::new(baudrate),
+                post_open_delay,
+            )
+            .await
+        }
 
-            // TODO check the input.
+        /// Opens a serial port as `open_port()` does.
+        ///
+        /// # Returns
+        ///
+        /// The informative message of the open, or the error: `AlreadyOpen` if a
+        /// port is already open, `InvalidInput` if the path is empty or the baudrate 0,
+        /// `Io` if the port could not be opened.
+        pub async fn try_open_port(
+            &mut self,
+            port_path: &str,
+            baudrate: u32,
+        ) -> Result<String, error::SerialError> {
+            self.try_open_configured(
+                port_path,
+                &config::SerialConfig::new(baudrate),
+                time::Duration::from_millis(0),
+            )
+            .await
+        }
 
-            let port_builder = serialport::new(port_path, baudrate)
-                .timeout(time::Duration::from_millis(SERIAL_OPEN_TIMEOUT_MS));
+        /// Opens a serial port as `open_port_with_config()` does, then waits for
+        /// `post_open_delay` as `open_port_with_delay()` does.
+        pub async fn open_configured(
+            &mut self,
+            port_path: &str,
+            config: &config::SerialConfig,
+            post_open_delay: time::Duration,
+        ) -> SerialPortResponse {
+            self.try_open_configured(port_path, config, post_open_delay)
+                .await
+                .into()
+        }
+
+        async fn try_open_configured(
+            &mut self,
+            port_path: &str,
+            config: &config::SerialConfig,
+            post_open_delay: time::Duration,
+        ) -> Result<String, error::SerialError> {
+            if let Some(port) = self.backend.if_open() {
+                return Err(error::SerialError::AlreadyOpen(
+                    port.name().unwrap_or_else(|| "default".to_string()),
+                ));
+            }
 
-            let port = port_builder.open();
+            if port_path.is_empty() {
+                return Err(error::SerialError::InvalidInput(
+                    "The port path can not be empty".to_string(),
+                ));
+            }
+            if let Err(e) = config.validate() {
+                return Err(error::SerialError::InvalidInput(format!(
+                    "Invalid port config: {}",
+                    e
+                )));
+            }
 
-            match port {
+            match self.open_path(port_path, config).await {
                 Ok(port) => {
+                    // As requested, the name of the port may differ.
+                    self.last_open = Some(reconnect::LastOpen {
+                        port_path: port_path.to_string(),
+                        config: *config,
+                        post_open_delay,
+                    });
+
                     let port_path = match port.name() {
                         Some(name) => name,
                         None => "default".to_string(),
                     };
 
-                    let baudrate = match port.baud_rate() {
-                        Ok(baudrate) => baudrate,
-                        Err(_) => 0,
-                    };
+                    let baudrate = port.baud_rate().unwrap_or(0);
 
-                    self.port = Some(port);
+                    self.backend = port;
+                    self.last_sent = None;
+                    self.last_received = None;
+                    self.release_rs485_bus();
 
-                    return SerialPortResponse {
-                        success: true,
-                        content: format!(
-                            "Openend port {} with a baudrate of {}",
-                            port_path, baudrate
-                        )
-                        .to_string(),
-                    };
-                }
-                Err(_e) => {
-                    return SerialPortResponse {
-                        success: false,
-                        content: "Could not open the port".to_string(),
-                    };
+                    let mut content =
+                        format!("Openend port {} with a baudrate of {}", port_path, baudrate);
+                    if post_open_delay > time::Duration::from_millis(0) {
+                        tokio::time::sleep(post_open_delay).await;
+                        content = format!(
+                            "{}, after a settle delay of {}ms",
+                            content,
+                            post_open_delay.as_millis()
+                        );
+                    }
+                    self.emit(events::PortEventKind::Opened, &port_path, content.clone());
+
+                    Ok(content)
                 }
+                Err(e) => Err(open_error(port_path, e)),
+            }
+        }
+
+        /// Opens a local or network port, without storing it.
+        async fn open_path(
+            &mut self,
+            port_path: &str,
+            config: &config::SerialConfig,
+        ) -> serialport::Result<Box<dyn backend::SerialBackend>> {
+            let timeout = self.open_timeout;
+            let options = backend::OpenOptions {
+                baudrate: config.baudrate,
+                data_bits: config.data_bits,
+                parity: config.parity,
+                stop_bits: config.stop_bits,
+                flow_control: config.flow_control,
+                timeout,
+                exclusive: self.is_exclusive(),
+            };
+
+            let mut port = self.backend.unopened();
+            port.open(port_path, &options).await?;
+            if self.read_timeout != timeout {
+                port.set_timeout(self.read_timeout)?;
             }
+
+            Ok(port)
         }
 
-        /// Closes the current serial port.
+        /// Closes the current serial port, after writing the writes queued by the
+        /// coalescing, if any.
         ///
         /// # Paramters
         ///
@@ -91,222 +386,1485 @@ pub mod serial_port {
         /// A `SerialPortResponse` containing:
         /// - `content`: informative message.
         /// - `success`: if the port has been closed correctly.
-        pub fn close_port(&mut self) -> SerialPortResponse {
-            if let Some(port) = self.port.as_mut() {
-                let port_path = match port.name() {
-                    Some(name) => name,
-                    None => "default".to_string(),
-                };
+        pub async fn close_port(&mut self) -> SerialPortResponse {
+            self.try_close_port().await.into()
+        }
 
-                drop(port);
-                self.port = None;
+        /// Closes the current serial port as `close_port()` does.
+        ///
+        /// # Returns
+        ///
+        /// The informative message of the close, or `NoPortOpen`.
+        pub async fn try_close_port(&mut self) -> Result<String, error::SerialError> {
+            let flushed = self.flush_pending().await;
 
-                return SerialPortResponse {
-                    success: true,
-                    content: format!("Port {} closed", port_path).to_string(),
-                };
+            if let Some(port_path) = self.release_port() {
+                self.stats = stats::PortStats::default();
+                let mut content = format!("Port {} closed", port_path);
+                if let Some(Err(e)) = flushed {
+                    content = format!("{}, but: {}", content, e);
+                }
+                self.emit(events::PortEventKind::Closed, &port_path, content.clone());
+
+                Ok(content)
             } else {
-                return SerialPortResponse {
-                    success: false,
-                    content: "No port is currently open".to_string(),
-                };
+                Err(error::SerialError::NoPortOpen)
             }
         }
 
-        /// Sends a message to the current opened serial port.
+        /// Drops the open port and the state tied to it.
         ///
-        /// # Paramters
+        /// # Returns
         ///
-        /// - `message`: The string slice to send.
+        /// The path of the dropped port, if one was open.
+        fn release_port(&mut self) -> Option<String> {
+            if !self.backend.is_open() {
+                return None;
+            }
+
+            // Stops the repeater first, it works on a clone of the port.
+            self.repeater = None;
+            self.read_buffer.clear();
+
+            let port_path = match self.backend.name() {
+                Some(name) => name,
+                None => "default".to_string(),
+            };
+
+            self.backend.close();
+
+            Some(port_path)
+        }
+
+        /// Returns the file descriptor of the currently open port, if any.
+        ///
+        /// This is a power-user escape hatch, for ex. to hand the port over to another
+        /// library or to inspect it with `lsof`. The descriptor is still owned and used
+        /// by this `SerialPort`: reading, writing or reconfiguring it directly is at the
+        /// caller's own risk, and it must not be closed by the caller.
+        #[cfg(unix)]
+        pub fn raw_fd(&self) -> Option<RawFd> {
+            self.backend.raw_fd()
+        }
+
+        /// Returns the handle of the currently open port, if any.
+        ///
+        /// Same contract as the Unix `raw_fd()`: the handle is still owned and used
+        /// by this `SerialPort`, using it directly is at the caller's own risk.
+        #[cfg(windows)]
+        pub fn raw_handle(&self) -> Option<RawHandle> {
+            self.backend.raw_handle()
+        }
+
+        /// Returns the timeouts currently in effect for the open port, if any.
+        ///
+        /// All of them are initially `SERIAL_OPEN_TIMEOUT_MS`.
+        pub fn timeouts(&self) -> Option<PortTimeouts> {
+            self.backend.if_open().map(|_| PortTimeouts {
+                open: self.open_timeout,
+                read: self.read_timeout,
+                write: self.write_timeout,
+            })
+        }
+
+        /// Sets the open, read and write timeouts at once: the read (and write)
+        /// timeout of the open port if any, and the timeout the next ports are
+        /// opened with.
         ///
         /// # Returns
         ///
         /// A `SerialPortResponse` containing:
         /// - `content`: informative message.
-        /// - `success`: if the message has been sent correctly.
-        pub fn send_once(&mut self, message: &str) -> SerialPortResponse {
-            let output = parse_str_to_serial(message);
-            let output = output.as_bytes();
-
-            if let Some(port) = self.port.as_mut() {
-                match port.write(output) {
-                    Ok(_t) => {
-                        return SerialPortResponse {
-                            success: true,
-                            content: "Request sent".to_string(),
-                        };
-                    }
+        /// - `success`: if the timeout has been applied to the open port.
+        pub fn set_timeout(&mut self, timeout: time::Duration) -> SerialPortResponse {
+            self.open_timeout = timeout;
+            self.write_timeout = timeout;
 
-                    Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
-                        return SerialPortResponse {
-                            success: false,
-                            content: "Serial write timed out".to_string(),
-                        };
-                    }
-                    Err(e) => {
-                        return SerialPortResponse {
-                            success: false,
-                            content: format!("Serial write error: {}", e).to_string(),
-                        };
-                    }
-                }
-            } else {
-                return SerialPortResponse {
+            let resp = self.set_read_timeout(timeout);
+            if !resp.success {
+                return resp;
+            }
+
+            SerialPortResponse {
+                success: true,
+                content: format!("Timeouts set to {}ms", timeout.as_millis()),
+            }
+        }
+
+        /// Sets the timeout the next ports are opened with, the open port being
+        /// left as is.
+        pub fn set_open_timeout(&mut self, timeout: time::Duration) {
+            self.open_timeout = timeout;
+        }
+
+        /// Sets the timeout of the reads, applied to the open port if any, and to
+        /// the next ports once opened.
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: informative message.
+        /// - `success`: if the timeout has been applied to the open port.
+        pub fn set_read_timeout(&mut self, timeout: time::Duration) -> SerialPortResponse {
+            self.read_timeout = timeout;
+
+            match self.backend.if_open_mut().map(|port| port.set_timeout(timeout)) {
+                Some(Err(e)) => SerialPortResponse {
                     success: false,
-                    content: "No port is currently open".to_string(),
-                };
+                    content: format!("Could not set the read timeout: {}", e),
+                },
+                _ => SerialPortResponse {
+                    success: true,
+                    content: format!("Read timeout set to {}ms", timeout.as_millis()),
+                },
             }
         }
 
-        /// Reads [TODO nb char] from the opened serial port.
+        /// Sets the timeout of the writes, of the open port if any and of the next ones.
+        pub fn set_write_timeout(&mut self, timeout: time::Duration) {
+            self.write_timeout = timeout;
+        }
+
+        /// Sends a message to the current opened serial port.
+        ///
+        /// The message is encoded by the codec pipeline, if any, see `set_codec_pipeline()`.
+        /// It is written whole, across as many writes as needed, then flushed.
         ///
         /// # Paramters
         ///
+        /// - `message`: The string slice to send.
+        ///
         /// # Returns
         ///
         /// A `SerialPortResponse` containing:
-        /// - `content`: The characters read from the serial port, or an informative message.
-        /// - `success`: if the chars has been correctly read from the serial port.
-        pub fn read_once(&mut self) -> SerialPortResponse {
-            if let Some(port) = self.port.as_mut() {
-                let mut serial_buf: Vec<u8> = vec![0; SERIAL_READ_BUFFER_SIZE];
-
-                match port.read(serial_buf.as_mut_slice()) {
-                    Ok(t) => {
-                        let content = String::from_utf8_lossy(&serial_buf[..t]).to_string();
-                        println!("From serial: {}", content);
-
-                        return SerialPortResponse {
-                            success: true,
-                            content: content,
-                        };
-                    }
+        /// - `content`: informative message, with the number of bytes written when
+        ///   the write failed midway.
+        /// - `success`: if the message has been sent correctly.
+        pub async fn send_once(&mut self, message: &str) -> SerialPortResponse {
+            self.try_send_once(message).await.into()
+        }
 
-                    Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
-                        return SerialPortResponse {
-                            success: false,
-                            content: "Serial read timed out".to_string(),
-                        };
-                    }
-                    Err(e) => {
-                        return SerialPortResponse {
-                            success: false,
-                            content: format!("Serial read error: {}", e).to_string(),
-                        };
-                    }
-                }
+        /// Sends a message as `send_once()` does.
+        ///
+        /// # Returns
+        ///
+        /// The informative message of the write, or the error: `NoPortOpen`,
+        /// `Timeout` or `Io`.
+        pub async fn try_send_once(&mut self, message: &str) -> Result<String, error::SerialError> {
+            let output = self.encode_message(message);
+            self.try_send_encoded(&output).await
+        }
+
+        /// Sends `data` as is, without parsing escapes, for binary frames. The codec
+        /// pipeline, if any, is applied as for `send_once()`.
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: informative message, see `send_once()`.
+        /// - `success`: if the bytes have been sent correctly.
+        pub async fn send_bytes(&mut self, data: &[u8]) -> SerialPortResponse {
+            self.try_send_bytes(data).await.into()
+        }
+
+        /// Sends bytes as `send_bytes()` does, with the errors of `try_send_once()`.
+        pub async fn try_send_bytes(&mut self, data: &[u8]) -> Result<String, error::SerialError> {
+            let output = codec::encode_with(&self.codecs, data);
+            self.try_send_encoded(&output).await
+        }
+
+        /// Returns the bytes `send_once()` would write for `message`.
+        ///
+        /// Along with `send_encoded()`, allows to write a message in several chunks.
+        pub fn encode_message(&self, message: &str) -> Vec<u8> {
+            codec::encode_with(&self.codecs, &self.message_bytes(message))
+        }
+
+        /// Returns the bytes of `message` once its escapes parsed, before the codecs.
+        pub(crate) fn message_bytes(&self, message: &str) -> Vec<u8> {
+            if self.byte_escapes {
+                parse_str_to_bytes(message)
             } else {
-                return SerialPortResponse {
-                    success: false,
-                    content: "No port is currently open".to_string(),
-                };
+                parse_str_to_serial(message).into_bytes()
             }
         }
 
-        /// Returns a list of available ports.
-        pub fn get_available_port_names() -> Vec<String> {
-            let ports = match serialport::available_ports() {
-                Ok(ports) => ports,
-                Err(_) => return vec!["No ports available".to_string()],
-            };
+        /// Sets if the hex escapes of the messages are sent as raw bytes, see
+        /// `parse_str_to_bytes()`, rather than as characters, see `parse_str_to_serial()`.
+        /// They only differ above `0x7F`. Off by default, kept across port changes.
+        pub fn set_byte_escapes(&mut self, byte_escapes: bool) {
+            self.byte_escapes = byte_escapes;
+        }
 
-            if ports.is_empty() {
-                return vec!["No ports available".to_string()];
-            }
+        /// Writes bytes already given by `encode_message()`, or part of them.
+        ///
+        /// With the write coalescing, the bytes are queued, see `set_write_coalescing()`.
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: informative message.
+        /// - `success`: if the bytes have been sent (or queued) correctly.
+        pub async fn send_encoded(&mut self, output: &[u8]) -> SerialPortResponse {
+            self.try_send_encoded(output).await.into()
+        }
 
-            let mut port_names = vec![];
+        async fn try_send_encoded(&mut self, output: &[u8]) -> Result<String, error::SerialError> {
+            self.last_io_elapsed = time::Duration::from_millis(0);
 
-            for p in ports {
-                port_names.push(p.port_name);
+            if self.backend.is_open() {
+                if let Some(result) = self.queue_write(output).await {
+                    return result;
+                }
             }
 
-            port_names
+            self.try_write_encoded(output).await
         }
-    }
-
-    use std::char;
 
-    const CHAR_0_AS_U32: u32 = '0' as u32;
-    const CHAR_9_AS_U32: u32 = '9' as u32;
-    const CHAR_A_AS_U32: u32 = 'A' as u32;
-    const CHAR_F_AS_U32: u32 = 'F' as u32;
+        /// Writes `output` right away.
+        async fn write_encoded(&mut self, output: &[u8]) -> SerialPortResponse {
+            self.try_write_encoded(output).await.into()
+        }
 
-    /// Mainly parses written/ascii hex value to real hex value (from 0x00 to 0xFF).
-    // pub fn parse_str_to_serial(s: String) -> String {
-    pub fn parse_str_to_serial(s: &str) -> String {
-        let mut parsed_s = String::from("");
+        /// Writes `output` right away, after the writes queued by the coalescing if
+        /// any, for the bytes to go out in the order they have been given.
+        async fn try_write_encoded(&mut self, output: &[u8]) -> Result<String, error::SerialError> {
+            if let Some(Err(e)) = self.flush_pending().await {
+                return Err(e);
+            }
 
-        if s.len() < 4 {
-            return String::from(s);
+            self.try_write_now(output).await
         }
 
-        let vec_s = s.chars().collect::<Vec<char>>();
-        let mut hex_windows_it = vec_s.windows(4);
-        let mut is_hex;
-        let mut hex_int: u32;
-        let mut hex_c: char = '0';
-
-        // Looking for hex in the form 0xAA.
-        while let Some(hex_word) = hex_windows_it.next() {
-            is_hex = false;
-            hex_int = 0;
-
-            if hex_word[0] == '0' && (hex_word[1] == 'X' || hex_word[1] == 'x') {
-                let hex_word_2 = hex_word[2] as u32;
-                let hex_word_3 = hex_word[3] as u32;
-                is_hex = true;
-
-                if hex_word_2 >= CHAR_0_AS_U32 && hex_word_2 <= CHAR_9_AS_U32 {
-                    hex_int += (hex_word_2 - CHAR_0_AS_U32) << 4;
-                } else if hex_word_2 >= CHAR_A_AS_U32 && hex_word_2 <= CHAR_F_AS_U32 {
-                    hex_int += (hex_word_2 - CHAR_A_AS_U32) << 4;
-                } else {
-                    is_hex = false;
+        /// Writes `output` as is, the writes queued by the coalescing staying queued.
+        async fn try_write_now(&mut self, output: &[u8]) -> Result<String, error::SerialError> {
+            if let Some(port) = self.backend.if_open_mut() {
+                let started = time::Instant::now();
+                let (t, result) =
+                    rs485::write_half_duplex(port, self.rs485, output, self.write_timeout).await;
+                self.last_io_elapsed += started.elapsed();
+                // The bytes written before a failure are counted too.
+                if result.is_err() && t > 0 {
+                    self.stats.record_write(&Ok(t));
                 }
-
-                if hex_word_3 >= CHAR_0_AS_U32 && hex_word_3 <= CHAR_9_AS_U32 {
-                    hex_int += hex_word_3 - CHAR_0_AS_U32;
-                } else if hex_word_3 >= CHAR_A_AS_U32 && hex_word_3 <= CHAR_F_AS_U32 {
-                    hex_int += hex_word_3 - CHAR_A_AS_U32;
-                } else {
-                    is_hex = false;
+                let result = result.map(|_| t);
+                self.stats.record_write(&result);
+                if let Some(trace) = self.byte_trace.as_mut() {
+                    trace.record(&output[..t], trace::Direction::Sent);
                 }
-
-                if let Some(hex_int_to_char) = char::from_u32(hex_int) {
-                    hex_c = hex_int_to_char;
+                monitor::tap(&self.traffic_hooks, trace::Direction::Sent, &output[..t]);
+                if let Err(e) = &result {
+                    self.check_disconnect(e).await;
                 } else {
-                    is_hex = false;
+                    payloads::record(&mut self.last_sent, &output[..t]);
                 }
+
+                write_result(t, output.len(), result.map(|_| ()))
+            } else {
+                Err(error::SerialError::NoPortOpen)
             }
+        }
 
-            if is_hex {
-                parsed_s.push(hex_c);
-                // Skips 3 next items.
-                hex_windows_it.nth(2);
+        /// Discards the bytes written to the port but not sent yet by the driver.
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: informative message.
+        /// - `success`: if the output buffer has been cleared.
+        pub fn discard_output(&mut self) -> SerialPortResponse {
+            if let Some(port) = self.backend.if_open_mut() {
+                match port.clear(serialport::ClearBuffer::Output) {
+                    Ok(_) => SerialPortResponse {
+                        success: true,
+                        content: "Output buffer discarded".to_string(),
+                    },
+                    Err(e) => SerialPortResponse {
+                        success: false,
+                        content: format!("Could not discard the output buffer: {}", e),
+                    },
+                }
             } else {
-                parsed_s.push(hex_word[0]);
+                SerialPortResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                }
             }
         }
 
-        parsed_s
-    }
-}
+        /// Discards the bytes received but not read yet, by the driver as well as the
+        /// ones read ahead, for ex. to drop a partial frame.
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: informative message.
+        /// - `success`: if the input buffer has been cleared.
+        pub fn clear_input(&mut self) -> SerialPortResponse {
+            self.clear_buffers(serialport::ClearBuffer::Input, "input buffer")
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::serial_port::*;
+        /// Discards the bytes written to the port but not sent yet by the driver,
+        /// as `discard_output()` does.
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: informative message.
+        /// - `success`: if the output buffer has been cleared.
+        pub fn clear_output(&mut self) -> SerialPortResponse {
+            self.clear_buffers(serialport::ClearBuffer::Output, "output buffer")
+        }
 
-    #[test]
-    fn parse_str_untouched() {
-        assert_eq!("ok", parse_str_to_serial("ok"));
-    }
+        /// Does both `clear_input()` and `clear_output()`.
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: informative message.
+        /// - `success`: if both buffers have been cleared.
+        pub fn clear_all(&mut self) -> SerialPortResponse {
+            self.clear_buffers(serialport::ClearBuffer::All, "input and output buffers")
+        }
 
-    #[test]
-    fn parse_str_hex() {
-        assert_eq!("\x02#", parse_str_to_serial("0x020x23"));
-        assert_eq!(
-            "\x02iii\x17ii\x03",
-            parse_str_to_serial("0x02iii0x17ii0x03")
+        /// Returns the number of bytes that can be read without waiting: the ones
+        /// received by the driver, and the ones read ahead by the other reads.
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: the number of bytes, or an informative message.
+        /// - `success`: if the number of bytes could be known.
+        pub fn bytes_available(&mut self) -> SerialPortResponse {
+            let port = match self.backend.if_open_mut() {
+                Some(port) => port,
+                None => {
+                    return SerialPortResponse {
+                        success: false,
+                        content: "No port is currently open".to_string(),
+                    }
+                }
+            };
+
+            match port.bytes_to_read() {
+                Ok(t) => SerialPortResponse {
+                    success: true,
+                    content: (t as usize + self.read_buffer.len()).to_string(),
+                },
+                Err(e) => SerialPortResponse {
+                    success: false,
+                    content: format!("Could not get the number of bytes to read: {}", e),
+                },
+            }
+        }
+
+        fn clear_buffers(
+            &mut self,
+            buffer: serialport::ClearBuffer,
+            name: &str,
+        ) -> SerialPortResponse {
+            let port = match self.backend.if_open_mut() {
+                Some(port) => port,
+                None => {
+                    return SerialPortResponse {
+                        success: false,
+                        content: "No port is currently open".to_string(),
+                    }
+                }
+            };
+
+            if buffer != serialport::ClearBuffer::Output {
+                self.read_buffer.clear();
+            }
+
+            match port.clear(buffer) {
+                Ok(_) => SerialPortResponse {
+                    success: true,
+                    content: format!("Cleared the {}", name),
+                },
+                Err(e) => SerialPortResponse {
+                    success: false,
+                    content: format!("Could not clear the {}: {}", name, e),
+                },
+            }
+        }
+
+        /// Reads up to the read buffer size of the port from the opened serial port,
+        /// see `with_buffer_size()`.
+        ///
+        /// The bytes read are decoded by the codec pipeline, if any, in reverse order.
+        ///
+        /// # Paramters
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: The characters read from the serial port, or an informative message.
+        /// - `success`: if the chars has been correctly read from the serial port.
+        pub async fn read_once(&mut self) -> SerialPortResponse {
+            self.read_n(self.read_buffer_size).await
+        }
+
+        /// Reads as `read_once()` does.
+        ///
+        /// # Returns
+        ///
+        /// The characters read, or the error: `NoPortOpen`, `Timeout` if nothing
+        /// came within the read timeout, or `Io`.
+        pub async fn try_read_once(&mut self) -> Result<String, error::SerialError> {
+            self.try_read_n_bytes(self.read_buffer_size)
+                .await
+                .map(|read| String::from_utf8_lossy(&read).to_string())
+        }
+
+        /// Reads as `read_once()` does, up to `max_bytes` bytes.
+        ///
+        /// # Paramters
+        ///
+        /// - `max_bytes`: The maximum number of bytes to read, in place of the read
+        ///   buffer size of the port. Zero is rejected.
+        ///
+        /// # Returns
+        ///
+        /// A `SerialPortResponse` containing:
+        /// - `content`: The characters read from the serial port, or an informative message.
+        /// - `success`: if the chars has been correctly read from the serial port.
+        pub async fn read_n(&mut self, max_bytes: usize) -> SerialPortResponse {
+            let (success, read) = self.read_n_bytes(max_bytes).await;
+
+            SerialPortResponse {
+                success,
+                content: String::from_utf8_lossy(&read).to_string(),
+            }
+        }
+
+        /// Reads as `read_once()` does, giving the bytes as is rather than decoded as
+        /// UTF-8, for binary data.
+        ///
+        /// # Returns
+        ///
+        /// - if the bytes have been correctly read from the serial port.
+        /// - The bytes read, after the codec pipeline if any, or an informative message.
+        pub async fn read_once_bytes(&mut self) -> (bool, Vec<u8>) {
+            self.read_n_bytes(self.read_buffer_size).await
+        }
+
+        /// Reads as `read_once_bytes()` does, up to `max_bytes` bytes, see `read_n()`.
+        pub async fn read_n_bytes(&mut self, max_bytes: usize) -> (bool, Vec<u8>) {
+            match self.try_read_n_bytes(max_bytes).await {
+                Ok(read) => (true, read),
+                Err(e) => (false, e.to_string().into_bytes()),
+            }
+        }
+
+        /// Reads as `read_n_bytes()` does.
+        ///
+        /// # Returns
+        ///
+        /// The bytes read, or the error: `InvalidInput` for 0 bytes, `NoPortOpen`,
+        /// `Timeout` if nothing came within the read timeout, or `Io`.
+        pub async fn try_read_n_bytes(
+            &mut self,
+            max_bytes: usize,
+        ) -> Result<Vec<u8>, error::SerialError> {
+            self.last_io_elapsed = time::Duration::from_millis(0);
+
+            if max_bytes == 0 {
+                return Err(error::SerialError::InvalidInput(
+                    "Can not read 0 bytes".to_string(),
+                ));
+            }
+
+            if !self.backend.is_open() {
+                return Err(error::SerialError::NoPortOpen);
+            }
+
+            // Only zeroed when it grows, then reused by every read no larger. Taken out
+            // for the time of the read, `read_port()` borrowing the whole port.
+            let mut scratch = std::mem::take(&mut self.read_scratch);
+            if scratch.len() < max_bytes {
+                scratch.resize(max_bytes, 0);
+            }
+
+            let read = if !self.read_buffer.is_empty() {
+                let len = self.read_buffer.len().min(max_bytes);
+                scratch[..len].copy_from_slice(&self.read_buffer[..len]);
+                self.read_buffer.drain(..len);
+                payloads::record(&mut self.last_received, &scratch[..len]);
+                Ok(len)
+            } else {
+                self.read_port(&mut scratch[..max_bytes]).await
+            };
+
+            let read = match read {
+                Ok(len) if self.codecs.is_empty() => Ok(scratch[..len].to_vec()),
+                Ok(len) => codec::decode_with(&self.codecs, &scratch[..len]).map_err(|e| {
+                    error::SerialError::Io(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Could not decode the read bytes: {}", e),
+                    ))
+                }),
+                Err(e) => Err(error::SerialError::read(e)),
+            };
+            self.read_scratch = scratch;
+
+            read
+        }
+
+        /// Reads from the open port into `buf`, as `AsyncReadExt::read()` does, for up
+        /// to the read timeout.
+        ///
+        /// Every read of the port goes through it, for the bytes read to be counted
+        /// by the stats, recorded by the byte trace, mirrored to the traffic hooks
+        /// and kept as `last_received()`. A read failing because the device is gone
+        /// closes the port, see `on_event()`.
+        ///
+        /// # Returns
+        ///
+        /// The number of bytes read, or the error of the read: `NotConnected` if no
+        /// port is open, `TimedOut` if nothing came within the timeout of the port.
+        pub(crate) async fn read_port(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let port = self.backend.if_open_mut().ok_or_else(no_port_error)?;
+
+            let (read, elapsed) = read_chunk_into(port, buf).await;
+            self.last_io_elapsed = elapsed;
+            self.stats.record_read(&read);
+
+            match &read {
+                Ok(t) => {
+                    let read = &buf[..*t];
+                    if let Some(trace) = self.byte_trace.as_mut() {
+                        trace.record(read, trace::Direction::Received);
+                    }
+                    monitor::tap(&self.traffic_hooks, trace::Direction::Received, read);
+                    payloads::record(&mut self.last_received, read);
+                }
+                Err(e) => self.check_disconnect(e).await,
+            }
+
+            read
+        }
+
+        /// Reads as `read_port()` does, waiting up to `timeout` rather than for the
+        /// read timeout, which the port is left with afterwards.
+        pub(crate) async fn read_port_within(
+            &mut self,
+            buf: &mut [u8],
+            timeout: time::Duration,
+        ) -> io::Result<usize> {
+            let port = self.backend.if_open_mut().ok_or_else(no_port_error)?;
+            port.set_timeout(timeout)?;
+
+            let read = self.read_port(buf).await;
+
+            // The port may have been closed by a disconnection.
+            if let Some(port) = self.backend.if_open_mut() {
+                let _ = port.set_timeout(self.read_timeout);
+            }
+
+            read
+        }
+
+        /// Returns a list of available ports.
+        pub fn get_available_port_names() -> Vec<String> {
+            let ports = match serialport::available_ports() {
+                Ok(ports) => ports,
+                Err(_) => return vec!["No ports available".to_string()],
+            };
+
+            if ports.is_empty() {
+                return vec!["No ports available".to_string()];
+            }
+
+            let mut port_names = vec![];
+
+            for p in ports {
+                port_names.push(p.port_name);
+            }
+
+            port_names
+        }
+    }
+
+    /// Gives the reason why `port_path` could not be opened.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn open_error_response(port_path: &str, e: serialport::Error) -> SerialPortResponse {
+        open_error(port_path, e).into()
+    }
+
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn open_error(port_path: &str, e: serialport::Error) -> error::SerialError {
+        #[cfg(unix)]
+        let busy = owner::is_busy_error(&e);
+        let e = io::Error::from(e);
+
+        #[cfg(unix)]
+        {
+            if busy {
+                let message = match owner::find_port_owner(port_path) {
+                    Some(owner) => format!("Could not open the port, it is held by {}", owner),
+                    None => "Could not open the port, it is busy".to_string(),
+                };
+                return error::SerialError::Io(io::Error::new(e.kind(), message));
+            }
+        }
+
+        // Only looked up on failure, the enumeration being slow. The ports missing
+        // from the list (ptys for ex.) may still be opened.
+        if !network::is_network_path(port_path) && !is_available(port_path) {
+            return error::SerialError::Io(io::Error::new(
+                e.kind(),
+                format!(
+                    "Could not open the port, {} is not among the available ports",
+                    port_path
+                ),
+            ));
+        }
+
+        error::SerialError::Io(io::Error::new(e.kind(), "Could not open the port"))
+    }
+
+    /// Returns if `port_path` is listed by `get_available_port_names()`.
+    fn is_available(port_path: &str) -> bool {
+        serialport::available_ports()
+            .unwrap_or_default()
+            .iter()
+            .any(|port| port.port_name == port_path)
+    }
+
+    /// Implementation of `send_once`, shared with the writer half of a split port,
+    /// in the RS-485 mode of the port it has been split from.
+    async fn send_once_to(
+        port: &mut dyn backend::SerialBackend,
+        rs485: Option<rs485::Rs485Mode>,
+        message: &str,
+    ) -> SerialPortResponse {
+        let output = parse_str_to_serial(message);
+        let timeout = port.timeout();
+        let (t, result) = rs485::write_half_duplex(port, rs485, output.as_bytes(), timeout).await;
+
+        write_response(t, output.len(), result)
+    }
+
+    /// Waits for `io` for up to `timeout`, failing with `TimedOut` after it. `io` is
+    /// polled once even with a zero timeout.
+    pub(crate) async fn within<T>(
+        timeout: time::Duration,
+        io: impl Future<Output = io::Result<T>>,
+    ) -> io::Result<T> {
+        match tokio::time::timeout(timeout, io).await {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Operation timed out",
+            )),
+        }
+    }
+
+    /// Writes `output`, for up to `write_timeout`.
+    pub(crate) async fn write_with_timeout(
+        port: &mut dyn backend::SerialBackend,
+        output: &[u8],
+        write_timeout: time::Duration,
+    ) -> io::Result<usize> {
+        within(write_timeout, port.write(output)).await
+    }
+
+    /// Writes as `AsyncWriteExt::write_all()` does, then flushes `port`, for the
+    /// bytes to leave the OS buffer. Each write and the flush wait for up to `timeout`.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes written, also on failure, and the error if any.
+    pub(crate) async fn write_all_to<W: AsyncWrite + Unpin + ?Sized>(
+        port: &mut W,
+        output: &[u8],
+        timeout: time::Duration,
+    ) -> (usize, io::Result<()>) {
+        let mut written = 0;
+
+        while written < output.len() {
+            match within(timeout, port.write(&output[written..])).await {
+                Ok(0) => return (written, Err(io::ErrorKind::WriteZero.into())),
+                Ok(t) => written += t,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return (written, Err(e)),
+            }
+        }
+
+        (written, within(timeout, port.flush()).await)
+    }
+
+    /// Gives the outcome of the write of `len` bytes, `written` of them having
+    /// been written.
+    pub(crate) fn write_response(
+        written: usize,
+        len: usize,
+        result: io::Result<()>,
+    ) -> SerialPortResponse {
+        write_result(written, len, result).into()
+    }
+
+    fn write_result(
+        written: usize,
+        len: usize,
+        result: io::Result<()>,
+    ) -> Result<String, error::SerialError> {
+        let progress = if written == 0 || written == len {
+            String::new()
+        } else {
+            format!(" after {} of {} bytes", written, len)
+        };
+
+        match result {
+            Ok(_) => Ok("Request sent".to_string()),
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => Err(error::SerialError::Timeout(
+                format!("Serial write timed out{}", progress),
+            )),
+            Err(e) => Err(error::SerialError::io(
+                &format!("Serial write error{}", progress),
+                e,
+            )),
+        }
+    }
+
+    /// Implementation of `read_once`, shared with the reader half of a split port.
+    async fn read_once_from(port: &mut dyn backend::SerialBackend) -> SerialPortResponse {
+        let mut serial_buf = [0; SERIAL_READ_BUFFER_SIZE];
+
+        match read_chunk_into(port, &mut serial_buf).await.0 {
+            Ok(t) => SerialPortResponse {
+                success: true,
+                content: String::from_utf8_lossy(&serial_buf[..t]).to_string(),
+            },
+            Err(e) => read_error_response(e),
+        }
+    }
+
+    /// Reads up to `serial_buf.len()` bytes into `serial_buf`, for up to the timeout
+    /// of `port`.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes read, and the time the read took.
+    async fn read_chunk_into(
+        port: &mut dyn backend::SerialBackend,
+        serial_buf: &mut [u8],
+    ) -> (io::Result<usize>, time::Duration) {
+        let started = time::Instant::now();
+        let read = within(port.timeout(), port.read(serial_buf)).await;
+        let elapsed = started.elapsed();
+
+        if let Ok(t) = &read {
+            tracing::info!(
+                "From serial: {}",
+                String::from_utf8_lossy(&serial_buf[..*t])
+            );
+        }
+
+        (read, elapsed)
+    }
+
+    /// The error of the `io` methods called without open port.
+    pub(crate) fn no_port_error() -> io::Error {
+        io::Error::new(io::ErrorKind::NotConnected, "No port is currently open")
+    }
+
+    fn read_error_response(e: io::Error) -> SerialPortResponse {
+        error::SerialError::read(e).into()
+    }
+
+    use std::char;
+
+    const CHAR_0_AS_U32: u32 = '0' as u32;
+    const CHAR_9_AS_U32: u32 = '9' as u32;
+    const CHAR_A_AS_U32: u32 = 'A' as u32;
+    const CHAR_F_AS_U32: u32 = 'F' as u32;
+    const CHAR_LOWER_A_AS_U32: u32 = 'a' as u32;
+    const CHAR_LOWER_F_AS_U32: u32 = 'f' as u32;
+
+    /// Returns the value of the hex digit `c`, in upper or lower case.
+    fn hex_nibble(c: char) -> Option<u32> {
+        let c = c as u32;
+
+        if (CHAR_0_AS_U32..=CHAR_9_AS_U32).contains(&c) {
+            Some(c - CHAR_0_AS_U32)
+        } else if (CHAR_A_AS_U32..=CHAR_F_AS_U32).contains(&c) {
+            Some(c - CHAR_A_AS_U32 + 10)
+        } else if (CHAR_LOWER_A_AS_U32..=CHAR_LOWER_F_AS_U32).contains(&c) {
+            Some(c - CHAR_LOWER_A_AS_U32 + 10)
+        } else {
+            None
+        }
+    }
+
+    /// Output of `parse_escapes()`, as a string or as raw bytes.
+    trait EscapeSink {
+        fn push_char(&mut self, c: char);
+
+        /// Pushes the value of a hex escape.
+        ///
+        /// # Returns
+        ///
+        /// If the value can be represented, the escape being kept literally otherwise.
+        fn push_value(&mut self, value: u32) -> bool;
+    }
+
+    impl EscapeSink for String {
+        fn push_char(&mut self, c: char) {
+            self.push(c);
+        }
+
+        fn push_value(&mut self, value: u32) -> bool {
+            match char::from_u32(value) {
+                Some(c) => {
+                    self.push(c);
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    impl EscapeSink for Vec<u8> {
+        fn push_char(&mut self, c: char) {
+            let mut utf8 = [0; 4];
+            self.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+        }
+
+        fn push_value(&mut self, value: u32) -> bool {
+            match std::convert::TryFrom::try_from(value) {
+                Ok(byte) => {
+                    self.push(byte);
+                    true
+                }
+                Err(_) => false,
+            }
+        }
+    }
+
+    /// Mainly parses written/ascii hex value to real hex value (from 0x00 to 0xFF).
+    ///
+    /// The values are pushed as characters: above 0x7F, they are written as
+    /// their UTF-8 encoding (`0xFF` giving `0xC3 0xBF`). See `parse_str_to_bytes()`
+    /// to write them as raw bytes.
+    ///
+    /// A single `0x` can be followed by several values (`0xAABB` giving `0xAA 0xBB`),
+    /// the pairs of hex digits being consumed until a non-hex character. An odd
+    /// digit ending the run (`B` of `0xAAB`), as well as an incomplete escape
+    /// (`0x` or `0x2` for ex.), is kept literally.
+    ///
+    /// The C-style escapes `\n`, `\r`, `\t`, `\0` and `\\` are parsed too, the
+    /// unknown ones (`\q` for ex.) being kept literally.
+    // pub fn parse_str_to_serial(s: String) -> String {
+    pub fn parse_str_to_serial(s: &str) -> String {
+        let mut parsed_s = String::from("");
+
+        parse_escapes(s, &mut parsed_s);
+        parsed_s
+    }
+
+    /// Byte mode of `parse_str_to_serial()`: the value of each hex escape is
+    /// written as a single raw byte (`0xFF` giving `0xFF`), for byte-oriented
+    /// protocols. The rest of the string is written as UTF-8.
+    pub fn parse_str_to_bytes(s: &str) -> Vec<u8> {
+        let mut parsed_s = vec![];
+
+        parse_escapes(s, &mut parsed_s);
+        parsed_s
+    }
+
+    /// Formats `bytes` as hex escapes: the reverse of `parse_str_to_bytes()`, for
+    /// display (`[0x02, 0xFF]` giving `0x020xFF`).
+    pub fn serial_to_hex_str(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect()
+    }
+
+    /// Formats `bytes` as `serial_to_hex_str()` does, the printable ASCII characters
+    /// being kept as is (`b"\x02ok"` giving `0x02ok`).
+    ///
+    /// The characters `parse_str_to_bytes()` would take as part of an escape are
+    /// escaped too, for the round-trip to stay lossless: the hex digits following
+    /// an escape, a `0` followed by `x` or `X`, and the backslashes.
+    pub fn serial_to_escaped_str(bytes: &[u8]) -> String {
+        let mut escaped = String::new();
+        let mut after_escape = false;
+
+        for (i, byte) in bytes.iter().enumerate() {
+            let starts_escape = *byte == b'\\'
+                || *byte == b'0' && matches!(bytes.get(i + 1), Some(b'x') | Some(b'X'));
+            let extends_escape = after_escape && byte.is_ascii_hexdigit();
+
+            after_escape =
+                !(byte.is_ascii_graphic() || *byte == b' ') || starts_escape || extends_escape;
+            if after_escape {
+                escaped.push_str(&format!("0x{:02X}", byte));
+            } else {
+                escaped.push(*byte as char);
+            }
+        }
+
+        escaped
+    }
+
+    /// Number of bytes per row of `hexdump()`.
+    pub const HEXDUMP_ROW_LEN: usize = 16;
+
+    /// Formats `bytes` as rows of `HEXDUMP_ROW_LEN` bytes, with the offset of their
+    /// first byte, their hex values and their ASCII characters, the non-printable
+    /// ones shown as `.`:
+    ///
+    /// `00000000  02 6F 6B 03 ...  |.ok.|`
+    ///
+    /// The rows are separated by newlines, without one after the last row.
+    pub fn hexdump(bytes: &[u8]) -> String {
+        let rows: Vec<String> = bytes
+            .chunks(HEXDUMP_ROW_LEN)
+            .enumerate()
+            .map(|(i, row)| {
+                let hex: Vec<String> = row.iter().map(|byte| format!("{:02X}", byte)).collect();
+                let ascii: String = row
+                    .iter()
+                    .map(|byte| {
+                        if byte.is_ascii_graphic() || *byte == b' ' {
+                            *byte as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+
+                format!(
+                    "{:08X}  {:width$}  |{}|",
+                    i * HEXDUMP_ROW_LEN,
+                    hex.join(" "),
+                    ascii,
+                    width = HEXDUMP_ROW_LEN * 3 - 1
+                )
+            })
+            .collect();
+
+        rows.join("\n")
+    }
+
+    fn parse_escapes(s: &str, parsed_s: &mut impl EscapeSink) {
+        let vec_s = s.chars().collect::<Vec<char>>();
+        let pair_at = |i: usize| -> Option<u32> {
+            let high = hex_nibble(*vec_s.get(i)?)?;
+            let low = hex_nibble(*vec_s.get(i + 1)?)?;
+            Some((high << 4) + low)
+        };
+        let mut i = 0;
+
+        // Looking for hex in the form 0xAA, or 0xAABBCC for a run of values.
+        while i < vec_s.len() {
+            if vec_s[i] == '0' && matches!(vec_s.get(i + 1), Some('x') | Some('X')) {
+                let mut end = i + 2;
+                while let Some(hex_int) = pair_at(end) {
+                    if !parsed_s.push_value(hex_int) {
+                        break;
+                    }
+                    end += 2;
+                }
+
+                // An escape without complete pair (`0x2` for ex.) is kept literally,
+                // as is an odd nibble ending a run.
+                if end > i + 2 {
+                    i = end;
+                    continue;
+                }
+            }
+
+            if vec_s[i] == '\\' {
+                if let Some(value) = vec_s.get(i + 1).and_then(|c| c_escape(*c)) {
+                    parsed_s.push_value(value);
+                    i += 2;
+                    continue;
+                }
+            }
+
+            parsed_s.push_char(vec_s[i]);
+            i += 1;
+        }
+    }
+
+    /// Returns the value of the C-style escape `\c`, if known.
+    fn c_escape(c: char) -> Option<u32> {
+        match c {
+            'n' => Some(0x0A),
+            'r' => Some(0x0D),
+            't' => Some(0x09),
+            '0' => Some(0x00),
+            '\\' => Some(0x5C),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::serial_port::*;
+
+    #[test]
+    fn parse_str_untouched() {
+        assert_eq!("ok", parse_str_to_serial("ok"));
+    }
+
+    #[test]
+    fn parse_str_hex() {
+        assert_eq!("\x02#", parse_str_to_serial("0x020x23"));
+        assert_eq!(
+            "\x02iii\x17ii\x03",
+            parse_str_to_serial("0x02iii0x17ii0x03")
+        );
+    }
+
+    #[test]
+    fn parse_str_keeps_the_tail() {
+        assert_eq!("abcd", parse_str_to_serial("abcd"));
+        assert_eq!("\x02xy", parse_str_to_serial("0x02xy"));
+        assert_eq!("a", parse_str_to_serial("a"));
+        assert_eq!("ab", parse_str_to_serial("ab"));
+        assert_eq!("abc", parse_str_to_serial("abc"));
+        assert_eq!("\x02x", parse_str_to_serial("0x02x"));
+        assert_eq!("\x02xyz", parse_str_to_serial("0x02xyz"));
+        assert_eq!("", parse_str_to_serial(""));
+    }
+
+    #[test]
+    fn parse_str_hex_run() {
+        assert_eq!(vec![0xaa, 0xbb, 0xcc], parse_str_to_bytes("0xAABBCC"));
+        assert_eq!(vec![0xaa, b'B'], parse_str_to_bytes("0xAAB"));
+        assert_eq!("\x02\x03 ok", parse_str_to_serial("0x0203 ok"));
+        assert_eq!("\x02a6", parse_str_to_serial("0x02616"));
+        // The run stops at a new escape.
+        assert_eq!("\x02#", parse_str_to_serial("0x020x23"));
+    }
+
+    #[test]
+    fn hex_str_round_trip() {
+        let bytes = [0x00, 0x02, b'a', 0x7f, 0x80, 0xff];
+
+        assert_eq!("0x000x020x610x7F0x800xFF", serial_to_hex_str(&bytes));
+        assert_eq!(
+            bytes.to_vec(),
+            parse_str_to_bytes(&serial_to_hex_str(&bytes))
+        );
+        assert_eq!(
+            "\x02\x1b\x03",
+            parse_str_to_serial(&serial_to_hex_str(b"\x02\x1b\x03"))
+        );
+    }
+
+    #[test]
+    fn escaped_str_round_trip() {
+        assert_eq!("0x02ok 0x03", serial_to_escaped_str(b"\x02ok \x03"));
+        // Hex letters after an escape, and a literal 0x.
+        assert_eq!("0x020x610x62g", serial_to_escaped_str(b"\x02abg"));
+        assert_eq!("a0x30x0", serial_to_escaped_str(b"a0x0"));
+
+        assert_eq!(r"0x5Cn", serial_to_escaped_str(br"\n"));
+
+        for bytes in [
+            &b"\x02abg"[..],
+            b"a0x0",
+            b"0X1",
+            br"\\t",
+            &[0xff, b'f', b' '],
+        ] {
+            assert_eq!(
+                bytes.to_vec(),
+                parse_str_to_bytes(&serial_to_escaped_str(bytes))
+            );
+        }
+    }
+
+    #[test]
+    fn hexdump_rows() {
+        let mut bytes: Vec<u8> = (0x3C..0x50).collect();
+        bytes[1] = 0x00;
+        bytes[18] = 0x7F;
+
+        assert_eq!(
+            "00000000  3C 00 3E 3F 40 41 42 43 44 45 46 47 48 49 4A 4B  |<.>?@ABCDEFGHIJK|\n\
+             00000010  4C 4D 7F 4F                                      |LM.O|",
+            hexdump(&bytes)
+        );
+        assert_eq!("", hexdump(&[]));
+    }
+
+    #[test]
+    fn parse_str_c_escapes() {
+        assert_eq!("a\nb", parse_str_to_serial(r"a\nb"));
+        assert_eq!("\t\t", parse_str_to_serial(r"\t\t"));
+        assert_eq!(r"\n", parse_str_to_serial(r"\\n"));
+        assert_eq!("\r\n\0", parse_str_to_serial(r"\r\n\0"));
+        assert_eq!(r"\q \", parse_str_to_serial(r"\q \"));
+        assert_eq!(vec![0x02, b'\n'], parse_str_to_bytes(r"0x02\n"));
+    }
+
+    #[test]
+    fn parse_str_lowercase_hex() {
+        assert_eq!("*", parse_str_to_serial("0x2a"));
+        assert_eq!(vec![0xff], parse_str_to_bytes("0xff"));
+        assert_eq!(vec![0xaf, 0xaf], parse_str_to_bytes("0xAf0XaF"));
+        assert_eq!("0xag", parse_str_to_serial("0xag"));
+    }
+
+    #[test]
+    fn parse_str_truncated_escape() {
+        assert_eq!("ok 0", parse_str_to_serial("ok 0"));
+        assert_eq!("ok 0x", parse_str_to_serial("ok 0x"));
+        assert_eq!("ok 0x2", parse_str_to_serial("ok 0x2"));
+        assert_eq!("\x020x2", parse_str_to_serial("0x020x2"));
+        assert_eq!(b"0x2".to_vec(), parse_str_to_bytes("0x2"));
+    }
+
+    #[test]
+    fn parse_str_raw_bytes() {
+        assert_eq!(vec![0xff], parse_str_to_bytes("0xFF"));
+        assert_eq!(vec![b'a', 0x80], parse_str_to_bytes("a0x80"));
+        // As characters, encoded in UTF-8.
+        assert_eq!("\u{ff}".as_bytes(), parse_str_to_serial("0xFF").as_bytes());
+
+        let mut port = SerialPort::new();
+        assert_eq!(vec![0xc2, 0x80], port.encode_message("0x80"));
+        port.set_byte_escapes(true);
+        assert_eq!(vec![0x80], port.encode_message("0x80"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn read_buffer_size() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::with_buffer_size(256);
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(
+            port.set_read_timeout(std::time::Duration::from_millis(100))
+                .success
+        );
+
+        master.write_all(&[b'a'; 300]).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(256, port.read_once().await.content.len());
+        assert_eq!(10, port.read_n(10).await.content.len());
+
+        let resp = port.read_n(0).await;
+        assert!(!resp.success);
+        assert_eq!("Can not read 0 bytes", resp.content);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn read_raw_bytes() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        master.write_all(&[0x00, 0x80, 0xFF]).unwrap();
+        assert_eq!((true, vec![0x00, 0x80, 0xFF]), port.read_once_bytes().await);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn send_raw_bytes() {
+        use std::io::Read;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(!port.send_bytes(&[0x00]).await.success);
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        // 0x41 not taken as an escape.
+        assert!(port.send_bytes(b"\x00\xFF0x41").await.success);
+        let mut sent = [0; 6];
+        master.read_exact(&mut sent).unwrap();
+        assert_eq!(b"\x00\xFF0x41", &sent);
+    }
+
+    /// Accepts up to `per_write` bytes per write, then fails after `capacity`.
+    struct SlowWriter {
+        written: Vec<u8>,
+        per_write: usize,
+        capacity: usize,
+        flushed: bool,
+    }
+
+    impl tokio::io::AsyncWrite for SlowWriter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            if self.written.len() >= self.capacity {
+                return std::task::Poll::Ready(Err(std::io::ErrorKind::TimedOut.into()));
+            }
+            let t = buf
+                .len()
+                .min(self.per_write)
+                .min(self.capacity - self.written.len());
+            self.written.extend_from_slice(&buf[..t]);
+            std::task::Poll::Ready(Ok(t))
+        }
+
+        fn poll_flush(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            self.flushed = true;
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn write_all_partial_writes() {
+        let mut writer = SlowWriter {
+            written: vec![],
+            per_write: 2,
+            capacity: 10,
+            flushed: false,
+        };
+        let (t, result) = super::serial_port::write_all_to(
+            &mut writer,
+            b"hello",
+            std::time::Duration::from_millis(10),
+        )
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(5, t);
+        assert_eq!(b"hello".to_vec(), writer.written);
+        assert!(writer.flushed);
+
+        writer.flushed = false;
+        let (t, result) = super::serial_port::write_all_to(
+            &mut writer,
+            b"world!",
+            std::time::Duration::from_millis(10),
+        )
+        .await;
+        assert_eq!(5, t);
+        assert!(!writer.flushed);
+        let resp = super::serial_port::write_response(t, 6, result);
+        assert!(!resp.success);
+        assert_eq!("Serial write timed out after 5 of 6 bytes", resp.content);
+    }
+
+    #[test]
+    fn clear_without_port() {
+        let mut port = SerialPort::new();
+
+        for resp in [port.clear_input(), port.clear_output(), port.clear_all()].iter() {
+            assert!(!resp.success);
+            assert_eq!("No port is currently open", resp.content);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn clear_input_drops_pending_bytes() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        master.write_all(b"stale").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let resp = port.clear_input();
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!("Cleared the input buffer", resp.content);
+
+        master.write_all(b"new").unwrap();
+        assert_eq!("new", port.read_once().await.content);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn bytes_available() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        let resp = port.bytes_available();
+        assert!(!resp.success);
+        assert_eq!("No port is currently open", resp.content);
+
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert_eq!("0", port.bytes_available().content);
+        master.write_all(b"abc").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!("3", port.bytes_available().content);
+    }
+
+    #[tokio::test]
+    async fn response_into_result() {
+        let mut port = SerialPort::new();
+        assert_eq!(
+            Err("No port is currently open".to_string()),
+            port.read_once().await.into_result()
+        );
+
+        let resp = SerialPortResponse {
+            success: true,
+            content: "ok".to_string(),
+        };
+        assert_eq!(Ok("ok".to_string()), resp.into_result());
+    }
+
+    #[tokio::test]
+    async fn open_invalid_input() {
+        let mut port = SerialPort::new();
+
+        let resp = port.open_port("", 9600).await;
+        assert!(!resp.success);
+        assert_eq!("The port path can not be empty", resp.content);
+
+        let resp = port.open_port("/dev/null", 0).await;
+        assert!(!resp.success);
+        assert_eq!(
+            "Invalid port config: The baudrate can not be 0",
+            resp.content
+        );
+
+        let resp = port.open_port("/dev/does-not-exist", 9600).await;
+        assert!(!resp.success);
+        assert_eq!(
+            "Could not open the port, /dev/does-not-exist is not among the available ports",
+            resp.content
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn timeouts_of_open_port() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+
+        assert_eq!(None, port.timeouts());
+
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        let timeouts = port.timeouts().unwrap();
+        assert_eq!(timeouts.open, timeouts.read);
+        assert_eq!(timeouts.read, timeouts.write);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn separate_read_and_write_timeouts() {
+        use std::time::Duration;
+
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        port.set_open_timeout(Duration::from_millis(5));
+        port.set_write_timeout(Duration::from_millis(50));
+
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(port.set_read_timeout(Duration::from_millis(20)).success);
+        assert!(port.send_once("ok").await.success);
+
+        let timeouts = port.timeouts().unwrap();
+        assert_eq!(Duration::from_millis(5), timeouts.open);
+        assert_eq!(Duration::from_millis(50), timeouts.write);
+        assert_eq!(Duration::from_millis(20), timeouts.read);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn set_timeout_before_and_after_open() {
+        use std::time::Duration;
+
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.set_timeout(Duration::from_millis(30)).success);
+
+        // Given to the builder: a read gives up after it.
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        let started = std::time::Instant::now();
+        assert!(!port.read_once().await.success);
+        assert!(started.elapsed() >= Duration::from_millis(30));
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        let resp = port.set_timeout(Duration::from_millis(40));
+        assert_eq!("Timeouts set to 40ms", resp.content);
+        let timeouts = port.timeouts().unwrap();
+        assert_eq!(timeouts.open, timeouts.read);
+        assert_eq!(Duration::from_millis(40), timeouts.write);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn raw_fd_follows_port_state() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+
+        assert_eq!(None, port.raw_fd());
+
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(port.raw_fd().is_some());
+
+        assert!(port.close_port().await.success);
+        assert_eq!(None, port.raw_fd());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn open_with_settle_delay() {
+        use std::time::{Duration, Instant};
+
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+
+        let started = Instant::now();
+        let resp = port
+            .open_port_with_delay(&slave_path, 9600, Duration::from_millis(30))
+            .await;
+        assert!(resp.success);
+        assert!(started.elapsed() >= Duration::from_millis(30));
+        assert!(resp.content.ends_with("after a settle delay of 30ms"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn send_encoded_in_chunks() {
+        use std::io::Read;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(port.set_codecs(&["crc16".to_string()]).success);
+
+        let output = port.encode_message("ok");
+        for chunk in output.chunks(3) {
+            assert!(port.send_encoded(chunk).await.success);
+        }
+
+        let mut received = vec![0; output.len()];
+        master.read_exact(&mut received).unwrap();
+        assert_eq!(
+            codec::encode_with(&[Box::new(codec::Crc16)], b"ok"),
+            received
         );
     }
 }
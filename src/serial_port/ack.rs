@@ -0,0 +1,225 @@
+//! Byte-by-byte sending, each byte being acknowledged by the device.
+
+use std::{io, time};
+
+use super::error::SerialError;
+use super::SerialPort;
+
+/// Ack timeout used when none is given.
+pub const DEFAULT_ACK_TIMEOUT_MS: u64 = 100;
+
+/// Represents a response given by `SerialPort::send_with_acks`.
+#[derive(Debug)]
+pub struct AckedSendResponse {
+    pub success: bool,
+    /// Informative message.
+    pub content: String,
+    /// Number of bytes sent and acknowledged, which is also the offset of the
+    /// failing byte on failure.
+    pub acked: usize,
+}
+
+impl SerialPort {
+    /// Sends `payload` one byte at a time, waiting for the device to acknowledge
+    /// each byte with `expected_ack` before sending the next one.
+    ///
+    /// # Paramters
+    ///
+    /// - `payload`: The bytes to send.
+    /// - `expected_ack`: The byte the device answers with after each received byte.
+    /// - `ack_timeout`: How long to wait for each ack. Zero means `DEFAULT_ACK_TIMEOUT_MS`.
+    ///
+    /// # Returns
+    ///
+    /// An `AckedSendResponse` containing:
+    /// - `content`: informative message, with the failing offset on failure.
+    /// - `success`: if every byte has been acknowledged.
+    /// - `acked`: the number of acknowledged bytes.
+    pub async fn send_with_acks(
+        &mut self,
+        payload: &[u8],
+        expected_ack: u8,
+        ack_timeout: time::Duration,
+    ) -> AckedSendResponse {
+        if !self.backend.is_open() {
+            return AckedSendResponse {
+                success: false,
+                content: "No port is currently open".to_string(),
+                acked: 0,
+            };
+        }
+
+        let ack_timeout = if ack_timeout.as_nanos() == 0 {
+            time::Duration::from_millis(DEFAULT_ACK_TIMEOUT_MS)
+        } else {
+            ack_timeout
+        };
+
+        let mut resp = AckedSendResponse {
+            success: true,
+            content: format!("{} bytes sent and acknowledged", payload.len()),
+            acked: payload.len(),
+        };
+
+        for (offset, byte) in payload.iter().enumerate() {
+            let failure = match self.send_acked_byte(*byte, ack_timeout).await {
+                Ok(Some(ack)) if ack == expected_ack => continue,
+                Ok(Some(ack)) => format!(
+                    "Ack mismatch at offset {}: expected 0x{:02X}, received 0x{:02X}",
+                    offset, expected_ack, ack
+                ),
+                Ok(None) => format!("Ack timed out at offset {}", offset),
+                Err(e) => format!("Serial error at offset {}: {}", offset, e),
+            };
+
+            resp = AckedSendResponse {
+                success: false,
+                content: failure,
+                acked: offset,
+            };
+            break;
+        }
+
+        resp
+    }
+
+    /// Writes one byte like the other writes, then reads the ack that follows within
+    /// `ack_timeout`, or `None` if none came.
+    ///
+    /// An ack already received, left by a previous read, is taken first.
+    async fn send_acked_byte(
+        &mut self,
+        byte: u8,
+        ack_timeout: time::Duration,
+    ) -> Result<Option<u8>, SerialError> {
+        self.try_write_encoded(&[byte]).await?;
+
+        if !self.read_buffer.is_empty() {
+            return Ok(Some(self.read_buffer.remove(0)));
+        }
+
+        let mut ack = [0; 1];
+        match self.read_port_within(&mut ack, ack_timeout).await {
+            Ok(0) => Err(SerialError::read(io::ErrorKind::UnexpectedEof.into())),
+            Ok(_) => Ok(Some(ack[0])),
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => Ok(None),
+            Err(e) => Err(SerialError::read(e)),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::thread;
+
+    const ACK: u8 = 0x06;
+    const NAK: u8 = 0x15;
+
+    async fn open_pty_port() -> (SerialPort, serialport::TTYPort) {
+        let (master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        (port, master)
+    }
+
+    /// Plays a device answering each byte with the corresponding ack of `acks`.
+    fn answer(mut device: serialport::TTYPort, acks: Vec<u8>) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut received = vec![];
+            for ack in acks {
+                let mut byte = [0; 1];
+                device.read_exact(&mut byte).unwrap();
+                received.push(byte[0]);
+                device.write_all(&[ack]).unwrap();
+            }
+            thread::sleep(time::Duration::from_millis(50));
+            received
+        })
+    }
+
+    #[tokio::test]
+    async fn all_bytes_acked() {
+        let (mut port, device) = open_pty_port().await;
+        let device = answer(device, vec![ACK, ACK, ACK]);
+
+        let resp = port
+            .send_with_acks(&[0x01, 0x02, 0x03], ACK, time::Duration::from_millis(0))
+            .await;
+
+        assert!(resp.success);
+        assert_eq!(3, resp.acked);
+        assert_eq!(vec![0x01, 0x02, 0x03], device.join().unwrap());
+    }
+
+    #[tokio::test]
+    async fn ack_mismatch_reports_offset() {
+        let (mut port, device) = open_pty_port().await;
+        let device = answer(device, vec![ACK, NAK]);
+
+        let resp = port
+            .send_with_acks(&[0x01, 0x02, 0x03], ACK, time::Duration::from_millis(0))
+            .await;
+
+        assert!(!resp.success);
+        assert_eq!(1, resp.acked);
+        assert_eq!(
+            "Ack mismatch at offset 1: expected 0x06, received 0x15",
+            resp.content
+        );
+        device.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn ack_timeout_reports_offset() {
+        let (mut port, _device) = open_pty_port().await;
+
+        let resp = port
+            .send_with_acks(&[0x01, 0x02], ACK, time::Duration::from_millis(20))
+            .await;
+
+        assert!(!resp.success);
+        assert_eq!(0, resp.acked);
+        assert_eq!("Ack timed out at offset 0", resp.content);
+    }
+
+    #[tokio::test]
+    async fn buffered_ack_taken_first() {
+        let (mut port, mut device) = open_pty_port().await;
+        port.read_buffer = vec![ACK];
+        let device = thread::spawn(move || {
+            let mut received = [0; 2];
+            device.read_exact(&mut received).unwrap();
+            device.write_all(&[ACK]).unwrap();
+            thread::sleep(time::Duration::from_millis(50));
+            received
+        });
+
+        let resp = port
+            .send_with_acks(&[0x01, 0x02], ACK, time::Duration::from_millis(0))
+            .await;
+
+        assert!(resp.success);
+        assert_eq!([0x01, 0x02], device.join().unwrap());
+    }
+
+    #[tokio::test]
+    async fn acked_bytes_counted_in_the_stats() {
+        let (mut port, device) = open_pty_port().await;
+        let device = answer(device, vec![ACK, ACK]);
+
+        let resp = port
+            .send_with_acks(&[0x01, 0x02], ACK, time::Duration::from_millis(0))
+            .await;
+
+        assert!(resp.success);
+        assert_eq!(2, port.stats().bytes_sent);
+        assert_eq!(2, port.stats().bytes_received);
+        // Each byte being a write of its own.
+        assert_eq!(vec![0x02], port.last_sent().unwrap().bytes);
+        assert_eq!(vec![ACK], port.last_received().unwrap().bytes);
+        device.join().unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+//! Restriction of the ports a server may open, for locked-down deployments where
+//! clients must not open arbitrary paths.
+
+use std::path::Path;
+
+/// The ports allowed by paths, and by USB `vid:pid` patterns.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PortAllowlist {
+    paths: Vec<String>,
+    /// `(vid, pid)`, no pid matching every product of the vendor.
+    usb_ids: Vec<(u16, Option<u16>)>,
+}
+
+impl PortAllowlist {
+    /// Builds an allowlist.
+    ///
+    /// # Paramters
+    ///
+    /// - `paths`: The allowed port paths, compared after resolving their symlinks
+    ///   (allowing `/dev/serial/by-id/...` allows the tty it points to).
+    /// - `usb_ids`: The allowed USB devices, as hex `VID:PID`, or `VID:*` for any
+    ///   product of the vendor (`0403:6001` or `0403:*` for ex.).
+    ///
+    /// # Returns
+    ///
+    /// The allowlist, or an error message for a malformed USB pattern.
+    pub fn new(paths: &[String], usb_ids: &[String]) -> Result<PortAllowlist, String> {
+        let usb_ids = usb_ids
+            .iter()
+            .map(|usb_id| parse_usb_id(usb_id))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(PortAllowlist {
+            paths: paths.to_vec(),
+            usb_ids,
+        })
+    }
+
+    /// Returns if `port_path` is listed, or is a USB port of a listed device.
+    pub fn allows(&self, port_path: &str) -> bool {
+        let resolved = resolve(port_path);
+        if self.paths.iter().any(|path| resolve(path) == resolved) {
+            return true;
+        }
+
+        if self.usb_ids.is_empty() {
+            return false;
+        }
+
+        serialport::available_ports()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|port| resolve(&port.port_name) == resolved)
+            .any(|port| match port.port_type {
+                serialport::SerialPortType::UsbPort(usb) => self.allows_usb(usb.vid, usb.pid),
+                _ => false,
+            })
+    }
+
+    /// Returns if the USB device `vid:pid` is listed.
+    pub fn allows_usb(&self, vid: u16, pid: u16) -> bool {
+        self.usb_ids.iter().any(|(allowed_vid, allowed_pid)| {
+            *allowed_vid == vid && allowed_pid.is_none_or(|allowed_pid| allowed_pid == pid)
+        })
+    }
+}
+
+fn parse_usb_id(usb_id: &str) -> Result<(u16, Option<u16>), String> {
+    let invalid = || format!("Invalid USB id '{}', expected VID:PID or VID:*", usb_id);
+
+    let (vid, pid) = usb_id.split_once(':').ok_or_else(invalid)?;
+    let vid = u16::from_str_radix(vid, 16).map_err(|_| invalid())?;
+    let pid = match pid {
+        "*" => None,
+        pid => Some(u16::from_str_radix(pid, 16).map_err(|_| invalid())?),
+    };
+
+    Ok((vid, pid))
+}
+
+fn resolve(port_path: &str) -> String {
+    match std::fs::canonicalize(Path::new(port_path)) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => port_path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_paths_and_usb_ids() {
+        let allowlist = PortAllowlist::new(
+            &["/dev/ttyS9".to_string(), "tcp://10.0.0.1:4001".to_string()],
+            &["0403:6001".to_string(), "10c4:*".to_string()],
+        )
+        .unwrap();
+
+        assert!(allowlist.allows("/dev/ttyS9"));
+        assert!(allowlist.allows("tcp://10.0.0.1:4001"));
+        assert!(!allowlist.allows("/dev/ttyS8"));
+
+        assert!(allowlist.allows_usb(0x0403, 0x6001));
+        assert!(!allowlist.allows_usb(0x0403, 0x6015));
+        assert!(allowlist.allows_usb(0x10c4, 0xea60));
+    }
+
+    #[test]
+    fn malformed_usb_id() {
+        assert!(PortAllowlist::new(&[], &["0403".to_string()]).is_err());
+        assert!(PortAllowlist::new(&[], &["0403:xyz".to_string()]).is_err());
+    }
+}
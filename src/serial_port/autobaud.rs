@@ -0,0 +1,288 @@
+//! Baudrate detection on an open port.
+//!
+//! Two methods are available:
+//!
+//! - `Heuristic`: listens at each standard baudrate and keeps the one giving the most
+//!   printable ASCII. Works with any device sending text, but is slow and easily fooled.
+//! - `Timing`: listens at a high sampling baudrate while the device sends a known
+//!   character with many edges (`0x55`, `'U'`, for ex. on reset), measures how many
+//!   sampled bits one of its bits lasts, and derives its baudrate.
+//!
+//! The timing method requires a cooperative device and only works from the UART: the
+//! driver only gives whole sampled frames, so edges falling between two frames are lost
+//! and the measure is only accurate when the device baudrate is well below the sampling
+//! one (at least 2x). USB adapters may also buffer or drop frames with framing errors.
+
+use std::{io, time};
+
+use tokio::io::AsyncReadExt;
+
+use super::backend::SerialBackend;
+use super::{within, SerialPort};
+
+/// Baudrates tried by the heuristic, and to which the timing estimate is snapped.
+pub const STANDARD_BAUDRATES: [u32; 12] = [
+    300, 600, 1200, 2400, 4800, 9600, 14400, 19200, 38400, 57600, 115200, 230400,
+];
+/// Baudrate the line is sampled at by the timing method.
+pub const TIMING_SAMPLING_BAUDRATE: u32 = 230400;
+/// How long to listen at each baudrate, when no window is given.
+pub const DEFAULT_AUTOBAUD_WINDOW_MS: u64 = 200;
+
+const AUTOBAUD_READ_TIMEOUT_MS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoBaudMethod {
+    Heuristic,
+    Timing,
+}
+
+/// Represents a response given by `SerialPort::auto_baud`.
+#[derive(Debug)]
+pub struct AutoBaudResponse {
+    pub success: bool,
+    /// Informative message.
+    pub content: String,
+    /// The detected baudrate, 0 on failure.
+    pub baudrate: u32,
+}
+
+impl AutoBaudResponse {
+    fn failure(content: String) -> AutoBaudResponse {
+        AutoBaudResponse {
+            success: false,
+            content,
+            baudrate: 0,
+        }
+    }
+}
+
+/// Returns the ratio of printable ASCII (and whitespace) chars in `bytes`.
+pub fn printable_score(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0.0;
+    }
+
+    let printable = bytes
+        .iter()
+        .filter(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+        .count();
+
+    printable as f64 / bytes.len() as f64
+}
+
+/// Estimates the baudrate of a device from frames sampled at `sampling_baudrate`.
+///
+/// Each sampled frame is rebuilt as its start bit and 8 data bits, and only the runs
+/// of identical bits delimited by 2 edges inside a frame are measured. The shortest
+/// run is one bit of the device.
+///
+/// # Returns
+///
+/// The closest standard baudrate, if at least one complete run has been measured.
+pub fn estimate_baudrate(samples: &[u8], sampling_baudrate: u32) -> Option<u32> {
+    let mut shortest_run: Option<u32> = None;
+
+    for sample in samples {
+        // Start bit, then data bits LSB first.
+        let bits: Vec<bool> = std::iter::once(false)
+            .chain((0..8).map(|i| sample & (1 << i) != 0))
+            .collect();
+
+        let edges: Vec<usize> = (1..bits.len())
+            .filter(|&i| bits[i] != bits[i - 1])
+            .collect();
+
+        for run in edges.windows(2) {
+            let run = (run[1] - run[0]) as u32;
+            shortest_run = Some(shortest_run.map_or(run, |shortest| shortest.min(run)));
+        }
+    }
+
+    let bit_baudrate = sampling_baudrate / shortest_run?;
+
+    STANDARD_BAUDRATES
+        .iter()
+        .copied()
+        .min_by_key(|baudrate| (*baudrate as i64 - bit_baudrate as i64).abs())
+}
+
+impl SerialPort {
+    /// Detects the baudrate of the device connected to the open port, and sets it.
+    ///
+    /// # Paramters
+    ///
+    /// - `method`: How to detect the baudrate, see the module documentation.
+    /// - `window`: How long to listen at each tried baudrate. Zero means the default.
+    ///
+    /// # Returns
+    ///
+    /// An `AutoBaudResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if a baudrate has been detected and set.
+    /// - `baudrate`: the detected baudrate.
+    pub async fn auto_baud(
+        &mut self,
+        method: AutoBaudMethod,
+        window: time::Duration,
+    ) -> AutoBaudResponse {
+        let port = match self.backend.if_open_mut() {
+            Some(port) => port,
+            None => return AutoBaudResponse::failure("No port is currently open".to_string()),
+        };
+
+        let window = if window.as_nanos() == 0 {
+            time::Duration::from_millis(DEFAULT_AUTOBAUD_WINDOW_MS)
+        } else {
+            window
+        };
+
+        let initial_timeout = port.timeout();
+        let initial_baudrate = port.baud_rate().unwrap_or(0);
+
+        let detected = match method {
+            AutoBaudMethod::Heuristic => detect_heuristic(port, window).await,
+            AutoBaudMethod::Timing => detect_timing(port, window).await,
+        };
+
+        let _ = port.set_timeout(initial_timeout);
+
+        let resp = match detected {
+            Ok(Some(baudrate)) => match port.set_baud_rate(baudrate) {
+                Ok(_) => {
+                    return AutoBaudResponse {
+                        success: true,
+                        content: format!("Detected a baudrate of {}", baudrate),
+                        baudrate,
+                    }
+                }
+                Err(e) => AutoBaudResponse::failure(format!(
+                    "Could not set the detected baudrate {}: {}",
+                    baudrate, e
+                )),
+            },
+            Ok(None) => AutoBaudResponse::failure("Could not detect the baudrate".to_string()),
+            Err(e) => AutoBaudResponse::failure(format!("Serial error during autobaud: {}", e)),
+        };
+
+        // Leaves the port as it was found.
+        if initial_baudrate != 0 {
+            let _ = port.set_baud_rate(initial_baudrate);
+        }
+
+        resp
+    }
+}
+
+/// Reads everything arriving during `window`.
+async fn listen(port: &mut dyn SerialBackend, window: time::Duration) -> io::Result<Vec<u8>> {
+    let mut received = vec![];
+    let mut serial_buf = [0; 64];
+    let start = time::Instant::now();
+
+    port.clear(serialport::ClearBuffer::Input)?;
+
+    while start.elapsed() < window {
+        let timeout = port.timeout();
+        match within(timeout, port.read(&mut serial_buf)).await {
+            Ok(t) => received.extend_from_slice(&serial_buf[..t]),
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(received)
+}
+
+async fn detect_heuristic(
+    port: &mut dyn SerialBackend,
+    window: time::Duration,
+) -> io::Result<Option<u32>> {
+    port.set_timeout(time::Duration::from_millis(AUTOBAUD_READ_TIMEOUT_MS))?;
+
+    let mut best: Option<(u32, f64)> = None;
+
+    for baudrate in STANDARD_BAUDRATES.iter() {
+        port.set_baud_rate(*baudrate)?;
+
+        let score = printable_score(&listen(port, window).await?);
+        if score > best.map_or(0.0, |(_, best_score)| best_score) {
+            best = Some((*baudrate, score));
+        }
+    }
+
+    Ok(best.map(|(baudrate, _)| baudrate))
+}
+
+async fn detect_timing(
+    port: &mut dyn SerialBackend,
+    window: time::Duration,
+) -> io::Result<Option<u32>> {
+    port.set_timeout(time::Duration::from_millis(AUTOBAUD_READ_TIMEOUT_MS))?;
+    port.set_baud_rate(TIMING_SAMPLING_BAUDRATE)?;
+
+    let samples = listen(port, window).await?;
+
+    Ok(estimate_baudrate(&samples, TIMING_SAMPLING_BAUDRATE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Emulates a UART sampling at `ratio` times the baudrate of a device sending `byte`.
+    fn sample_slow_byte(byte: u8, ratio: usize) -> Vec<u8> {
+        // Idle, start bit, data bits LSB first, stop bit, idle.
+        let mut line = vec![true; ratio];
+        line.resize(2 * ratio, false);
+        for i in 0..8 {
+            line.resize(line.len() + ratio, byte & (1 << i) != 0);
+        }
+        line.resize(line.len() + 2 * ratio, true);
+
+        let mut samples = vec![];
+        let mut i = 1;
+        while i + 10 <= line.len() {
+            // A sampled frame starts on a falling edge.
+            if line[i - 1] && !line[i] {
+                let data = (0..8).fold(0, |data, bit| data | ((line[i + 1 + bit] as u8) << bit));
+                samples.push(data);
+                i += 10;
+            } else {
+                i += 1;
+            }
+        }
+
+        samples
+    }
+
+    #[test]
+    fn printable_score_of_text_and_noise() {
+        assert_eq!(1.0, printable_score(b"Hello\r\n"));
+        assert_eq!(0.5, printable_score(&[b'a', 0x80]));
+        assert_eq!(0.0, printable_score(&[]));
+    }
+
+    #[test]
+    fn estimate_baudrate_of_sampled_u() {
+        let samples = sample_slow_byte(b'U', 4);
+        assert_eq!(Some(57600), estimate_baudrate(&samples, 230400));
+
+        let samples = sample_slow_byte(b'U', 2);
+        assert_eq!(Some(115200), estimate_baudrate(&samples, 230400));
+    }
+
+    #[test]
+    fn estimate_baudrate_without_edges() {
+        assert_eq!(None, estimate_baudrate(&[0xFF, 0x00], 230400));
+    }
+
+    #[tokio::test]
+    async fn auto_baud_without_port() {
+        let mut port = SerialPort::new();
+        let resp = port
+            .auto_baud(AutoBaudMethod::Heuristic, time::Duration::from_millis(0))
+            .await;
+        assert!(!resp.success);
+    }
+}
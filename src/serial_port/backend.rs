@@ -0,0 +1,763 @@
+//! Backends driving the ports, for new kinds of ports to be added without branching
+//! through the methods of `SerialPort`.
+//!
+//! A `SerialPort` holds one backend, through which it opens and closes its ports,
+//! reads and writes them (`AsyncRead` and `AsyncWrite`, the flush included), drives
+//! their control lines and their line settings. `LocalSerialBackend`, the default,
+//! opens the local serial ports through `tokio-serial`, and the `tcp://` and
+//! `rfc2217://` ones through `network`. Another backend is given with
+//! `SerialPort::with_backend()`.
+//!
+//! The reads and writes of a backend wait for as long as needed: `timeout()` is
+//! only stored, `SerialPort` bounding each read and write with it.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::{io, time};
+
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, RawHandle};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::{network, SerialPort};
+
+/// How a port is opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenOptions {
+    pub baudrate: u32,
+    pub data_bits: serialport::DataBits,
+    pub parity: serialport::Parity,
+    pub stop_bits: serialport::StopBits,
+    pub flow_control: serialport::FlowControl,
+    pub timeout: time::Duration,
+    /// If the port is to be opened in exclusive mode, for the backends supporting it.
+    pub exclusive: bool,
+}
+
+/// A port, open or not. The methods other than `open()` fail with a `NoDevice`
+/// error (`NotConnected` for the reads and writes) while it is closed.
+#[async_trait::async_trait]
+pub trait SerialBackend: AsyncRead + AsyncWrite + Unpin + Send {
+    /// Opens `port_path`, the port being closed.
+    async fn open(&mut self, port_path: &str, options: &OpenOptions) -> serialport::Result<()>;
+
+    /// Closes the port, if open.
+    fn close(&mut self);
+
+    fn is_open(&self) -> bool;
+
+    /// Returns a closed backend of the same kind, to open another port with while
+    /// this one is in use.
+    fn unopened(&self) -> Box<dyn SerialBackend>;
+
+    /// Returns another handle on the open port, see `split`.
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialBackend>>;
+
+    fn name(&self) -> Option<String>;
+
+    fn baud_rate(&self) -> serialport::Result<u32>;
+    fn data_bits(&self) -> serialport::Result<serialport::DataBits>;
+    fn parity(&self) -> serialport::Result<serialport::Parity>;
+    fn stop_bits(&self) -> serialport::Result<serialport::StopBits>;
+    fn flow_control(&self) -> serialport::Result<serialport::FlowControl>;
+    /// Timeout of the reads and the writes.
+    fn timeout(&self) -> time::Duration;
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()>;
+    fn set_data_bits(&mut self, data_bits: serialport::DataBits) -> serialport::Result<()>;
+    fn set_parity(&mut self, parity: serialport::Parity) -> serialport::Result<()>;
+    fn set_stop_bits(&mut self, stop_bits: serialport::StopBits) -> serialport::Result<()>;
+    fn set_flow_control(&mut self, flow_control: serialport::FlowControl)
+        -> serialport::Result<()>;
+    fn set_timeout(&mut self, timeout: time::Duration) -> serialport::Result<()>;
+
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()>;
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()>;
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool>;
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool>;
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool>;
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool>;
+
+    fn bytes_to_read(&self) -> serialport::Result<u32>;
+    fn clear(&self, buffer_to_clear: serialport::ClearBuffer) -> serialport::Result<()>;
+
+    /// Sets the exclusive mode of the open port, see `exclusive`.
+    fn set_exclusive(&mut self, _exclusive: bool) -> serialport::Result<()> {
+        Err(serialport::Error::new(
+            serialport::ErrorKind::Unknown,
+            "The exclusive mode is only supported by local ports",
+        ))
+    }
+
+    /// OS-level file descriptor of the open port, see `SerialPort::raw_fd()`.
+    #[cfg(unix)]
+    fn raw_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    /// OS-level handle of the open port, see `SerialPort::raw_handle()`.
+    #[cfg(windows)]
+    fn raw_handle(&self) -> Option<RawHandle> {
+        None
+    }
+}
+
+impl dyn SerialBackend {
+    /// Returns the backend if its port is open.
+    pub(crate) fn if_open(&self) -> Option<&dyn SerialBackend> {
+        if self.is_open() {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn if_open_mut(&mut self) -> Option<&mut dyn SerialBackend> {
+        if self.is_open() {
+            Some(self)
+        } else {
+            None
+        }
+    }
+}
+
+fn not_open() -> serialport::Error {
+    serialport::Error::new(serialport::ErrorKind::NoDevice, "No port is currently open")
+}
+
+fn not_connected() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "No port is currently open")
+}
+
+/// A port of `LocalSerialBackend`.
+enum LocalPort {
+    Serial(tokio_serial::SerialStream),
+    Network(network::NetworkPort),
+}
+
+impl LocalPort {
+    fn settings(&self) -> &dyn serialport::SerialPort {
+        match self {
+            LocalPort::Serial(port) => port,
+            LocalPort::Network(port) => port,
+        }
+    }
+
+    fn settings_mut(&mut self) -> &mut dyn serialport::SerialPort {
+        match self {
+            LocalPort::Serial(port) => port,
+            LocalPort::Network(port) => port,
+        }
+    }
+}
+
+/// The local serial ports through `tokio-serial`, and the network ones through
+/// `network`.
+#[derive(Default)]
+pub struct LocalSerialBackend {
+    port: Option<LocalPort>,
+    /// If the open port is in exclusive mode, for its clones to keep it so.
+    #[cfg(unix)]
+    exclusive: bool,
+    timeout: time::Duration,
+}
+
+impl LocalSerialBackend {
+    pub fn new() -> LocalSerialBackend {
+        LocalSerialBackend::default()
+    }
+
+    fn port(&self) -> serialport::Result<&dyn serialport::SerialPort> {
+        match self.port.as_ref() {
+            Some(port) => Ok(port.settings()),
+            None => Err(not_open()),
+        }
+    }
+
+    fn port_mut(&mut self) -> serialport::Result<&mut dyn serialport::SerialPort> {
+        match self.port.as_mut() {
+            Some(port) => Ok(port.settings_mut()),
+            None => Err(not_open()),
+        }
+    }
+
+    async fn open_network(
+        port_path: &str,
+        options: &OpenOptions,
+    ) -> serialport::Result<network::NetworkPort> {
+        use serialport::SerialPort as _;
+
+        let mut port =
+            network::NetworkPort::open(port_path, options.baudrate, options.timeout).await?;
+
+        // Only the settings differing from the 8N1 the port starts with are sent.
+        if options.data_bits != port.data_bits()? {
+            port.set_data_bits(options.data_bits)?;
+        }
+        if options.parity != port.parity()? {
+            port.set_parity(options.parity)?;
+        }
+        if options.stop_bits != port.stop_bits()? {
+            port.set_stop_bits(options.stop_bits)?;
+        }
+        if options.flow_control != port.flow_control()? {
+            port.set_flow_control(options.flow_control)?;
+        }
+
+        Ok(port)
+    }
+
+    fn open_native(
+        port_path: &str,
+        options: &OpenOptions,
+    ) -> serialport::Result<tokio_serial::SerialStream> {
+        let builder = serialport::new(port_path, options.baudrate)
+            .data_bits(options.data_bits)
+            .parity(options.parity)
+            .stop_bits(options.stop_bits)
+            .flow_control(options.flow_control)
+            .timeout(options.timeout);
+        #[allow(unused_mut)]
+        let mut port = tokio_serial::SerialStream::open(&builder)?;
+
+        #[cfg(unix)]
+        {
+            if options.exclusive {
+                port.set_exclusive(true)?;
+            }
+        }
+
+        Ok(port)
+    }
+
+    /// Returns a port on a duplicate of the descriptor of the serial port.
+    #[cfg(unix)]
+    fn clone_serial(
+        port: &tokio_serial::SerialStream,
+        exclusive: bool,
+    ) -> serialport::Result<tokio_serial::SerialStream> {
+        use std::os::unix::io::{BorrowedFd, FromRawFd, IntoRawFd};
+
+        let fd = unsafe { BorrowedFd::borrow_raw(port.as_raw_fd()) }.try_clone_to_owned()?;
+        let mut tty = unsafe { serialport::TTYPort::from_raw_fd(fd.into_raw_fd()) };
+        // `from_raw_fd()` makes the tty exclusive.
+        tty.set_exclusive(exclusive)?;
+
+        tokio_serial::SerialStream::try_from(tty)
+    }
+}
+
+impl AsyncRead for LocalSerialBackend {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.port.as_mut() {
+            Some(LocalPort::Serial(port)) => {
+                let filled = buf.filled().len();
+                match Pin::new(port).poll_read(cx, buf) {
+                    // A tty readable without any byte has been hung up, its device removed.
+                    Poll::Ready(Ok(())) if buf.filled().len() == filled && buf.remaining() > 0 => {
+                        Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "The device has been disconnected",
+                        )))
+                    }
+                    polled => polled,
+                }
+            }
+            Some(LocalPort::Network(port)) => Pin::new(port).poll_read(cx, buf),
+            None => Poll::Ready(Err(not_connected())),
+        }
+    }
+}
+
+impl AsyncWrite for LocalSerialBackend {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.port.as_mut() {
+            Some(LocalPort::Serial(port)) => Pin::new(port).poll_write(cx, buf),
+            Some(LocalPort::Network(port)) => Pin::new(port).poll_write(cx, buf),
+            None => Poll::Ready(Err(not_connected())),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.port.as_mut() {
+            Some(LocalPort::Serial(port)) => Pin::new(port).poll_flush(cx),
+            Some(LocalPort::Network(port)) => Pin::new(port).poll_flush(cx),
+            None => Poll::Ready(Err(not_connected())),
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.port.as_mut() {
+            Some(LocalPort::Serial(port)) => Pin::new(port).poll_shutdown(cx),
+            Some(LocalPort::Network(port)) => Pin::new(port).poll_shutdown(cx),
+            None => Poll::Ready(Err(not_connected())),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SerialBackend for LocalSerialBackend {
+    async fn open(&mut self, port_path: &str, options: &OpenOptions) -> serialport::Result<()> {
+        self.close();
+
+        let port = if network::is_network_path(port_path) {
+            LocalPort::Network(LocalSerialBackend::open_network(port_path, options).await?)
+        } else {
+            LocalPort::Serial(LocalSerialBackend::open_native(port_path, options)?)
+        };
+        self.port = Some(port);
+        #[cfg(unix)]
+        {
+            self.exclusive = options.exclusive;
+        }
+        self.timeout = options.timeout;
+
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.port = None;
+    }
+
+    fn is_open(&self) -> bool {
+        self.port.is_some()
+    }
+
+    fn unopened(&self) -> Box<dyn SerialBackend> {
+        Box::new(LocalSerialBackend::new())
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialBackend>> {
+        let port = match self.port.as_ref() {
+            #[cfg(unix)]
+            Some(LocalPort::Serial(port)) => {
+                LocalPort::Serial(LocalSerialBackend::clone_serial(port, self.exclusive)?)
+            }
+            #[cfg(not(unix))]
+            Some(LocalPort::Serial(_)) => {
+                return Err(serialport::Error::new(
+                    serialport::ErrorKind::Unknown,
+                    "The local ports can only be cloned on Unix",
+                ))
+            }
+            Some(LocalPort::Network(port)) => LocalPort::Network(port.try_clone_stream()?),
+            None => return Err(not_open()),
+        };
+
+        Ok(Box::new(LocalSerialBackend {
+            port: Some(port),
+            #[cfg(unix)]
+            exclusive: self.exclusive,
+            timeout: self.timeout,
+        }))
+    }
+
+    fn name(&self) -> Option<String> {
+        self.port.as_ref().and_then(|port| port.settings().name())
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        self.port()?.baud_rate()
+    }
+
+    fn data_bits(&self) -> serialport::Result<serialport::DataBits> {
+        self.port()?.data_bits()
+    }
+
+    fn parity(&self) -> serialport::Result<serialport::Parity> {
+        self.port()?.parity()
+    }
+
+    fn stop_bits(&self) -> serialport::Result<serialport::StopBits> {
+        self.port()?.stop_bits()
+    }
+
+    fn flow_control(&self) -> serialport::Result<serialport::FlowControl> {
+        self.port()?.flow_control()
+    }
+
+    fn timeout(&self) -> time::Duration {
+        self.timeout
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.port_mut()?.set_baud_rate(baud_rate)
+    }
+
+    fn set_data_bits(&mut self, data_bits: serialport::DataBits) -> serialport::Result<()> {
+        self.port_mut()?.set_data_bits(data_bits)
+    }
+
+    fn set_parity(&mut self, parity: serialport::Parity) -> serialport::Result<()> {
+        self.port_mut()?.set_parity(parity)
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: serialport::StopBits) -> serialport::Result<()> {
+        self.port_mut()?.set_stop_bits(stop_bits)
+    }
+
+    fn set_flow_control(
+        &mut self,
+        flow_control: serialport::FlowControl,
+    ) -> serialport::Result<()> {
+        self.port_mut()?.set_flow_control(flow_control)
+    }
+
+    fn set_timeout(&mut self, timeout: time::Duration) -> serialport::Result<()> {
+        self.port()?;
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.port_mut()?.write_request_to_send(level)
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.port_mut()?.write_data_terminal_ready(level)
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        self.port_mut()?.read_clear_to_send()
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        self.port_mut()?.read_data_set_ready()
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        self.port_mut()?.read_ring_indicator()
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        self.port_mut()?.read_carrier_detect()
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        self.port()?.bytes_to_read()
+    }
+
+    fn clear(&self, buffer_to_clear: serialport::ClearBuffer) -> serialport::Result<()> {
+        self.port()?.clear(buffer_to_clear)
+    }
+
+    #[cfg(unix)]
+    fn set_exclusive(&mut self, exclusive: bool) -> serialport::Result<()> {
+        match self.port.as_mut() {
+            Some(LocalPort::Serial(port)) => port.set_exclusive(exclusive)?,
+            Some(LocalPort::Network(_)) => {
+                return Err(serialport::Error::new(
+                    serialport::ErrorKind::Unknown,
+                    "The exclusive mode is only supported by local ports",
+                ))
+            }
+            None => return Err(not_open()),
+        }
+        self.exclusive = exclusive;
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn raw_fd(&self) -> Option<RawFd> {
+        match self.port.as_ref() {
+            Some(LocalPort::Serial(port)) => Some(port.as_raw_fd()),
+            _ => None,
+        }
+    }
+
+    #[cfg(windows)]
+    fn raw_handle(&self) -> Option<RawHandle> {
+        match self.port.as_ref() {
+            Some(LocalPort::Serial(port)) => Some(port.as_raw_handle()),
+            _ => None,
+        }
+    }
+}
+
+impl SerialPort {
+    /// Creates a port driven by `backend`, instead of a `LocalSerialBackend`.
+    pub fn with_backend(backend: Box<dyn SerialBackend>) -> SerialPort {
+        SerialPort {
+            backend,
+            ..SerialPort::default()
+        }
+    }
+}
+
+/// A backend opening every path as a new pty pair, keeping the options and the
+/// master side of the ptys, the device end, for the tests.
+#[cfg(all(test, unix))]
+pub(crate) mod pty {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// The master sides of the ptys opened, the last one last.
+    pub(crate) type Masters = Arc<Mutex<Vec<serialport::TTYPort>>>;
+
+    #[derive(Default)]
+    pub(crate) struct PtyBackend {
+        pub(crate) masters: Masters,
+        pub(crate) options: Arc<Mutex<Option<OpenOptions>>>,
+        /// Number of the next opens to fail, as a device not plugged back yet.
+        pub(crate) failures: Arc<Mutex<u32>>,
+        slave: LocalSerialBackend,
+    }
+
+    impl AsyncRead for PtyBackend {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.slave).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for PtyBackend {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.slave).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.slave).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.slave).poll_shutdown(cx)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SerialBackend for PtyBackend {
+        async fn open(
+            &mut self,
+            _port_path: &str,
+            options: &OpenOptions,
+        ) -> serialport::Result<()> {
+            {
+                let mut failures = self.failures.lock().unwrap();
+                if *failures > 0 {
+                    *failures -= 1;
+                    return Err(serialport::Error::new(
+                        serialport::ErrorKind::NoDevice,
+                        "Device not back yet",
+                    ));
+                }
+            }
+
+            let (master, slave) = serialport::TTYPort::pair()?;
+            let slave_path = serialport::SerialPort::name(&slave).unwrap();
+            self.slave.open(&slave_path, options).await?;
+            self.masters.lock().unwrap().push(master);
+            *self.options.lock().unwrap() = Some(*options);
+
+            Ok(())
+        }
+
+        fn close(&mut self) {
+            self.slave.close()
+        }
+
+        fn is_open(&self) -> bool {
+            self.slave.is_open()
+        }
+
+        fn unopened(&self) -> Box<dyn SerialBackend> {
+            Box::new(PtyBackend {
+                masters: Arc::clone(&self.masters),
+                options: Arc::clone(&self.options),
+                failures: Arc::clone(&self.failures),
+                slave: LocalSerialBackend::new(),
+            })
+        }
+
+        fn try_clone(&self) -> serialport::Result<Box<dyn SerialBackend>> {
+            self.slave.try_clone()
+        }
+
+        fn name(&self) -> Option<String> {
+            self.slave.name()
+        }
+
+        fn baud_rate(&self) -> serialport::Result<u32> {
+            self.slave.baud_rate()
+        }
+
+        fn data_bits(&self) -> serialport::Result<serialport::DataBits> {
+            self.slave.data_bits()
+        }
+
+        fn parity(&self) -> serialport::Result<serialport::Parity> {
+            self.slave.parity()
+        }
+
+        fn stop_bits(&self) -> serialport::Result<serialport::StopBits> {
+            self.slave.stop_bits()
+        }
+
+        fn flow_control(&self) -> serialport::Result<serialport::FlowControl> {
+            self.slave.flow_control()
+        }
+
+        fn timeout(&self) -> time::Duration {
+            self.slave.timeout()
+        }
+
+        fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+            self.slave.set_baud_rate(baud_rate)
+        }
+
+        fn set_data_bits(&mut self, data_bits: serialport::DataBits) -> serialport::Result<()> {
+            self.slave.set_data_bits(data_bits)
+        }
+
+        fn set_parity(&mut self, parity: serialport::Parity) -> serialport::Result<()> {
+            self.slave.set_parity(parity)
+        }
+
+        fn set_stop_bits(&mut self, stop_bits: serialport::StopBits) -> serialport::Result<()> {
+            self.slave.set_stop_bits(stop_bits)
+        }
+
+        fn set_flow_control(
+            &mut self,
+            flow_control: serialport::FlowControl,
+        ) -> serialport::Result<()> {
+            self.slave.set_flow_control(flow_control)
+        }
+
+        fn set_timeout(&mut self, timeout: time::Duration) -> serialport::Result<()> {
+            self.slave.set_timeout(timeout)
+        }
+
+        fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+            self.slave.write_request_to_send(level)
+        }
+
+        fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+            self.slave.write_data_terminal_ready(level)
+        }
+
+        fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+            self.slave.read_clear_to_send()
+        }
+
+        fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+            self.slave.read_data_set_ready()
+        }
+
+        fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+            self.slave.read_ring_indicator()
+        }
+
+        fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+            self.slave.read_carrier_detect()
+        }
+
+        fn bytes_to_read(&self) -> serialport::Result<u32> {
+            self.slave.bytes_to_read()
+        }
+
+        fn clear(&self, buffer_to_clear: serialport::ClearBuffer) -> serialport::Result<()> {
+            self.slave.clear(buffer_to_clear)
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::pty::PtyBackend;
+    use super::*;
+    use std::io::Read;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn given_backend() {
+        let backend = PtyBackend::default();
+        let masters = std::sync::Arc::clone(&backend.masters);
+        let mut port = SerialPort::with_backend(Box::new(backend));
+
+        assert!(port.open_port("pty://test", 9600).await.success);
+        assert!(port.send_once("ok").await.success);
+
+        let mut sent = [0; 2];
+        masters.lock().unwrap()[0].read_exact(&mut sent).unwrap();
+        assert_eq!(b"ok", &sent);
+    }
+
+    #[tokio::test]
+    async fn open_with_software_flow_control() {
+        let backend = PtyBackend::default();
+        let options = std::sync::Arc::clone(&backend.options);
+        let mut port = SerialPort::with_backend(Box::new(backend));
+
+        // 7E1 with XON/XOFF.
+        let config = crate::serial_port::config::SerialConfig {
+            data_bits: serialport::DataBits::Seven,
+            parity: serialport::Parity::Even,
+            flow_control: serialport::FlowControl::Software,
+            ..crate::serial_port::config::SerialConfig::new(4800)
+        };
+        assert!(
+            port.open_port_with_config("pty://test", config)
+                .await
+                .success
+        );
+
+        let options = options.lock().unwrap().unwrap();
+        assert_eq!(4800, options.baudrate);
+        assert_eq!(serialport::DataBits::Seven, options.data_bits);
+        assert_eq!(serialport::Parity::Even, options.parity);
+        assert_eq!(serialport::StopBits::One, options.stop_bits);
+        assert_eq!(serialport::FlowControl::Software, options.flow_control);
+    }
+
+    #[tokio::test]
+    async fn closed_backend() {
+        let mut backend = LocalSerialBackend::new();
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let options = OpenOptions {
+            baudrate: 9600,
+            data_bits: serialport::DataBits::Eight,
+            parity: serialport::Parity::None,
+            stop_bits: serialport::StopBits::One,
+            flow_control: serialport::FlowControl::None,
+            timeout: time::Duration::from_millis(50),
+            exclusive: false,
+        };
+
+        assert!(backend.open(&slave_path, &options).await.is_ok());
+        assert!(backend.raw_fd().is_some());
+        assert_eq!(Ok(9600), backend.baud_rate().map_err(|e| e.kind));
+
+        backend.close();
+        assert!(!backend.is_open());
+        assert_eq!(None, backend.raw_fd());
+        assert_eq!(
+            Err(serialport::ErrorKind::NoDevice),
+            backend.baud_rate().map_err(|e| e.kind)
+        );
+        let mut buf = [0; 1];
+        assert_eq!(
+            io::ErrorKind::NotConnected,
+            backend.read(&mut buf).await.unwrap_err().kind()
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! What the platform, and the open port if any, are able to do.
+
+use super::{network, SerialPort};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Baudrates outside of the standard ones, 250000 for ex.
+    CustomBaudrate,
+    /// RTS/CTS flow control.
+    HardwareFlowControl,
+    /// Opening the port prevents other processes from opening it.
+    ExclusiveLock,
+    /// Sending a break condition.
+    Break,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [
+        Capability::CustomBaudrate,
+        Capability::HardwareFlowControl,
+        Capability::ExclusiveLock,
+        Capability::Break,
+    ];
+}
+
+/// Capabilities of the local serial ports of the platform.
+fn platform_supports(capability: Capability) -> bool {
+    match capability {
+        // Through termios2 on Linux, IOSSIOSPEED on macOS and the DCB on Windows.
+        Capability::CustomBaudrate => {
+            cfg!(any(target_os = "linux", target_os = "macos", windows))
+        }
+        Capability::HardwareFlowControl | Capability::Break => cfg!(any(unix, windows)),
+        // TIOCEXCL on Unix, Windows always opens ports exclusively.
+        Capability::ExclusiveLock => cfg!(any(unix, windows)),
+    }
+}
+
+/// Capabilities of a network port, see `network`.
+fn network_supports(port_path: &str, capability: Capability) -> bool {
+    let rfc2217 = port_path.starts_with(network::RFC2217_SCHEME);
+
+    match capability {
+        Capability::CustomBaudrate | Capability::HardwareFlowControl | Capability::Break => rfc2217,
+        // The adapter decides whether other clients can connect.
+        Capability::ExclusiveLock => false,
+    }
+}
+
+impl SerialPort {
+    /// Returns if `capability` is available.
+    ///
+    /// With an open port, the answer is given for this port (a network port does
+    /// not support the same things as a local one), otherwise for the local ports
+    /// of the platform. The driver of a given device may still refuse a setting.
+    pub fn supports(&self, capability: Capability) -> bool {
+        match self.backend.if_open().and_then(|port| port.name()) {
+            Some(port_path) if network::is_network_path(&port_path) => {
+                network_supports(&port_path, capability)
+            }
+            _ => platform_supports(capability),
+        }
+    }
+
+    /// Returns every supported capability, see `supports()`.
+    pub fn capabilities(&self) -> Vec<Capability> {
+        Capability::ALL
+            .iter()
+            .copied()
+            .filter(|capability| self.supports(*capability))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_without_port() {
+        let port = SerialPort::new();
+        assert_eq!(Capability::ALL.to_vec(), port.capabilities());
+    }
+
+    #[tokio::test]
+    async fn raw_tcp_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut port = SerialPort::new();
+        let path = format!("tcp://{}", listener.local_addr().unwrap());
+        assert!(port.open_port(&path, 9600).await.success);
+
+        assert!(port.capabilities().is_empty());
+    }
+}
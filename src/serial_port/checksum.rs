@@ -0,0 +1,147 @@
+//! Trailing checksums, for the device protocols ending each frame with one.
+
+use super::error::SerialError;
+use super::{SerialPort, SerialPortResponse};
+
+/// Checksum appended by `SerialPort::send_with_checksum()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgo {
+    /// XOR of the bytes, see `xor8()`.
+    Xor8,
+    /// Sum of the bytes modulo 256, see `sum8()`.
+    Sum8,
+    /// CRC16-CCITT, see `crc16_ccitt()`. Sent high byte first.
+    Crc16Ccitt,
+}
+
+impl ChecksumAlgo {
+    /// Returns the checksum of `bytes`, as the bytes to append to them.
+    pub fn checksum(self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            ChecksumAlgo::Xor8 => vec![xor8(bytes)],
+            ChecksumAlgo::Sum8 => vec![sum8(bytes)],
+            ChecksumAlgo::Crc16Ccitt => crc16_ccitt(bytes).to_be_bytes().to_vec(),
+        }
+    }
+}
+
+/// Computes the XOR of `bytes`.
+pub fn xor8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |checksum, byte| checksum ^ byte)
+}
+
+/// Computes the sum of `bytes`, modulo 256.
+pub fn sum8(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0, |checksum: u8, byte| checksum.wrapping_add(*byte))
+}
+
+/// Computes the CRC16-CCITT (poly 0x1021, init 0xFFFF, not reflected) of `bytes`,
+/// the "CCITT-FALSE" variant.
+pub fn crc16_ccitt(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+
+    crc
+}
+
+impl SerialPort {
+    /// Sends `message` followed by its checksum.
+    ///
+    /// The checksum is computed over the bytes of the message once its escapes
+    /// parsed (see `set_byte_escapes()`), the codec pipeline, if any, being applied
+    /// to the whole frame afterwards.
+    ///
+    /// # Paramters
+    ///
+    /// - `message`: The string slice to send.
+    /// - `algo`: The checksum to append.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message, see `send_once()`.
+    /// - `success`: if the frame has been sent correctly.
+    pub async fn send_with_checksum(
+        &mut self,
+        message: &str,
+        algo: ChecksumAlgo,
+    ) -> SerialPortResponse {
+        self.try_send_with_checksum(message, algo).await.into()
+    }
+
+    /// Sends a message as `send_with_checksum()` does.
+    ///
+    /// # Returns
+    ///
+    /// As `try_send_once()`.
+    pub async fn try_send_with_checksum(
+        &mut self,
+        message: &str,
+        algo: ChecksumAlgo,
+    ) -> Result<String, SerialError> {
+        let mut frame = self.message_bytes(message);
+        let checksum = algo.checksum(&frame);
+        frame.extend(checksum);
+
+        self.try_send_bytes(&frame).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_vectors() {
+        assert_eq!(0x31, xor8(b"123456789"));
+        assert_eq!(0xDD, sum8(b"123456789"));
+        assert_eq!(0x29B1, crc16_ccitt(b"123456789"));
+
+        assert_eq!(0, xor8(b""));
+        assert_eq!(0, sum8(b""));
+        assert_eq!(0xFFFF, crc16_ccitt(b""));
+        assert_eq!(0x00, sum8(&[0x80, 0x80]));
+        assert_eq!(0xB915, crc16_ccitt(b"A"));
+
+        assert_eq!(
+            vec![0x29, 0xB1],
+            ChecksumAlgo::Crc16Ccitt.checksum(b"123456789")
+        );
+        assert_eq!(vec![0xDD], ChecksumAlgo::Sum8.checksum(b"123456789"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn send_checksummed_frames() {
+        use std::io::Read;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        let resp = port.send_with_checksum("0x01ok", ChecksumAlgo::Xor8).await;
+        assert!(resp.success, "{}", resp.content);
+        let resp = port
+            .send_with_checksum("123456789", ChecksumAlgo::Crc16Ccitt)
+            .await;
+        assert!(resp.success, "{}", resp.content);
+
+        let mut sent = [0; 15];
+        master.read_exact(&mut sent).unwrap();
+        assert_eq!(b"\x01ok\x05123456789\x29\xB1", &sent);
+    }
+}
@@ -0,0 +1,263 @@
+//! Coalescing of the writes, for high-frequency senders of small messages.
+//!
+//! Instead of being written right away, the messages of `send_once()` are queued,
+//! and written at once, then flushed, after `max_writes` messages or `max_delay`
+//! since the first one queued, whichever comes first. The delay is only checked
+//! by the next `send_once()` and by `flush_if_due()`, to be called periodically
+//! (the server does it every `COALESCING_CHECK_INTERVAL_MS`).
+//!
+//! The other writes (Modbus requests, files, ...) are not queued: the pending
+//! writes go out before them, for the bytes to keep their order on the wire.
+
+use std::time;
+
+use tokio::io::AsyncWriteExt;
+
+use super::{error::SerialError, within, SerialPort, SerialPortResponse};
+
+/// How often `flush_if_due()` should be called to honor `max_delay`.
+pub const COALESCING_CHECK_INTERVAL_MS: u64 = 5;
+
+/// Queued writes, see `SerialPort::set_write_coalescing()`.
+#[derive(Debug)]
+pub(crate) struct Coalescing {
+    max_writes: usize,
+    max_delay: time::Duration,
+    pending: Vec<u8>,
+    pending_writes: usize,
+    /// When the oldest pending write has been queued.
+    since: Option<time::Instant>,
+}
+
+impl Coalescing {
+    fn is_due(&self) -> bool {
+        self.pending_writes >= self.max_writes
+            || self
+                .since
+                .is_some_and(|since| since.elapsed() >= self.max_delay)
+    }
+}
+
+impl SerialPort {
+    /// Sets the write coalescing policy of the port, kept across port changes.
+    ///
+    /// Pending writes of the previous policy are flushed first.
+    ///
+    /// # Paramters
+    ///
+    /// - `max_writes`: The number of writes queued before they are flushed. 0 or 1
+    ///   disables the coalescing, every write going out right away.
+    /// - `max_delay`: The maximum time a write stays queued.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the pending writes, if any, have been flushed.
+    pub async fn set_write_coalescing(
+        &mut self,
+        max_writes: usize,
+        max_delay: time::Duration,
+    ) -> SerialPortResponse {
+        let flushed = self.flush_pending().await;
+
+        self.coalescing = if max_writes <= 1 {
+            None
+        } else {
+            Some(Coalescing {
+                max_writes,
+                max_delay,
+                pending: vec![],
+                pending_writes: 0,
+                since: None,
+            })
+        };
+
+        match flushed {
+            Some(Err(e)) => e.into(),
+            _ => SerialPortResponse {
+                success: true,
+                content: match self.coalescing {
+                    Some(_) => format!(
+                        "Writes coalesced by {}, for up to {}ms",
+                        max_writes,
+                        max_delay.as_millis()
+                    ),
+                    None => "Write coalescing disabled".to_string(),
+                },
+            },
+        }
+    }
+
+    /// Writes the pending writes, if any, and waits for the output to be transmitted.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the output has been flushed.
+    pub async fn flush(&mut self) -> SerialPortResponse {
+        self.try_flush().await.into()
+    }
+
+    async fn try_flush(&mut self) -> Result<String, SerialError> {
+        if let Some(Err(e)) = self.flush_pending().await {
+            return Err(e);
+        }
+
+        let write_timeout = self.write_timeout;
+        match self.backend.if_open_mut() {
+            Some(port) => match within(write_timeout, port.flush()).await {
+                Ok(_) => Ok("Output flushed".to_string()),
+                Err(e) => Err(SerialError::io("Serial flush error", e)),
+            },
+            None => Err(SerialError::NoPortOpen),
+        }
+    }
+
+    /// Flushes the pending writes if `max_delay` has elapsed.
+    ///
+    /// # Returns
+    ///
+    /// The response of the flush, if one was due.
+    pub async fn flush_if_due(&mut self) -> Option<SerialPortResponse> {
+        match &self.coalescing {
+            Some(coalescing) if coalescing.since.is_some() && coalescing.is_due() => {
+                Some(self.flush().await)
+            }
+            _ => None,
+        }
+    }
+
+    /// Queues `output` if the writes are coalesced.
+    ///
+    /// # Returns
+    ///
+    /// The response to give to the write, `None` if it has to be written right away.
+    pub(crate) async fn queue_write(
+        &mut self,
+        output: &[u8],
+    ) -> Option<Result<String, SerialError>> {
+        let coalescing = self.coalescing.as_mut()?;
+
+        coalescing.pending.extend_from_slice(output);
+        coalescing.pending_writes += 1;
+        coalescing.since.get_or_insert_with(time::Instant::now);
+
+        if coalescing.is_due() {
+            return Some(self.try_flush().await);
+        }
+
+        Some(Ok(format!(
+            "Request queued, {} writes pending",
+            coalescing.pending_writes
+        )))
+    }
+
+    /// Writes the pending writes at once, without flushing.
+    ///
+    /// # Returns
+    ///
+    /// The response of the write, if there was anything pending.
+    pub(crate) async fn flush_pending(&mut self) -> Option<Result<String, SerialError>> {
+        let coalescing = self.coalescing.as_mut()?;
+        if coalescing.pending_writes == 0 {
+            return None;
+        }
+
+        let pending = std::mem::take(&mut coalescing.pending);
+        coalescing.pending_writes = 0;
+        coalescing.since = None;
+
+        Some(self.try_write_now(&pending).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn coalesced_writes() {
+        use std::io::Read;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(
+            port.set_write_coalescing(3, time::Duration::from_secs(60))
+                .await
+                .success
+        );
+
+        assert!(port.send_once("a").await.success);
+        assert!(port.send_once("b").await.success);
+        assert_eq!(0, port.stats().bytes_sent);
+
+        // The third write flushes the three.
+        assert!(port.send_once("c").await.success);
+        let mut sent = [0; 3];
+        master.read_exact(&mut sent).unwrap();
+        assert_eq!(b"abc", &sent);
+
+        assert!(port.send_once("d").await.success);
+        assert!(port.close_port().await.success);
+        master.read_exact(&mut sent[..1]).unwrap();
+        assert_eq!(b"d", &sent[..1]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn queued_writes_go_out_first() {
+        use std::io::Read;
+
+        let path = std::env::temp_dir().join(format!(
+            "serial-term-rpc-coalesce-{}.bin",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"file").unwrap();
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(
+            port.set_write_coalescing(3, time::Duration::from_secs(60))
+                .await
+                .success
+        );
+
+        assert!(port.send_once("queued").await.success);
+        let resp = port.send_file(&path, 2).await;
+        std::fs::remove_file(&path).unwrap();
+        assert!(resp.success, "{}", resp.content);
+        assert!(port.send_slice(b"!").await.is_ok());
+
+        let mut sent = [0; 11];
+        master.read_exact(&mut sent).unwrap();
+        assert_eq!(b"queuedfile!", &sent);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn delay_bounds_latency() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(
+            port.set_write_coalescing(100, time::Duration::from_millis(10))
+                .await
+                .success
+        );
+
+        assert!(port.send_once("a").await.success);
+        assert!(port.flush_if_due().await.is_none());
+
+        std::thread::sleep(time::Duration::from_millis(10));
+        assert!(port.flush_if_due().await.unwrap().success);
+        assert_eq!(1, port.stats().bytes_sent);
+    }
+}
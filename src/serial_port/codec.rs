@@ -0,0 +1,310 @@
+//! Composable encodings applied to the bytes sent and read by `SerialPort`.
+//!
+//! A pipeline is an ordered list of codecs: on the send path each codec encodes the
+//! output of the previous one, for ex. `["crc16", "cobs"]` appends a CRC then frames
+//! the result with COBS. Reads go through the same codecs, decoding in reverse order.
+
+use super::modbus::crc16_modbus;
+use super::{SerialPort, SerialPortResponse};
+
+/// A reversible transformation of the bytes going through a port.
+///
+/// Codecs are kept by the port while it is shared between threads, hence `Send`.
+pub trait SendCodec: Send {
+    /// The name the codec is configured with.
+    fn name(&self) -> &str;
+
+    fn encode(&self, data: &[u8]) -> Vec<u8>;
+
+    /// Reverts `encode`, failing if `data` has not been encoded by this codec.
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+/// Escapes the non-printable bytes (and `\`) as `\xNN`.
+pub struct HexEscape;
+
+impl SendCodec for HexEscape {
+    fn name(&self) -> &str {
+        "hex-escape"
+    }
+
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![];
+
+        for byte in data {
+            if (byte.is_ascii_graphic() || *byte == b' ') && *byte != b'\\' {
+                encoded.push(*byte);
+            } else {
+                encoded.extend_from_slice(format!("\\x{:02X}", byte).as_bytes());
+            }
+        }
+
+        encoded
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut decoded = vec![];
+        let mut i = 0;
+
+        while i < data.len() {
+            if data[i] != b'\\' {
+                decoded.push(data[i]);
+                i += 1;
+                continue;
+            }
+
+            let escape = data
+                .get(i..i + 4)
+                .filter(|escape| escape[1] == b'x')
+                .and_then(|escape| std::str::from_utf8(&escape[2..]).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+            match escape {
+                Some(byte) => decoded.push(byte),
+                None => return Err(format!("Invalid escape at offset {}", i)),
+            }
+            i += 4;
+        }
+
+        Ok(decoded)
+    }
+}
+
+/// Appends the CRC16-Modbus of the data, low byte first.
+pub struct Crc16;
+
+impl SendCodec for Crc16 {
+    fn name(&self) -> &str {
+        "crc16"
+    }
+
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let mut encoded = data.to_vec();
+        encoded.extend_from_slice(&crc16_modbus(data).to_le_bytes());
+        encoded
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        if data.len() < 2 {
+            return Err("Too short to hold a CRC16".to_string());
+        }
+
+        let (payload, crc) = data.split_at(data.len() - 2);
+        if crc16_modbus(payload).to_le_bytes() != crc {
+            return Err("CRC16 mismatch".to_string());
+        }
+
+        Ok(payload.to_vec())
+    }
+}
+
+/// Consistent Overhead Byte Stuffing, each frame being terminated by a `0x00`.
+pub struct Cobs;
+
+impl SendCodec for Cobs {
+    fn name(&self) -> &str {
+        "cobs"
+    }
+
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![0];
+        let mut code_index = 0;
+
+        for byte in data {
+            if *byte != 0 {
+                encoded.push(*byte);
+            }
+
+            let code = encoded.len() - code_index;
+            if *byte == 0 || code == 0xFF {
+                encoded[code_index] = code as u8;
+                code_index = encoded.len();
+                encoded.push(0);
+            }
+        }
+
+        encoded[code_index] = (encoded.len() - code_index) as u8;
+        encoded.push(0);
+
+        encoded
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let data = data.strip_suffix(&[0]).unwrap_or(data);
+        let mut decoded = vec![];
+        let mut i = 0;
+
+        while i < data.len() {
+            let code = data[i] as usize;
+            if code == 0 || i + code > data.len() {
+                return Err(format!("Invalid COBS code at offset {}", i));
+            }
+
+            decoded.extend_from_slice(&data[i + 1..i + code]);
+            i += code;
+
+            if code != 0xFF && i < data.len() {
+                decoded.push(0);
+            }
+        }
+
+        Ok(decoded)
+    }
+}
+
+/// Returns the built-in codec called `name`, if any.
+pub fn codec_by_name(name: &str) -> Option<Box<dyn SendCodec>> {
+    match name {
+        "hex-escape" => Some(Box::new(HexEscape)),
+        "crc16" => Some(Box::new(Crc16)),
+        "cobs" => Some(Box::new(Cobs)),
+        _ => None,
+    }
+}
+
+/// Encodes `data` through every codec, in order.
+pub fn encode_with(codecs: &[Box<dyn SendCodec>], data: &[u8]) -> Vec<u8> {
+    codecs
+        .iter()
+        .fold(data.to_vec(), |data, codec| codec.encode(&data))
+}
+
+/// Decodes `data` through every codec, in reverse order.
+pub fn decode_with(codecs: &[Box<dyn SendCodec>], data: &[u8]) -> Result<Vec<u8>, String> {
+    codecs.iter().rev().try_fold(data.to_vec(), |data, codec| {
+        codec
+            .decode(&data)
+            .map_err(|e| format!("{}: {}", codec.name(), e))
+    })
+}
+
+impl SerialPort {
+    /// Sets the codecs applied by `send_once()` and, in reverse, by `read_once()`.
+    ///
+    /// The pipeline is kept when the port is closed and reopened.
+    pub fn set_codec_pipeline(&mut self, codecs: Vec<Box<dyn SendCodec>>) {
+        self.codecs = codecs;
+    }
+
+    /// Sets the pipeline from built-in codec names, see `codec_by_name()`.
+    /// An empty list removes every codec.
+    ///
+    /// # Paramters
+    ///
+    /// - `names`: The codecs, in the order they are applied when sending.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if every codec is known. Otherwise, the pipeline is left untouched.
+    pub fn set_codecs(&mut self, names: &[String]) -> SerialPortResponse {
+        let mut codecs = vec![];
+
+        for name in names {
+            match codec_by_name(name) {
+                Some(codec) => codecs.push(codec),
+                None => {
+                    return SerialPortResponse {
+                        success: false,
+                        content: format!("Unknown codec {}", name),
+                    }
+                }
+            }
+        }
+
+        self.codecs = codecs;
+
+        SerialPortResponse {
+            success: true,
+            content: format!("Codec pipeline set to [{}]", names.join(", ")),
+        }
+    }
+
+    /// Returns the names of the codecs of the pipeline, in sending order.
+    pub fn codec_names(&self) -> Vec<String> {
+        self.codecs
+            .iter()
+            .map(|codec| codec.name().to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(codec: &dyn SendCodec, data: &[u8]) {
+        assert_eq!(Ok(data.to_vec()), codec.decode(&codec.encode(data)));
+    }
+
+    #[test]
+    fn hex_escape() {
+        assert_eq!(b"a\\x00\\x5Cb".to_vec(), HexEscape.encode(b"a\x00\\b"));
+        round_trip(&HexEscape, b"a\x00\\b\xFF");
+        assert!(HexEscape.decode(b"\\x0").is_err());
+    }
+
+    #[test]
+    fn crc16() {
+        assert_eq!(
+            vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A],
+            Crc16.encode(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01])
+        );
+        round_trip(&Crc16, b"payload");
+        assert_eq!(Err("CRC16 mismatch".to_string()), Crc16.decode(b"abcd"));
+    }
+
+    #[test]
+    fn cobs() {
+        assert_eq!(vec![0x01, 0x01, 0x00], Cobs.encode(&[0x00]));
+        assert_eq!(
+            vec![0x03, 0x11, 0x22, 0x02, 0x33, 0x00],
+            Cobs.encode(&[0x11, 0x22, 0x00, 0x33])
+        );
+        round_trip(&Cobs, &[]);
+        round_trip(&Cobs, &[0x00, 0x00, 0x01]);
+        round_trip(&Cobs, &[0x42; 300]);
+    }
+
+    #[test]
+    fn pipeline_order() {
+        let codecs: Vec<Box<dyn SendCodec>> = vec![Box::new(Crc16), Box::new(Cobs)];
+        let encoded = encode_with(&codecs, b"ab");
+
+        assert_eq!(Cobs.encode(&Crc16.encode(b"ab")), encoded);
+        assert_eq!(Ok(b"ab".to_vec()), decode_with(&codecs, &encoded));
+    }
+
+    #[test]
+    fn set_unknown_codec_keeps_pipeline() {
+        let mut port = SerialPort::new();
+        assert!(port.set_codecs(&["crc16".to_string()]).success);
+
+        let resp = port.set_codecs(&["crc16".to_string(), "rot13".to_string()]);
+        assert!(!resp.success);
+        assert_eq!(vec!["crc16"], port.codec_names());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn send_and_read_through_pipeline() {
+        use std::io::{Read, Write};
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(port.set_codecs(&["crc16".to_string()]).success);
+
+        assert!(port.send_once("ok").await.success);
+        let mut sent = [0; 4];
+        master.read_exact(&mut sent).unwrap();
+        assert_eq!(Crc16.encode(b"ok"), sent);
+
+        master.write_all(&Crc16.encode(b"hi")).unwrap();
+        let resp = port.read_once().await;
+        assert!(resp.success);
+        assert_eq!("hi", resp.content);
+    }
+}
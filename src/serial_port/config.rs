@@ -0,0 +1,191 @@
+//! The line settings a port is opened with, for the devices not using 8N1.
+
+use std::time;
+
+use super::{SerialPort, SerialPortResponse};
+
+/// How to open a port, see `SerialPort::open_port_with_config()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialConfig {
+    pub baudrate: u32,
+    pub data_bits: serialport::DataBits,
+    pub parity: serialport::Parity,
+    pub stop_bits: serialport::StopBits,
+    /// `Software` for XON/XOFF, `Hardware` for RTS/CTS. A port uses one or the
+    /// other, not both: RTS can still be driven by hand with software flow control.
+    pub flow_control: serialport::FlowControl,
+}
+
+impl SerialConfig {
+    /// 8N1 without flow control, the settings of `SerialPort::open_port()`.
+    pub fn new(baudrate: u32) -> SerialConfig {
+        SerialConfig {
+            baudrate,
+            data_bits: serialport::DataBits::Eight,
+            parity: serialport::Parity::None,
+            stop_bits: serialport::StopBits::One,
+            flow_control: serialport::FlowControl::None,
+        }
+    }
+
+    /// Returns why the settings can not be used together, if so.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.baudrate == 0 {
+            return Err("The baudrate can not be 0".to_string());
+        }
+
+        // Two stop bits are 1.5 stop bits for 5 data bits on most UARTs.
+        if self.data_bits == serialport::DataBits::Five
+            && self.stop_bits == serialport::StopBits::Two
+        {
+            return Err("2 stop bits are not supported with 5 data bits".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl SerialPort {
+    /// Returns if a port is open.
+    pub fn is_open(&self) -> bool {
+        self.backend.is_open()
+    }
+
+    /// Returns the line settings of the open port, read from the port itself where
+    /// it can tell them, see `port_settings()` for every setting.
+    pub fn current_config(&self) -> Option<SerialConfig> {
+        self.port_settings().map(|settings| SerialConfig {
+            baudrate: settings.baudrate,
+            data_bits: settings.data_bits,
+            parity: settings.parity,
+            stop_bits: settings.stop_bits,
+            flow_control: settings.flow_control,
+        })
+    }
+
+    /// Opens a serial port as `open_port()` does, with the line settings of `config`.
+    ///
+    /// # Paramters
+    ///
+    /// - `port_path`: As given to `open_port()`.
+    /// - `config`: The baudrate and line settings (7E1 or 8N2 for ex.).
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message, telling why `config` is invalid if so.
+    /// - `success`: if the port has been open correctly.
+    pub async fn open_port_with_config(
+        &mut self,
+        port_path: &str,
+        config: SerialConfig,
+    ) -> SerialPortResponse {
+        self.open_configured(port_path, &config, time::Duration::from_millis(0))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serialport::{DataBits, FlowControl, Parity, StopBits};
+
+    fn config(
+        data_bits: DataBits,
+        parity: Parity,
+        stop_bits: StopBits,
+        flow_control: FlowControl,
+    ) -> SerialConfig {
+        SerialConfig {
+            baudrate: 9600,
+            data_bits,
+            parity,
+            stop_bits,
+            flow_control,
+        }
+    }
+
+    #[test]
+    fn validate_configs() {
+        let valid = [
+            SerialConfig::new(9600),
+            // 7E1 and 8N2.
+            config(
+                DataBits::Seven,
+                Parity::Even,
+                StopBits::One,
+                FlowControl::None,
+            ),
+            config(
+                DataBits::Eight,
+                Parity::None,
+                StopBits::Two,
+                FlowControl::None,
+            ),
+            config(
+                DataBits::Six,
+                Parity::Odd,
+                StopBits::One,
+                FlowControl::Software,
+            ),
+            config(
+                DataBits::Five,
+                Parity::None,
+                StopBits::One,
+                FlowControl::Hardware,
+            ),
+        ];
+        for config in valid.iter() {
+            assert_eq!(Ok(()), config.validate(), "{:?}", config);
+        }
+
+        assert!(SerialConfig::new(0).validate().is_err());
+        assert!(config(
+            DataBits::Five,
+            Parity::None,
+            StopBits::Two,
+            FlowControl::None
+        )
+        .validate()
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn invalid_config_not_opened() {
+        let mut port = SerialPort::new();
+        let resp = port
+            .open_port_with_config("/dev/null", SerialConfig::new(0))
+            .await;
+        assert!(!resp.success);
+        assert_eq!(
+            "Invalid port config: The baudrate can not be 0",
+            resp.content
+        );
+        assert!(port.port_settings().is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn open_with_config() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+
+        // A pty keeps its input flags, unlike the control flags of the data bits,
+        // parity and stop bits.
+        let config = SerialConfig {
+            flow_control: FlowControl::Software,
+            ..SerialConfig::new(19200)
+        };
+        assert!(!port.is_open());
+        assert_eq!(None, port.current_config());
+
+        let resp = port.open_port_with_config(&slave_path, config).await;
+        assert!(resp.success, "{}", resp.content);
+        assert!(port.is_open());
+        assert_eq!(Some(config), port.current_config());
+
+        assert!(port.close_port().await.success);
+        assert!(!port.is_open());
+    }
+}
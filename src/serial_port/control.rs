@@ -0,0 +1,234 @@
+//! The control lines of the open port: the DTR and RTS outputs, used by some
+//! devices to reset or to enter their bootloader, and the CTS, DSR, CD and RI
+//! inputs, for the hardware flow control debugging.
+
+use std::{fmt, time};
+
+use super::backend::SerialBackend;
+use super::{SerialPort, SerialPortResponse};
+
+/// How long `reset_pulse` holds DTR low.
+pub const RESET_PULSE_MS: u64 = 100;
+
+/// The levels of the input lines, `None` for the ones that could not be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ModemSignals {
+    pub cts: Option<bool>,
+    pub dsr: Option<bool>,
+    pub cd: Option<bool>,
+    pub ri: Option<bool>,
+}
+
+impl fmt::Display for ModemSignals {
+    /// As `CTS=1 DSR=0 CD=0 RI=?`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = |signal: Option<bool>| match signal {
+            Some(level) => (level as u8).to_string(),
+            None => "?".to_string(),
+        };
+
+        write!(
+            f,
+            "CTS={} DSR={} CD={} RI={}",
+            level(self.cts),
+            level(self.dsr),
+            level(self.cd),
+            level(self.ri)
+        )
+    }
+}
+
+/// Represents a response given by `SerialPort::read_signals`.
+#[derive(Debug)]
+pub struct SignalsResponse {
+    pub success: bool,
+    /// The levels, as given by `ModemSignals`, followed by the read errors if any.
+    pub content: String,
+    pub signals: ModemSignals,
+}
+
+impl SerialPort {
+    /// Reads the levels of the CTS, DSR, CD and RI input lines.
+    ///
+    /// A line that can not be read (by a network port for ex.) does not prevent
+    /// reading the others.
+    ///
+    /// # Returns
+    ///
+    /// A `SignalsResponse` containing:
+    /// - `content`: the levels, and the errors, as `CTS=1 DSR=? CD=0 RI=0, could not read DSR: ...`.
+    /// - `success`: if every line has been read.
+    /// - `signals`: the levels read.
+    pub fn read_signals(&mut self) -> SignalsResponse {
+        let port = match self.backend.if_open_mut() {
+            Some(port) => port,
+            None => {
+                return SignalsResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                    signals: ModemSignals::default(),
+                }
+            }
+        };
+
+        let mut errors = vec![];
+        let mut read = |name, level: serialport::Result<bool>| match level {
+            Ok(level) => Some(level),
+            Err(e) => {
+                errors.push(format!("could not read {}: {}", name, e));
+                None
+            }
+        };
+        let signals = ModemSignals {
+            cts: read("CTS", port.read_clear_to_send()),
+            dsr: read("DSR", port.read_data_set_ready()),
+            cd: read("CD", port.read_carrier_detect()),
+            ri: read("RI", port.read_ring_indicator()),
+        };
+
+        let mut content = signals.to_string();
+        if !errors.is_empty() {
+            content = format!("{}, {}", content, errors.join(", "));
+        }
+
+        SignalsResponse {
+            success: errors.is_empty(),
+            content,
+            signals,
+        }
+    }
+
+    /// Sets the level of the DTR (Data Terminal Ready) line.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the line has been set.
+    pub fn set_dtr(&mut self, level: bool) -> SerialPortResponse {
+        self.write_line("DTR", level, |port, level| {
+            port.write_data_terminal_ready(level)
+        })
+    }
+
+    /// Sets the level of the RTS (Request To Send) line.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the line has been set.
+    pub fn set_rts(&mut self, level: bool) -> SerialPortResponse {
+        self.write_line("RTS", level, |port, level| {
+            port.write_request_to_send(level)
+        })
+    }
+
+    /// Resets the device the common way: DTR low for `RESET_PULSE_MS`, then high.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the pulse has been sent.
+    pub async fn reset_pulse(&mut self) -> SerialPortResponse {
+        let low = self.set_dtr(false);
+        if !low.success {
+            return low;
+        }
+
+        tokio::time::sleep(time::Duration::from_millis(RESET_PULSE_MS)).await;
+
+        let high = self.set_dtr(true);
+        if !high.success {
+            return high;
+        }
+
+        SerialPortResponse {
+            success: true,
+            content: format!("DTR pulsed low for {}ms", RESET_PULSE_MS),
+        }
+    }
+
+    fn write_line(
+        &mut self,
+        name: &str,
+        level: bool,
+        write: impl FnOnce(&mut dyn SerialBackend, bool) -> serialport::Result<()>,
+    ) -> SerialPortResponse {
+        let port = match self.backend.if_open_mut() {
+            Some(port) => port,
+            None => {
+                return SerialPortResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                }
+            }
+        };
+
+        match write(port, level) {
+            Ok(_) => SerialPortResponse {
+                success: true,
+                content: format!("{} set to {}", name, level as u8),
+            },
+            Err(e) => SerialPortResponse {
+                success: false,
+                content: format!("Could not set {}: {}", name, e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn lines_without_port() {
+        let mut port = SerialPort::new();
+
+        for resp in [
+            port.set_dtr(true),
+            port.set_rts(false),
+            port.reset_pulse().await,
+        ]
+        .iter()
+        {
+            assert!(!resp.success);
+            assert_eq!("No port is currently open", resp.content);
+        }
+
+        let resp = port.read_signals();
+        assert!(!resp.success);
+        assert_eq!("No port is currently open", resp.content);
+    }
+
+    #[test]
+    fn format_signals() {
+        let signals = ModemSignals {
+            cts: Some(true),
+            dsr: Some(false),
+            cd: None,
+            ri: Some(false),
+        };
+        assert_eq!("CTS=1 DSR=0 CD=? RI=0", signals.to_string());
+    }
+
+    #[tokio::test]
+    async fn signals_not_readable() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let path = format!("tcp://{}", listener.local_addr().unwrap());
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&path, 9600).await.success);
+
+        let resp = port.read_signals();
+        assert!(!resp.success);
+        assert_eq!(ModemSignals::default(), resp.signals);
+        assert!(
+            resp.content
+                .starts_with("CTS=? DSR=? CD=? RI=?, could not read CTS: "),
+            "{}",
+            resp.content
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Reads of frames ending with a delimiter byte.
+
+use std::io;
+
+use super::{SerialPort, SerialPortResponse};
+
+/// Maximum frame length used when none is given.
+pub const DEFAULT_READ_UNTIL_MAX_LEN: usize = 1024 * 1024;
+
+const READ_UNTIL_BUFFER_SIZE: usize = 256;
+
+/// What to do when `max_len` bytes have been read without finding the delimiter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Returns the first `max_len` bytes, flagged as truncated. The default.
+    #[default]
+    Truncate,
+    /// Fails the read, dropping the `max_len` bytes.
+    Error,
+    /// Drops the whole oversized frame, up to its delimiter included, and keeps
+    /// reading the next frame.
+    Discard,
+}
+
+/// Represents a response given by `SerialPort::read_until_with`.
+#[derive(Debug)]
+pub struct ReadUntilResponse {
+    pub success: bool,
+    /// The frame, delimiter included, or an informative message.
+    pub content: String,
+    /// If the frame has been cut at `max_len` without reaching the delimiter.
+    pub truncated: bool,
+}
+
+impl SerialPort {
+    /// Reads until `delimiter` is received, as `read_until_with()` does with the
+    /// default max length and overflow policy.
+    ///
+    /// # Paramters
+    ///
+    /// - `delimiter`: The byte ending a frame (`b'\n'`, or ETX `0x03` for ex.).
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: The frame, delimiter included. On timeout, the bytes accumulated
+    ///   so far, or an informative message.
+    /// - `success`: if a frame has been read.
+    pub async fn read_until(&mut self, delimiter: u8) -> SerialPortResponse {
+        let resp = self
+            .read_until_with(delimiter, 0, OverflowPolicy::default())
+            .await;
+
+        SerialPortResponse {
+            success: resp.success,
+            content: resp.content,
+        }
+    }
+
+    /// Reads a line, as `read_until(b'\n')` does, without its `\n` or `\r\n` terminator.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: The line. On timeout, the start of the line received so far, or
+    ///   an informative message.
+    /// - `success`: if a whole line has been read.
+    pub async fn read_line(&mut self) -> SerialPortResponse {
+        let mut resp = self.read_until(b'\n').await;
+
+        if resp.success {
+            resp.content.pop();
+            if resp.content.ends_with('\r') {
+                resp.content.pop();
+            }
+        }
+
+        resp
+    }
+
+    /// Reads until `delimiter` is received, across as many reads as needed.
+    ///
+    /// Each read waits for the port timeout: if it elapses before the delimiter is
+    /// seen, the read fails and the bytes accumulated so far are returned in `content`.
+    /// Bytes following the delimiter are kept for the next read.
+    ///
+    /// # Paramters
+    ///
+    /// - `delimiter`: The byte ending a frame.
+    /// - `max_len`: The maximum frame length, delimiter included. Zero means
+    ///   `DEFAULT_READ_UNTIL_MAX_LEN`. It is also a hard cap on the memory used: a
+    ///   device streaming without ever sending the delimiter can not grow the
+    ///   buffer above it.
+    /// - `overflow`: What to do when `max_len` is reached before the delimiter.
+    ///
+    /// # Returns
+    ///
+    /// A `ReadUntilResponse` containing:
+    /// - `content`: The frame, or an informative message.
+    /// - `success`: if a frame has been read.
+    /// - `truncated`: if the frame has been cut at `max_len`.
+    pub async fn read_until_with(
+        &mut self,
+        delimiter: u8,
+        max_len: usize,
+        overflow: OverflowPolicy,
+    ) -> ReadUntilResponse {
+        if !self.backend.is_open() {
+            return failure("No port is currently open".to_string());
+        }
+
+        let max_len = if max_len == 0 {
+            DEFAULT_READ_UNTIL_MAX_LEN
+        } else {
+            max_len
+        };
+
+        let mut serial_buf = [0; READ_UNTIL_BUFFER_SIZE];
+        let mut discarding = false;
+
+        loop {
+            let buffer = &mut self.read_buffer;
+
+            if discarding {
+                match buffer.iter().position(|b| *b == delimiter) {
+                    Some(end) => {
+                        buffer.drain(..=end);
+                        discarding = false;
+                        continue;
+                    }
+                    None => buffer.clear(),
+                }
+            } else {
+                let window = buffer.len().min(max_len);
+
+                if let Some(end) = buffer[..window].iter().position(|b| *b == delimiter) {
+                    let frame: Vec<u8> = buffer.drain(..=end).collect();
+
+                    return ReadUntilResponse {
+                        success: true,
+                        content: String::from_utf8_lossy(&frame).to_string(),
+                        truncated: false,
+                    };
+                }
+
+                if buffer.len() >= max_len {
+                    let frame: Vec<u8> = buffer.drain(..max_len).collect();
+
+                    match overflow {
+                        OverflowPolicy::Truncate => {
+                            return ReadUntilResponse {
+                                success: true,
+                                content: String::from_utf8_lossy(&frame).to_string(),
+                                truncated: true,
+                            }
+                        }
+                        OverflowPolicy::Error => return failure(max_len_exceeded(max_len)),
+                        OverflowPolicy::Discard => {
+                            discarding = true;
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            // Never reads more than what the frame can still hold, the buffer
+            // staying under `max_len` (`READ_UNTIL_BUFFER_SIZE` when discarding).
+            let len = if discarding {
+                serial_buf.len()
+            } else {
+                serial_buf.len().min(max_len - buffer.len())
+            };
+
+            match self.read_port(&mut serial_buf[..len]).await {
+                Ok(t) => self.read_buffer.extend_from_slice(&serial_buf[..t]),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                    let partial = std::mem::take(&mut self.read_buffer);
+                    return failure(String::from_utf8_lossy(&partial).to_string());
+                }
+                Err(e) => return failure(format!("Serial read error: {}", e)),
+            }
+        }
+    }
+}
+
+/// Content of the `OverflowPolicy::Error` failure.
+pub fn max_len_exceeded(max_len: usize) -> String {
+    format!("Max length of {} bytes exceeded without delimiter", max_len)
+}
+
+fn failure(content: String) -> ReadUntilResponse {
+    ReadUntilResponse {
+        success: false,
+        content,
+        truncated: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn read_until_without_port() {
+        let mut port = SerialPort::new();
+        let resp = port
+            .read_until_with(b'\n', 0, OverflowPolicy::default())
+            .await;
+        assert!(!resp.success);
+    }
+
+    #[cfg(unix)]
+    mod pty {
+        use super::*;
+        use std::io::Write;
+
+        async fn open_pty_port() -> (SerialPort, serialport::TTYPort) {
+            let (master, slave) = serialport::TTYPort::pair().unwrap();
+            let slave_path = serialport::SerialPort::name(&slave).unwrap();
+            let mut port = SerialPort::new();
+            assert!(port.open_port(&slave_path, 9600).await.success);
+            (port, master)
+        }
+
+        #[tokio::test]
+        async fn frame_within_max_len() {
+            let (mut port, mut device) = open_pty_port().await;
+            device.write_all(b"ab\ncd\n").unwrap();
+
+            let resp = port.read_until_with(b'\n', 3, OverflowPolicy::Error).await;
+            assert!(resp.success);
+            assert_eq!("ab\n", resp.content);
+
+            let resp = port.read_until_with(b'\n', 3, OverflowPolicy::Error).await;
+            assert_eq!("cd\n", resp.content);
+        }
+
+        #[tokio::test]
+        async fn overflow_truncate() {
+            let (mut port, mut device) = open_pty_port().await;
+            device.write_all(b"abcdef\n").unwrap();
+
+            let resp = port
+                .read_until_with(b'\n', 4, OverflowPolicy::Truncate)
+                .await;
+            assert!(resp.success);
+            assert!(resp.truncated);
+            assert_eq!("abcd", resp.content);
+
+            let resp = port
+                .read_until_with(b'\n', 4, OverflowPolicy::Truncate)
+                .await;
+            assert!(!resp.truncated);
+            assert_eq!("ef\n", resp.content);
+        }
+
+        #[tokio::test]
+        async fn overflow_error() {
+            let (mut port, mut device) = open_pty_port().await;
+            device.write_all(b"abcdef\n").unwrap();
+
+            let resp = port.read_until_with(b'\n', 4, OverflowPolicy::Error).await;
+            assert!(!resp.success);
+            assert_eq!(max_len_exceeded(4), resp.content);
+            assert!(port.read_buffer.len() <= 4);
+        }
+
+        #[tokio::test]
+        async fn overflow_discard() {
+            let (mut port, mut device) = open_pty_port().await;
+            device.write_all(b"abcdef\ngh\n").unwrap();
+
+            let resp = port
+                .read_until_with(b'\n', 4, OverflowPolicy::Discard)
+                .await;
+            assert!(resp.success);
+            assert!(!resp.truncated);
+            assert_eq!("gh\n", resp.content);
+        }
+
+        #[tokio::test]
+        async fn stream_without_delimiter_stays_capped() {
+            let (mut port, mut device) = open_pty_port().await;
+            device.write_all(&[b'a'; 600]).unwrap();
+
+            let resp = port
+                .read_until_with(b'\n', 8, OverflowPolicy::Discard)
+                .await;
+            assert!(!resp.success);
+            assert!(port.read_buffer.is_empty());
+        }
+
+        #[tokio::test]
+        async fn read_until_delimiter() {
+            let (mut port, mut device) = open_pty_port().await;
+            device.write_all(b"a\nb\n").unwrap();
+
+            // Only the first of the delimiters received at once.
+            let resp = port.read_until(b'\n').await;
+            assert!(resp.success);
+            assert_eq!("a\n", resp.content);
+            assert_eq!("b\n", port.read_until(b'\n').await.content);
+
+            device.write_all(b"\x02data\x03").unwrap();
+            let resp = port.read_until(0x03).await;
+            assert!(resp.success);
+            assert_eq!("\x02data\x03", resp.content);
+
+            device.write_all(b"no etx").unwrap();
+            let resp = port.read_until(0x03).await;
+            assert!(!resp.success);
+            assert_eq!("no etx", resp.content);
+        }
+
+        #[tokio::test]
+        async fn read_line_without_terminator() {
+            let (mut port, mut device) = open_pty_port().await;
+            device.write_all(b"hello\r\nhello\n").unwrap();
+
+            let resp = port.read_line().await;
+            assert!(resp.success);
+            assert_eq!("hello", resp.content);
+            assert_eq!("hello", port.read_line().await.content);
+
+            device.write_all(b"hel").unwrap();
+            let resp = port.read_line().await;
+            assert!(!resp.success);
+            assert_eq!("hel", resp.content);
+        }
+
+        #[tokio::test]
+        async fn partial_frame_on_timeout() {
+            let (mut port, mut device) = open_pty_port().await;
+            device.write_all(b"abc").unwrap();
+
+            let resp = port
+                .read_until_with(b'\n', 0, OverflowPolicy::default())
+                .await;
+            assert!(!resp.success);
+            assert_eq!("abc", resp.content);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Typed errors of the `try_` methods of `SerialPort`, for the callers to tell the
+//! failures apart without matching the messages of `SerialPortResponse`.
+
+use std::{error, fmt, io};
+
+use super::SerialPortResponse;
+
+#[derive(Debug)]
+pub enum SerialError {
+    NoPortOpen,
+    /// A port is already open, with its name.
+    AlreadyOpen(String),
+    /// What timed out, as `Serial read timed out`.
+    Timeout(String),
+    /// An I/O error, its message telling the operation that failed.
+    Io(io::Error),
+    InvalidInput(String),
+}
+
+impl SerialError {
+    /// An `Io` error of `context` (`Serial read error` for ex.), keeping the kind of `e`.
+    pub(crate) fn io(context: &str, e: io::Error) -> SerialError {
+        SerialError::Io(io::Error::new(e.kind(), format!("{}: {}", context, e)))
+    }
+
+    /// The error of a failed read.
+    pub(crate) fn read(e: io::Error) -> SerialError {
+        if e.kind() == io::ErrorKind::TimedOut {
+            SerialError::Timeout("Serial read timed out".to_string())
+        } else {
+            SerialError::io("Serial read error", e)
+        }
+    }
+}
+
+impl fmt::Display for SerialError {
+    /// The `content` of the matching failed `SerialPortResponse`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SerialError::NoPortOpen => write!(f, "No port is currently open"),
+            SerialError::AlreadyOpen(name) => {
+                write!(f, "A port is already open ({}), close it first", name)
+            }
+            SerialError::Timeout(message) | SerialError::InvalidInput(message) => {
+                write!(f, "{}", message)
+            }
+            SerialError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for SerialError {}
+
+impl From<SerialError> for io::Error {
+    /// The `io::Error` of `e`, for the methods following `std::io` (`send_slice()`
+    /// for ex.).
+    fn from(e: SerialError) -> io::Error {
+        match e {
+            SerialError::Io(e) => e,
+            SerialError::NoPortOpen => io::Error::new(io::ErrorKind::NotConnected, e.to_string()),
+            SerialError::Timeout(message) => io::Error::new(io::ErrorKind::TimedOut, message),
+            e => io::Error::new(io::ErrorKind::InvalidInput, e.to_string()),
+        }
+    }
+}
+
+impl From<SerialError> for SerialPortResponse {
+    fn from(e: SerialError) -> SerialPortResponse {
+        SerialPortResponse {
+            success: false,
+            content: e.to_string(),
+        }
+    }
+}
+
+impl From<Result<String, SerialError>> for SerialPortResponse {
+    fn from(result: Result<String, SerialError>) -> SerialPortResponse {
+        match result {
+            Ok(content) => SerialPortResponse {
+                success: true,
+                content,
+            },
+            Err(e) => e.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial_port::SerialPort;
+
+    #[tokio::test]
+    async fn no_port_error() {
+        let mut port = SerialPort::new();
+
+        assert!(matches!(
+            port.try_read_once().await,
+            Err(SerialError::NoPortOpen)
+        ));
+        assert!(matches!(
+            port.try_send_once("ok").await,
+            Err(SerialError::NoPortOpen)
+        ));
+        assert!(matches!(
+            port.try_close_port().await,
+            Err(SerialError::NoPortOpen)
+        ));
+        let resp: SerialPortResponse = port.try_read_once().await.into();
+        assert_eq!("No port is currently open", resp.content);
+    }
+
+    #[tokio::test]
+    async fn invalid_input_error() {
+        let mut port = SerialPort::new();
+
+        assert!(matches!(
+            port.try_read_n_bytes(0).await,
+            Err(SerialError::InvalidInput(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn timeout_error() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.try_open_port(&slave_path, 9600).await.is_ok());
+
+        match port.try_open_port(&slave_path, 9600).await {
+            Err(SerialError::AlreadyOpen(name)) => assert_eq!(slave_path, name),
+            other => panic!("{:?}", other),
+        }
+        match port.try_read_once().await {
+            Err(SerialError::Timeout(message)) => assert_eq!("Serial read timed out", message),
+            other => panic!("{:?}", other),
+        }
+    }
+}
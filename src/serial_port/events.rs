@@ -0,0 +1,168 @@
+//! Notifications of the port state changes.
+
+use std::io;
+
+use super::SerialPort;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortEventKind {
+    Opened,
+    /// Closed on request, or replaced by `swap_port()`.
+    Closed,
+    /// Closed because the device is gone, see `is_disconnect_error()`.
+    Disconnected,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortEvent {
+    pub kind: PortEventKind,
+    pub path: String,
+    /// Human-readable detail, the I/O error of a disconnection for ex.
+    pub reason: String,
+}
+
+/// Called on every `PortEvent`, see `SerialPort::on_event()`.
+pub type EventHook = Box<dyn Fn(PortEvent) + Send>;
+
+/// Returns if a read or write error means that the device is gone (USB adapter
+/// unplugged, remote end of a network port closed, ...).
+pub fn is_disconnect_error(e: &io::Error) -> bool {
+    // EIO, ENXIO and ENODEV, given by ttys whose device has been removed.
+    #[cfg(unix)]
+    {
+        if let Some(5) | Some(6) | Some(19) = e.raw_os_error() {
+            return true;
+        }
+    }
+
+    matches!(
+        e.kind(),
+        io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::UnexpectedEof
+    )
+}
+
+impl SerialPort {
+    /// Registers `hook`, called on every open, close and disconnection of the port.
+    ///
+    /// Hooks are called synchronously, while the `SerialPort` is borrowed: they must
+    /// be quick, and must not use this `SerialPort` (in the server, its lock is held).
+    pub fn on_event(&mut self, hook: EventHook) {
+        self.event_hooks.push(hook);
+    }
+
+    pub(crate) fn emit(&self, kind: PortEventKind, path: &str, reason: String) {
+        for hook in &self.event_hooks {
+            hook(PortEvent {
+                kind,
+                path: path.to_string(),
+                reason: reason.clone(),
+            });
+        }
+    }
+
+    /// Closes the port after `e`, if it means that the device is gone, then reopens
+    /// it if the auto reconnect is enabled, see `enable_auto_reconnect()`.
+    pub(crate) async fn check_disconnect(&mut self, e: &io::Error) {
+        if is_disconnect_error(e) {
+            if let Some(port_path) = self.release_port() {
+                self.emit(PortEventKind::Disconnected, &port_path, e.to_string());
+                self.reconnect().await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::modbus::ModbusStatus;
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time;
+
+    fn record_events(port: &mut SerialPort) -> Arc<Mutex<Vec<PortEvent>>> {
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorded = Arc::clone(&events);
+        port.on_event(Box::new(move |event| recorded.lock().unwrap().push(event)));
+        events
+    }
+
+    #[test]
+    fn disconnect_errors() {
+        assert!(is_disconnect_error(&io::Error::from(
+            io::ErrorKind::BrokenPipe
+        )));
+        assert!(!is_disconnect_error(&io::Error::from(
+            io::ErrorKind::TimedOut
+        )));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn open_close_and_disconnect_events() {
+        let (master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        let events = record_events(&mut port);
+
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(port.close_port().await.success);
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        drop(master);
+        assert!(!port.read_once().await.success);
+        assert!(!port.send_once("ok").await.success);
+
+        let kinds: Vec<PortEventKind> = events.lock().unwrap().iter().map(|e| e.kind).collect();
+        assert_eq!(
+            vec![
+                PortEventKind::Opened,
+                PortEventKind::Closed,
+                PortEventKind::Opened,
+                PortEventKind::Disconnected
+            ],
+            kinds
+        );
+        assert_eq!(slave_path, events.lock().unwrap()[3].path);
+    }
+
+    /// Every read, whatever the method, closes the port of an unplugged device.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn disconnect_events_of_every_read() {
+        let reads = 4;
+        let mut port = SerialPort::new();
+        let events = record_events(&mut port);
+
+        for read in 0..reads {
+            let (master, slave) = serialport::TTYPort::pair().unwrap();
+            let slave_path = serialport::SerialPort::name(&slave).unwrap();
+            assert!(port.open_port(&slave_path, 9600).await.success);
+
+            drop(master);
+            let success = match read {
+                0 => {
+                    port.read_lines(1, time::Duration::from_millis(100))
+                        .await
+                        .success
+                }
+                1 => {
+                    port.send_modbus_rtu(&[0x01, 0x03, 0x00, 0x00]).await.status == ModbusStatus::Ok
+                }
+                2 => port.read_until(b'\n').await.success,
+                _ => port.read_framed().await.success,
+            };
+            assert!(!success);
+            assert!(!port.is_open());
+        }
+
+        let kinds: Vec<PortEventKind> = events.lock().unwrap().iter().map(|e| e.kind).collect();
+        assert_eq!(
+            [PortEventKind::Opened, PortEventKind::Disconnected].repeat(reads),
+            kinds
+        );
+    }
+}
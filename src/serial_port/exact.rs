@@ -0,0 +1,214 @@
+//! Reads of a fixed number of bytes, for binary protocols with fixed-size frames.
+
+use std::{io, time};
+
+use super::{SerialPort, SerialPortResponse};
+
+/// How long to wait before reading again after a read giving no byte, to not spin.
+const READ_EXACT_IDLE_WAIT_MS: u64 = 1;
+
+const READ_EXACT_BUFFER_SIZE: usize = 256;
+
+/// What `read_exact_from()` reads from.
+#[async_trait::async_trait]
+trait ChunkReader: Send {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+/// Reads from `reader` until `buf` holds `n` bytes.
+///
+/// Fails with `TimedOut` if no byte comes within `timeout`, be it because a read
+/// timed out, or because the reads gave no byte (a reader at its end for ex.).
+/// The bytes read so far are left in `buf`.
+async fn read_exact_from(
+    reader: &mut dyn ChunkReader,
+    buf: &mut Vec<u8>,
+    n: usize,
+    timeout: time::Duration,
+) -> io::Result<()> {
+    let mut chunk = [0; READ_EXACT_BUFFER_SIZE];
+    let mut last_byte = time::Instant::now();
+
+    while buf.len() < n {
+        let len = chunk.len().min(n - buf.len());
+
+        match reader.read(&mut chunk[..len]).await {
+            Ok(0) => {
+                if last_byte.elapsed() >= timeout {
+                    return Err(io::ErrorKind::TimedOut.into());
+                }
+                tokio::time::sleep(time::Duration::from_millis(READ_EXACT_IDLE_WAIT_MS)).await;
+            }
+            Ok(t) => {
+                buf.extend_from_slice(&chunk[..t]);
+                last_byte = time::Instant::now();
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the port as its other reads do, see `SerialPort::read_port()`.
+struct PortReader<'a>(&'a mut SerialPort);
+
+#[async_trait::async_trait]
+impl ChunkReader for PortReader<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read_port(buf).await
+    }
+}
+
+impl SerialPort {
+    /// Reads exactly `n` bytes, across as many reads as needed, the bytes read ahead
+    /// by the other read methods coming first.
+    ///
+    /// Each read waits for the read timeout: if it elapses before a byte comes, the
+    /// read fails, and the bytes read so far are dropped.
+    ///
+    /// # Paramters
+    ///
+    /// - `n`: The number of bytes to read.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: The `n` bytes, or an informative message with the number of bytes
+    ///   read before the timeout.
+    /// - `success`: if the `n` bytes have been read.
+    pub async fn read_exact_n(&mut self, n: usize) -> SerialPortResponse {
+        let (buf, read) = match self.read_exact_bytes(n).await {
+            Some(read) => read,
+            None => {
+                return SerialPortResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                }
+            }
+        };
+
+        match read {
+            Ok(_) => SerialPortResponse {
+                success: true,
+                content: String::from_utf8_lossy(&buf).to_string(),
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => SerialPortResponse {
+                success: false,
+                content: format!("Serial read timed out after {} of {} bytes", buf.len(), n),
+            },
+            Err(e) => SerialPortResponse {
+                success: false,
+                content: format!("Serial read error: {}", e),
+            },
+        }
+    }
+
+    /// Reads exactly `n` bytes as `read_exact_n()` does, giving them as is.
+    ///
+    /// # Returns
+    ///
+    /// `None` if no port is open, else the bytes read, the `n` of them unless the
+    /// read failed, and the error if any.
+    pub(crate) async fn read_exact_bytes(&mut self, n: usize) -> Option<(Vec<u8>, io::Result<()>)> {
+        self.backend.if_open()?;
+
+        let ahead = self.read_buffer.len().min(n);
+        let mut buf: Vec<u8> = self.read_buffer.drain(..ahead).collect();
+        buf.reserve(n - ahead);
+
+        let timeout = self.read_timeout;
+        let read = read_exact_from(&mut PortReader(self), &mut buf, n, timeout).await;
+
+        Some((buf, read))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Gives its chunks one read at a time, then no byte.
+    struct ChunkedReader {
+        chunks: VecDeque<Vec<u8>>,
+        reads: usize,
+    }
+
+    #[async_trait::async_trait]
+    impl ChunkReader for ChunkedReader {
+        async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+
+            match self.chunks.pop_front() {
+                Some(mut chunk) => {
+                    let t = chunk.len().min(buf.len());
+                    buf[..t].copy_from_slice(&chunk[..t]);
+                    if t < chunk.len() {
+                        self.chunks.push_front(chunk.split_off(t));
+                    }
+                    Ok(t)
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    fn chunked(chunks: &[&[u8]]) -> ChunkedReader {
+        ChunkedReader {
+            chunks: chunks.iter().map(|chunk| chunk.to_vec()).collect(),
+            reads: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn read_chunks() {
+        let mut reader = chunked(&[b"a", b"", b"bc", b"defg"]);
+        let mut buf = vec![];
+
+        read_exact_from(&mut reader, &mut buf, 5, time::Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(b"abcde".to_vec(), buf);
+        // The rest of the last chunk is not read.
+        assert_eq!(b"fg".to_vec(), reader.chunks[0]);
+    }
+
+    #[tokio::test]
+    async fn time_out_without_spinning() {
+        let mut reader = chunked(&[b"ab"]);
+        let mut buf = vec![];
+
+        let started = time::Instant::now();
+        let e = read_exact_from(&mut reader, &mut buf, 4, time::Duration::from_millis(20))
+            .await
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, e.kind());
+        assert_eq!(b"ab".to_vec(), buf);
+        assert!(started.elapsed() >= time::Duration::from_millis(20));
+        // About one read per `READ_EXACT_IDLE_WAIT_MS`, not a busy loop.
+        assert!(reader.reads < 40, "{} reads", reader.reads);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn read_exact_from_port() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        master.write_all(b"abc").unwrap();
+        let resp = port.read_exact_n(3).await;
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!("abc", resp.content);
+
+        master.write_all(b"d").unwrap();
+        let resp = port.read_exact_n(2).await;
+        assert!(!resp.success);
+        assert_eq!("Serial read timed out after 1 of 2 bytes", resp.content);
+    }
+}
@@ -0,0 +1,138 @@
+//! Request/reply exchanges: a write followed by the read of the reply, within a
+//! total time budget.
+//!
+//! The port is busy for the whole exchange: callers sharing it (the server, behind
+//! a mutex) can not run any other operation on it until the reply is read or the
+//! budget is exhausted, which makes the budget the worst-case time the port is held.
+
+use std::time;
+
+use super::{SerialPort, SerialPortResponse};
+
+/// Budget of `write_then_read` when none is given.
+pub const DEFAULT_WRITE_THEN_READ_BUDGET_MS: u64 = 1000;
+
+impl SerialPort {
+    /// Sends `message` as `send_once()` does, then reads the reply as `read_once()`
+    /// does, the write and the read sharing `budget`.
+    ///
+    /// The write is bounded by the write timeout, and the read by what remains of
+    /// the budget, rather than by the read timeout. Pending coalesced writes are
+    /// written first, and `message` is written right away, whatever the coalescing.
+    ///
+    /// # Paramters
+    ///
+    /// - `message`: The request.
+    /// - `budget`: The maximum time of the whole exchange. Zero means `DEFAULT_WRITE_THEN_READ_BUDGET_MS`.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: the reply, an informative message on failure.
+    /// - `success`: if the request has been sent, and a reply read within the budget.
+    pub async fn write_then_read(
+        &mut self,
+        message: &str,
+        budget: time::Duration,
+    ) -> SerialPortResponse {
+        if !self.backend.is_open() {
+            return SerialPortResponse {
+                success: false,
+                content: "No port is currently open".to_string(),
+            };
+        }
+
+        let budget = if budget.as_nanos() == 0 {
+            time::Duration::from_millis(DEFAULT_WRITE_THEN_READ_BUDGET_MS)
+        } else {
+            budget
+        };
+        let deadline = time::Instant::now() + budget;
+        let out_of_budget = || SerialPortResponse {
+            success: false,
+            content: format!("No reply within the budget of {}ms", budget.as_millis()),
+        };
+
+        let output = self.encode_message(message);
+        let write_timeout = self.write_timeout;
+        self.write_timeout = write_timeout.min(budget);
+        let written = self.write_encoded(&output).await;
+        self.write_timeout = write_timeout;
+        if !written.success {
+            return written;
+        }
+
+        let remaining = deadline.saturating_duration_since(time::Instant::now());
+        if remaining.as_nanos() == 0 {
+            return out_of_budget();
+        }
+
+        let read_timeout = self.read_timeout;
+        if let Some(Err(e)) = self
+            .backend
+            .if_open_mut()
+            .map(|port| port.set_timeout(remaining))
+        {
+            return SerialPortResponse {
+                success: false,
+                content: format!("Could not set the read timeout: {}", e),
+            };
+        }
+
+        let resp = self.read_once().await;
+
+        // Puts back the read timeout in use.
+        if let Some(port) = self.backend.if_open_mut() {
+            let _ = port.set_timeout(read_timeout);
+        }
+
+        // The read timed out with the budget.
+        if !resp.success && resp.content == "Serial read timed out" {
+            return out_of_budget();
+        }
+
+        resp
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[tokio::test]
+    async fn reply_within_budget() {
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        let device = std::thread::spawn(move || {
+            let mut request = [0; 3];
+            master.read_exact(&mut request).unwrap();
+            assert_eq!(b"ID?", &request);
+            master.write_all(b"v1").unwrap();
+            master
+        });
+
+        let resp = port
+            .write_then_read("ID?", time::Duration::from_millis(500))
+            .await;
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!("v1", resp.content);
+        let _master = device.join().unwrap();
+
+        // No reply.
+        let started = time::Instant::now();
+        let resp = port
+            .write_then_read("ID?", time::Duration::from_millis(50))
+            .await;
+        assert!(!resp.success);
+        assert_eq!("No reply within the budget of 50ms", resp.content);
+        assert!(started.elapsed() < time::Duration::from_millis(500));
+        assert_eq!(
+            time::Duration::from_millis(crate::serial_port::SERIAL_OPEN_TIMEOUT_MS),
+            port.backend.timeout()
+        );
+    }
+}
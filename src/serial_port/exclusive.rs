@@ -0,0 +1,99 @@
+//! Exclusive mode of the local ports, preventing other processes from opening them.
+//!
+//! Only available on Unix, through the `TIOCEXCL` ioctl of `tokio_serial::SerialStream`,
+//! which the cross-platform `serialport::SerialPort` trait does not expose: the
+//! backends supporting it implement `SerialBackend::set_exclusive()`. On
+//! Windows, ports are always opened exclusively and the mode can not be changed:
+//! `set_exclusive()` fails there, leaving the port as is.
+
+use super::{SerialPort, SerialPortResponse};
+
+impl SerialPort {
+    /// Sets if the open port, and the next local ports once opened, are opened in
+    /// exclusive mode. Ports are not exclusive by default.
+    ///
+    /// Unix only, see `exclusive`. A process running as root bypasses the exclusive mode.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the mode has been applied. It fails for a network port, and
+    ///   on other platforms than Unix.
+    #[cfg(unix)]
+    pub fn set_exclusive(&mut self, exclusive: bool) -> SerialPortResponse {
+        self.exclusive = exclusive;
+
+        let result = match self.backend.if_open_mut() {
+            Some(port) => port.set_exclusive(exclusive),
+            None => Ok(()),
+        };
+
+        match result {
+            Ok(_) => SerialPortResponse {
+                success: true,
+                content: format!("Exclusive mode {}", if exclusive { "on" } else { "off" }),
+            },
+            Err(e) => SerialPortResponse {
+                success: false,
+                content: format!("Could not set the exclusive mode: {}", e),
+            },
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn set_exclusive(&mut self, _exclusive: bool) -> SerialPortResponse {
+        SerialPortResponse {
+            success: false,
+            content: "The exclusive mode can only be changed on Unix".to_string(),
+        }
+    }
+
+    /// Returns if the ports are opened in exclusive mode, see `set_exclusive()`.
+    #[cfg(unix)]
+    pub fn is_exclusive(&self) -> bool {
+        self.exclusive
+    }
+
+    #[cfg(windows)]
+    pub fn is_exclusive(&self) -> bool {
+        true
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    pub fn is_exclusive(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exclusive_mode_follows_port() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(!port.is_exclusive());
+
+        assert!(port.set_exclusive(true).success);
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(port.is_exclusive());
+
+        assert!(port.set_exclusive(false).success);
+        // The port is still usable after the temporary `TTYPort` is gone.
+        assert!(port.send_once("ok").await.success);
+    }
+
+    #[tokio::test]
+    async fn network_port_is_not_exclusive() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut port = SerialPort::new();
+        let path = format!("tcp://{}", listener.local_addr().unwrap());
+        assert!(port.open_port(&path, 9600).await.success);
+
+        assert!(!port.set_exclusive(true).success);
+    }
+}
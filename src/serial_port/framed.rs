@@ -0,0 +1,189 @@
+//! STX/ETX framing, for the device protocols wrapping each payload between a
+//! start of text and an end of text byte.
+
+use std::io;
+
+use super::delimited::{max_len_exceeded, DEFAULT_READ_UNTIL_MAX_LEN};
+use super::error::SerialError;
+use super::{SerialPort, SerialPortResponse};
+
+/// Start of text, opening a frame.
+pub const STX: u8 = 0x02;
+/// End of text, closing a frame.
+pub const ETX: u8 = 0x03;
+
+const READ_FRAMED_BUFFER_SIZE: usize = 256;
+
+impl SerialPort {
+    /// Sends `payload` between STX and ETX.
+    ///
+    /// The escapes of the payload are parsed as for `send_once()`, the codec
+    /// pipeline, if any, being applied to the whole frame.
+    ///
+    /// # Paramters
+    ///
+    /// - `payload`: The string slice to frame.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message, see `send_once()`.
+    /// - `success`: if the frame has been sent correctly.
+    pub async fn send_framed(&mut self, payload: &str) -> SerialPortResponse {
+        self.try_send_framed(payload).await.into()
+    }
+
+    /// Sends a payload as `send_framed()` does.
+    ///
+    /// # Returns
+    ///
+    /// As `try_send_once()`.
+    pub async fn try_send_framed(&mut self, payload: &str) -> Result<String, SerialError> {
+        let mut frame = vec![STX];
+        frame.extend(self.message_bytes(payload));
+        frame.push(ETX);
+
+        self.try_send_bytes(&frame).await
+    }
+
+    /// Reads a frame, across as many reads as needed, and strips its STX and ETX.
+    ///
+    /// The bytes received before the STX are discarded, as is the start of a frame
+    /// followed by a new STX. Each read waits for the port timeout: if it elapses
+    /// before the ETX, the read fails and the start of the frame is kept for the
+    /// next read. Bytes following the ETX are kept for the next read too.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: The payload, or an informative message. An ETX received
+    ///   without STX before it is a framing error.
+    /// - `success`: if a frame has been read.
+    pub async fn read_framed(&mut self) -> SerialPortResponse {
+        if !self.backend.is_open() {
+            return failure("No port is currently open".to_string());
+        }
+
+        let mut serial_buf = [0; READ_FRAMED_BUFFER_SIZE];
+
+        loop {
+            let buffer = &mut self.read_buffer;
+
+            if let Some(end) = buffer.iter().position(|b| *b == ETX) {
+                let frame: Vec<u8> = buffer.drain(..=end).collect();
+
+                return match frame.iter().rposition(|b| *b == STX) {
+                    Some(start) => SerialPortResponse {
+                        success: true,
+                        content: String::from_utf8_lossy(&frame[start + 1..end]).to_string(),
+                    },
+                    None => failure("Framing error: ETX received without STX".to_string()),
+                };
+            }
+
+            // Only the last frame started can still end, the ETX not received yet.
+            match buffer.iter().rposition(|b| *b == STX) {
+                Some(start) => {
+                    buffer.drain(..start);
+                }
+                None => buffer.clear(),
+            }
+            if buffer.len() >= DEFAULT_READ_UNTIL_MAX_LEN {
+                buffer.clear();
+                return failure(max_len_exceeded(DEFAULT_READ_UNTIL_MAX_LEN));
+            }
+
+            match self.read_port(&mut serial_buf).await {
+                Ok(t) => self.read_buffer.extend_from_slice(&serial_buf[..t]),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                    return failure("Serial read timed out".to_string())
+                }
+                Err(e) => return failure(format!("Serial read error: {}", e)),
+            }
+        }
+    }
+}
+
+fn failure(content: String) -> SerialPortResponse {
+    SerialPortResponse {
+        success: false,
+        content,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn framed_without_port() {
+        let mut port = SerialPort::new();
+
+        let resp = port.read_framed().await;
+        assert!(!resp.success);
+        assert_eq!("No port is currently open", resp.content);
+        assert!(!port.send_framed("ok").await.success);
+    }
+
+    #[cfg(unix)]
+    mod pty {
+        use super::*;
+        use std::io::{Read, Write};
+
+        async fn open_pty_port() -> (SerialPort, serialport::TTYPort) {
+            let (master, slave) = serialport::TTYPort::pair().unwrap();
+            let slave_path = serialport::SerialPort::name(&slave).unwrap();
+            let mut port = SerialPort::new();
+            assert!(port.open_port(&slave_path, 9600).await.success);
+            (port, master)
+        }
+
+        #[tokio::test]
+        async fn send_frame() {
+            let (mut port, mut device) = open_pty_port().await;
+
+            let resp = port.send_framed("ok0x0A").await;
+            assert!(resp.success, "{}", resp.content);
+            let mut sent = [0; 5];
+            device.read_exact(&mut sent).unwrap();
+            assert_eq!(b"\x02ok\n\x03", &sent);
+        }
+
+        #[tokio::test]
+        async fn well_formed_frames() {
+            let (mut port, mut device) = open_pty_port().await;
+            // Noise, an aborted frame, then two frames.
+            device
+                .write_all(b"noise\x02abo\x02first\x03\x02second\x03")
+                .unwrap();
+
+            let resp = port.read_framed().await;
+            assert!(resp.success, "{}", resp.content);
+            assert_eq!("first", resp.content);
+            assert_eq!("second", port.read_framed().await.content);
+
+            // Split across the timeout.
+            device.write_all(b"\x02sl").unwrap();
+            let resp = port.read_framed().await;
+            assert!(!resp.success);
+            assert_eq!("Serial read timed out", resp.content);
+            device.write_all(b"ow\x03").unwrap();
+            assert_eq!("slow", port.read_framed().await.content);
+        }
+
+        #[tokio::test]
+        async fn malformed_frames() {
+            let (mut port, mut device) = open_pty_port().await;
+            device.write_all(b"lost\x03\x02\x03").unwrap();
+
+            let resp = port.read_framed().await;
+            assert!(!resp.success);
+            assert_eq!("Framing error: ETX received without STX", resp.content);
+
+            // The next frame is still read, empty here.
+            let resp = port.read_framed().await;
+            assert!(resp.success, "{}", resp.content);
+            assert_eq!("", resp.content);
+        }
+    }
+}
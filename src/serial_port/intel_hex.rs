@@ -0,0 +1,297 @@
+//! Intel HEX files, for the microcontroller bootloaders taking their firmware as
+//! HEX records.
+//!
+//! The whole file is parsed and checked before the first record is sent: a
+//! corrupted file is not half programmed.
+
+use std::fs;
+use std::path::Path;
+
+use super::error::SerialError;
+use super::{SerialPort, SerialPortResponse};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    Data,
+    EndOfFile,
+    /// Sets bits 4 to 19 of the address of the next data records.
+    ExtendedSegmentAddress,
+    StartSegmentAddress,
+    /// Sets bits 16 to 31 of the address of the next data records.
+    ExtendedLinearAddress,
+    StartLinearAddress,
+}
+
+impl RecordType {
+    fn from_code(code: u8) -> Option<RecordType> {
+        match code {
+            0 => Some(RecordType::Data),
+            1 => Some(RecordType::EndOfFile),
+            2 => Some(RecordType::ExtendedSegmentAddress),
+            3 => Some(RecordType::StartSegmentAddress),
+            4 => Some(RecordType::ExtendedLinearAddress),
+            5 => Some(RecordType::StartLinearAddress),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexRecord {
+    /// Line of the record in the file, from 1.
+    pub line: usize,
+    pub record_type: RecordType,
+    /// For the data records, the address of their first byte, the extended
+    /// addresses before them applied. The address field as is otherwise.
+    pub address: u32,
+    pub data: Vec<u8>,
+    /// The record as written in the file, without its line ending.
+    pub raw: String,
+}
+
+/// Parses the record of `line`, the `number`th line of the file.
+fn parse_record(line: &str, number: usize) -> Result<(RecordType, u16, Vec<u8>), String> {
+    let digits = match line.strip_prefix(':') {
+        Some(digits) => digits,
+        None => return Err(format!("Line {}: a record starts with ':'", number)),
+    };
+    if digits.len() % 2 != 0 || !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return Err(format!("Line {}: invalid hex digits", number));
+    }
+
+    let bytes: Vec<u8> = (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect();
+    if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+        return Err(format!(
+            "Line {}: the length does not match the data",
+            number
+        ));
+    }
+
+    let (record, checksum) = bytes.split_at(bytes.len() - 1);
+    let expected = record
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg();
+    if checksum[0] != expected {
+        return Err(format!(
+            "Line {}: invalid checksum 0x{:02X}, expected 0x{:02X}",
+            number, checksum[0], expected
+        ));
+    }
+
+    let record_type = match RecordType::from_code(record[3]) {
+        Some(record_type) => record_type,
+        None => {
+            return Err(format!(
+                "Line {}: unknown record type 0x{:02X}",
+                number, record[3]
+            ))
+        }
+    };
+    let address = u16::from_be_bytes([record[1], record[2]]);
+
+    Ok((record_type, address, record[4..].to_vec()))
+}
+
+/// Parses the records of an Intel HEX file, checking their checksums.
+///
+/// The blank lines are skipped. The file must end with an end of file record.
+///
+/// # Returns
+///
+/// The records, or the error of the first invalid one, with its line number.
+pub fn parse_intel_hex(text: &str) -> Result<Vec<HexRecord>, String> {
+    let mut records = vec![];
+    let mut base = 0u32;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if records.last().map(|record: &HexRecord| record.record_type)
+            == Some(RecordType::EndOfFile)
+        {
+            return Err(format!("Line {}: record after the end of file", i + 1));
+        }
+
+        let (record_type, offset, data) = parse_record(line, i + 1)?;
+        let address = match record_type {
+            RecordType::Data => base.wrapping_add(offset as u32),
+            _ => offset as u32,
+        };
+        match (record_type, data.as_slice()) {
+            (RecordType::ExtendedSegmentAddress, [high, low]) => {
+                base = (u16::from_be_bytes([*high, *low]) as u32) << 4;
+            }
+            (RecordType::ExtendedLinearAddress, [high, low]) => {
+                base = (u16::from_be_bytes([*high, *low]) as u32) << 16;
+            }
+            (RecordType::ExtendedSegmentAddress, _) | (RecordType::ExtendedLinearAddress, _) => {
+                return Err(format!("Line {}: an extended address has 2 bytes", i + 1));
+            }
+            _ => {}
+        }
+
+        records.push(HexRecord {
+            line: i + 1,
+            record_type,
+            address,
+            data,
+            raw: line.to_string(),
+        });
+    }
+
+    match records.last() {
+        Some(record) if record.record_type == RecordType::EndOfFile => Ok(records),
+        _ => Err("No end of file record".to_string()),
+    }
+}
+
+impl SerialPort {
+    /// Sends the Intel HEX file at `path`, record by record, each followed by the
+    /// line ending (see `set_line_ending()`).
+    ///
+    /// The records are sent as written in the file, neither escaped nor encoded, once
+    /// all of them have been parsed and their checksums checked.
+    ///
+    /// # Paramters
+    ///
+    /// - `path`: The `.hex` file.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message, with the line of the invalid record if any.
+    /// - `success`: if every record has been sent.
+    pub async fn send_intel_hex(&mut self, path: &Path) -> SerialPortResponse {
+        self.try_send_intel_hex(path).await.into()
+    }
+
+    /// Sends an Intel HEX file as `send_intel_hex()` does.
+    ///
+    /// # Returns
+    ///
+    /// The informative message of the sending, or the error: `NoPortOpen`, `Io` if
+    /// the file could not be read, `InvalidInput` for an invalid record, or the
+    /// `Timeout` or `Io` of a write.
+    pub async fn try_send_intel_hex(&mut self, path: &Path) -> Result<String, SerialError> {
+        if !self.backend.is_open() {
+            return Err(SerialError::NoPortOpen);
+        }
+
+        let text = fs::read_to_string(path)
+            .map_err(|e| SerialError::io(&format!("Could not read {}", path.display()), e))?;
+        let records = parse_intel_hex(&text).map_err(SerialError::InvalidInput)?;
+
+        for record in &records {
+            let line = format!("{}{}", record.raw, self.line_ending.as_str());
+            self.try_write_encoded(line.as_bytes())
+                .await
+                .map_err(|e| match e {
+                    SerialError::Timeout(message) => {
+                        SerialError::Timeout(format!("{}, at line {}", message, record.line))
+                    }
+                    e => e,
+                })?;
+        }
+
+        let data_len: usize = records.iter().map(|record| record.data.len()).sum();
+        Ok(format!(
+            "Sent the {} records of {}, {} bytes",
+            records.len(),
+            path.display(),
+            data_len
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX: &str = ":10010000214601360121470136007EFE09D2190140\n\
+                       :02000004ABCD82\n\
+                       :0300300002337A1E\n\
+                       \n\
+                       :00000001FF\n";
+
+    #[test]
+    fn parse_records() {
+        let records = parse_intel_hex(HEX).unwrap();
+
+        assert_eq!(4, records.len());
+        assert_eq!(RecordType::Data, records[0].record_type);
+        assert_eq!(0x0100, records[0].address);
+        assert_eq!(16, records[0].data.len());
+        assert_eq!(0x21, records[0].data[0]);
+        assert_eq!(RecordType::ExtendedLinearAddress, records[1].record_type);
+        assert_eq!(0xABCD_0030, records[2].address);
+        assert_eq!(vec![0x02, 0x33, 0x7A], records[2].data);
+        assert_eq!(5, records[3].line);
+        assert_eq!(":00000001FF", records[3].raw);
+    }
+
+    #[test]
+    fn invalid_records() {
+        let corrupted = HEX.replace(":0300300002337A1E", ":0300300002337A1F");
+        assert_eq!(
+            Err("Line 3: invalid checksum 0x1F, expected 0x1E".to_string()),
+            parse_intel_hex(&corrupted)
+        );
+
+        let invalid = [
+            ("10010000", "Line 1: a record starts with ':'"),
+            (":0001", "Line 1: the length does not match the data"),
+            (":0000000G", "Line 1: invalid hex digits"),
+            (":00000007F9", "Line 1: unknown record type 0x07"),
+            (
+                ":00000001FF\n:00000001FF",
+                "Line 2: record after the end of file",
+            ),
+            (":0300300002337A1E", "No end of file record"),
+        ];
+        for (text, error) in invalid.iter() {
+            assert_eq!(Err(error.to_string()), parse_intel_hex(text), "{}", text);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn send_hex_file() {
+        use std::io::Read;
+
+        let path = std::env::temp_dir().join(format!(
+            "serial-term-rpc-intel-hex-{}.hex",
+            std::process::id()
+        ));
+        let corrupted_path = path.with_extension("bad.hex");
+        fs::write(&path, HEX).unwrap();
+        fs::write(&corrupted_path, HEX.replace("82", "83")).unwrap();
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        let resp = port.send_intel_hex(&corrupted_path).await;
+        assert!(!resp.success);
+        assert_eq!("Line 2: invalid checksum 0x83, expected 0x82", resp.content);
+        assert_eq!(0, port.stats().bytes_sent);
+
+        let resp = port.send_intel_hex(&path).await;
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&corrupted_path).unwrap();
+        assert!(resp.success, "{}", resp.content);
+        assert!(resp.content.starts_with("Sent the 4 records of "));
+        assert!(resp.content.ends_with(", 21 bytes"), "{}", resp.content);
+
+        let expected = HEX.replace("\n\n", "\n").replace('\n', "\r\n");
+        let mut sent = vec![0; expected.len()];
+        master.read_exact(&mut sent).unwrap();
+        assert_eq!(expected.as_bytes(), &sent[..]);
+    }
+}
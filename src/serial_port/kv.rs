@@ -0,0 +1,220 @@
+//! Reads of replies made of `KEY:VALUE` pairs, as given by AT-command-style devices.
+
+use std::{collections::HashMap, io, time};
+
+use super::{delimited, SerialPort};
+
+/// How long `read_kv` waits when no timeout is given.
+pub const DEFAULT_READ_KV_TIMEOUT_MS: u64 = 1000;
+
+/// Separator of the pairs used when none is given.
+pub const DEFAULT_PAIR_SEP: &str = "\n";
+
+/// Separator of a key and its value used when none is given.
+pub const DEFAULT_KV_SEP: &str = ":";
+
+const READ_KV_BUFFER_SIZE: usize = 256;
+
+/// Represents a response given by `SerialPort::read_kv`.
+#[derive(Debug)]
+pub struct KeyValueResponse {
+    pub success: bool,
+    /// Informative message.
+    pub content: String,
+    /// The pairs of the reply, the last one winning for a repeated key.
+    pub pairs: HashMap<String, String>,
+    /// Number of malformed pairs: without separator, or with an empty key.
+    pub skipped: usize,
+}
+
+impl SerialPort {
+    /// Reads a reply until `terminator`, and parses it into key-value pairs.
+    ///
+    /// Keys and values are trimmed of whitespaces (and of the `\r` of `\r\n`
+    /// terminated lines). Empty pairs are ignored, malformed ones are skipped and
+    /// counted. Bytes following the terminator are kept for the next read.
+    ///
+    /// # Paramters
+    ///
+    /// - `terminator`: The end of the reply, not parsed (`"OK\r\n"` for ex.). Empty
+    ///   means everything received until the timeout.
+    /// - `pair_sep`: The separator of the pairs. Empty means `DEFAULT_PAIR_SEP`.
+    /// - `kv_sep`: The separator of a key and its value, the first one of each pair
+    ///   being used. Empty means `DEFAULT_KV_SEP`.
+    /// - `timeout`: How long to wait for the terminator. Zero means `DEFAULT_READ_KV_TIMEOUT_MS`.
+    ///
+    /// # Returns
+    ///
+    /// A `KeyValueResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the reply has been read.
+    /// - `pairs`: the pairs of the reply.
+    /// - `skipped`: the number of malformed pairs.
+    pub async fn read_kv(
+        &mut self,
+        terminator: &str,
+        pair_sep: &str,
+        kv_sep: &str,
+        timeout: time::Duration,
+    ) -> KeyValueResponse {
+        if !self.backend.is_open() {
+            return failure("No port is currently open".to_string());
+        }
+
+        let timeout = if timeout.as_nanos() == 0 {
+            time::Duration::from_millis(DEFAULT_READ_KV_TIMEOUT_MS)
+        } else {
+            timeout
+        };
+        let pair_sep = if pair_sep.is_empty() {
+            DEFAULT_PAIR_SEP
+        } else {
+            pair_sep
+        };
+        let kv_sep = if kv_sep.is_empty() {
+            DEFAULT_KV_SEP
+        } else {
+            kv_sep
+        };
+
+        let terminator = terminator.as_bytes();
+        let deadline = time::Instant::now() + timeout;
+        let mut serial_buf = [0; READ_KV_BUFFER_SIZE];
+
+        let reply = loop {
+            if !terminator.is_empty() {
+                if let Some(end) = find(&self.read_buffer, terminator) {
+                    let reply: Vec<u8> = self.read_buffer.drain(..end).collect();
+                    self.read_buffer.drain(..terminator.len());
+                    break Ok(reply);
+                }
+            }
+
+            // Same bound as `read_until()`, for a device never sending the terminator.
+            if self.read_buffer.len() >= delimited::DEFAULT_READ_UNTIL_MAX_LEN {
+                self.read_buffer.clear();
+                break Err(delimited::max_len_exceeded(
+                    delimited::DEFAULT_READ_UNTIL_MAX_LEN,
+                ));
+            }
+
+            let now = time::Instant::now();
+            if now >= deadline {
+                if terminator.is_empty() {
+                    break Ok(std::mem::take(&mut self.read_buffer));
+                }
+                break Err(format!(
+                    "Terminator not received within {}ms",
+                    timeout.as_millis()
+                ));
+            }
+
+            match self.read_port_within(&mut serial_buf, deadline - now).await {
+                Ok(t) => self.read_buffer.extend_from_slice(&serial_buf[..t]),
+                // The deadline is checked by the next iteration.
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => break Err(format!("Serial read error: {}", e)),
+            }
+        };
+
+        match reply {
+            Ok(reply) => {
+                let (pairs, skipped) =
+                    parse_key_values(&String::from_utf8_lossy(&reply), pair_sep, kv_sep);
+
+                KeyValueResponse {
+                    success: true,
+                    content: format!("Read {} pairs, skipped {}", pairs.len(), skipped),
+                    pairs,
+                    skipped,
+                }
+            }
+            Err(content) => failure(content),
+        }
+    }
+}
+
+/// Parses `reply` into key-value pairs, see `SerialPort::read_kv()`.
+///
+/// # Returns
+///
+/// The pairs, and the number of malformed pairs skipped.
+pub fn parse_key_values(
+    reply: &str,
+    pair_sep: &str,
+    kv_sep: &str,
+) -> (HashMap<String, String>, usize) {
+    let mut pairs = HashMap::new();
+    let mut skipped = 0;
+
+    for pair in reply.split(pair_sep).map(str::trim) {
+        if pair.is_empty() {
+            continue;
+        }
+
+        match pair.split_once(kv_sep) {
+            Some((key, value)) if !key.trim().is_empty() => {
+                pairs.insert(key.trim().to_string(), value.trim().to_string());
+            }
+            _ => skipped += 1,
+        }
+    }
+
+    (pairs, skipped)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn failure(content: String) -> KeyValueResponse {
+    KeyValueResponse {
+        success: false,
+        content,
+        pairs: HashMap::new(),
+        skipped: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pairs() {
+        let (pairs, skipped) =
+            parse_key_values("FW: 1.2\r\nSN:42:a\r\n\r\ngarbage\r\n:x", "\n", ":");
+
+        assert_eq!(2, pairs.len());
+        assert_eq!("1.2", pairs["FW"]);
+        assert_eq!("42:a", pairs["SN"]);
+        assert_eq!(2, skipped);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn read_until_terminator() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        master.write_all(b"A=1;B=2;OK\r\nnext").unwrap();
+        let resp = port
+            .read_kv("OK\r\n", ";", "=", time::Duration::from_millis(100))
+            .await;
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!(2, resp.pairs.len());
+        assert_eq!("2", resp.pairs["B"]);
+        assert_eq!(b"next".to_vec(), port.read_buffer);
+
+        let resp = port
+            .read_kv("OK\r\n", "", "", time::Duration::from_millis(50))
+            .await;
+        assert!(!resp.success);
+    }
+}
@@ -0,0 +1,260 @@
+//! Newline-delimited lines: batch reading, and sending with a line ending.
+
+use std::{io, time};
+
+use super::{SerialPort, SerialPortResponse};
+
+/// How long `read_lines` waits when no timeout is given.
+pub const DEFAULT_READ_LINES_TIMEOUT_MS: u64 = 1000;
+
+const READ_LINES_BUFFER_SIZE: usize = 256;
+
+/// Represents a response given by `SerialPort::read_lines`.
+#[derive(Debug)]
+pub struct ReadLinesResponse {
+    pub success: bool,
+    /// Informative message.
+    pub content: String,
+    /// The complete lines, without their `\n` or `\r\n` terminator.
+    pub lines: Vec<String>,
+    /// If the start of a line, not terminated yet, remains buffered for the next call.
+    pub partial_line: bool,
+}
+
+/// The line ending appended by `SerialPort::send_line`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    Lf,
+    /// The default.
+    #[default]
+    CrLf,
+    Cr,
+    None,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+            LineEnding::None => "",
+        }
+    }
+}
+
+impl SerialPort {
+    /// Sends `message` as `send_once()` does, followed by the line ending, see
+    /// `set_line_ending()`.
+    ///
+    /// # Paramters
+    ///
+    /// - `message`: The line, without line ending.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the line has been sent correctly.
+    pub async fn send_line(&mut self, message: &str) -> SerialPortResponse {
+        let line = format!("{}{}", message, self.line_ending.as_str());
+        let output = self.encode_message(&line);
+        self.send_encoded(&output).await
+    }
+
+    /// Sets the line ending appended by `send_line()`. `LineEnding::CrLf` by
+    /// default, kept across port changes.
+    pub fn set_line_ending(&mut self, ending: LineEnding) {
+        self.line_ending = ending;
+    }
+
+    /// Reads complete lines, for up to `timeout` or until `max_lines` lines are collected.
+    ///
+    /// Bytes following the last complete line, as well as complete lines above
+    /// `max_lines`, are kept for the next read. This buffer is cleared when the
+    /// port is closed.
+    ///
+    /// # Paramters
+    ///
+    /// - `max_lines`: The maximum number of lines to return. Zero means no limit.
+    /// - `timeout`: How long to wait for lines. Zero means `DEFAULT_READ_LINES_TIMEOUT_MS`.
+    ///
+    /// # Returns
+    ///
+    /// A `ReadLinesResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if no error happened. Timing out is not an error.
+    /// - `lines`: the complete lines read.
+    /// - `partial_line`: if an incomplete line remains buffered.
+    pub async fn read_lines(
+        &mut self,
+        max_lines: usize,
+        timeout: time::Duration,
+    ) -> ReadLinesResponse {
+        if !self.backend.is_open() {
+            return ReadLinesResponse {
+                success: false,
+                content: "No port is currently open".to_string(),
+                lines: vec![],
+                partial_line: false,
+            };
+        }
+
+        let timeout = if timeout.as_nanos() == 0 {
+            time::Duration::from_millis(DEFAULT_READ_LINES_TIMEOUT_MS)
+        } else {
+            timeout
+        };
+
+        let deadline = time::Instant::now() + timeout;
+        let mut lines = take_lines(&mut self.read_buffer, max_lines);
+        let mut serial_buf = [0; READ_LINES_BUFFER_SIZE];
+        let mut error = None;
+
+        while max_lines == 0 || lines.len() < max_lines {
+            let now = time::Instant::now();
+            if now >= deadline {
+                break;
+            }
+
+            match self.read_port_within(&mut serial_buf, deadline - now).await {
+                Ok(t) => {
+                    self.read_buffer.extend_from_slice(&serial_buf[..t]);
+
+                    let remaining = if max_lines == 0 {
+                        0
+                    } else {
+                        max_lines - lines.len()
+                    };
+                    lines.extend(take_lines(&mut self.read_buffer, remaining));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => break,
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let partial_line = !self.read_buffer.is_empty() && !self.read_buffer.contains(&b'\n');
+
+        match error {
+            None => ReadLinesResponse {
+                success: true,
+                content: format!("Read {} lines", lines.len()),
+                lines,
+                partial_line,
+            },
+            Some(e) => ReadLinesResponse {
+                success: false,
+                content: format!("Serial read error: {}", e),
+                lines,
+                partial_line,
+            },
+        }
+    }
+}
+
+/// Removes up to `max_lines` complete lines (all of them if zero) from the start of `buffer`.
+fn take_lines(buffer: &mut Vec<u8>, max_lines: usize) -> Vec<String> {
+    let mut lines = vec![];
+
+    while max_lines == 0 || lines.len() < max_lines {
+        let end = match buffer.iter().position(|b| *b == b'\n') {
+            Some(end) => end,
+            None => break,
+        };
+
+        let mut line: Vec<u8> = buffer.drain(..=end).collect();
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        lines.push(String::from_utf8_lossy(&line).to_string());
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_lines_keeps_the_rest() {
+        let mut buffer = b"one\r\ntwo\nthr".to_vec();
+
+        assert_eq!(vec!["one"], take_lines(&mut buffer, 1));
+        assert_eq!(vec!["two"], take_lines(&mut buffer, 0));
+        assert!(take_lines(&mut buffer, 0).is_empty());
+        assert_eq!(b"thr".to_vec(), buffer);
+    }
+
+    #[tokio::test]
+    async fn read_lines_without_port() {
+        let mut port = SerialPort::new();
+        assert!(
+            !port
+                .read_lines(0, time::Duration::from_millis(1))
+                .await
+                .success
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn read_lines_across_calls() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        let timeout = time::Duration::from_millis(50);
+
+        master.write_all(b"one\r\ntwo\nthr").unwrap();
+        let resp = port.read_lines(1, timeout).await;
+        assert!(resp.success);
+        assert_eq!(vec!["one"], resp.lines);
+        assert!(!resp.partial_line);
+
+        let resp = port.read_lines(0, timeout).await;
+        assert_eq!(vec!["two"], resp.lines);
+        assert!(resp.partial_line);
+
+        master.write_all(b"ee\n").unwrap();
+        let resp = port.read_lines(0, timeout).await;
+        assert_eq!(vec!["three"], resp.lines);
+        assert!(!resp.partial_line);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn send_line_endings() {
+        use std::io::Read;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        let endings: [(Option<LineEnding>, &[u8]); 5] = [
+            (None, b"\x02ok\r\n"),
+            (Some(LineEnding::Lf), b"\x02ok\n"),
+            (Some(LineEnding::CrLf), b"\x02ok\r\n"),
+            (Some(LineEnding::Cr), b"\x02ok\r"),
+            (Some(LineEnding::None), b"\x02ok"),
+        ];
+        for (ending, expected) in endings.iter() {
+            if let Some(ending) = ending {
+                port.set_line_ending(*ending);
+            }
+            assert!(port.send_line("0x02ok").await.success);
+
+            let mut sent = vec![0; expected.len()];
+            master.read_exact(&mut sent).unwrap();
+            assert_eq!(expected.to_vec(), sent);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Loopback self-test, for checking the wiring and the driver setup with a TX/RX
+//! jumper in place.
+
+use std::io;
+
+use super::{SerialPort, SerialPortResponse};
+
+/// Number of differing bytes detailed by a mismatch, the others being counted only.
+pub const LOOPBACK_DIFFS_SHOWN: usize = 8;
+
+/// Describes the bytes of `read` differing from the ones of `sent`, as `2 of 5 bytes
+/// differ: at 1 sent 0x62 read 0x78, at 3 sent 0x64 read 0x00`.
+fn diff_summary(sent: &[u8], read: &[u8]) -> String {
+    let diffs: Vec<(usize, u8, u8)> = sent
+        .iter()
+        .zip(read)
+        .enumerate()
+        .filter(|(_, (sent, read))| sent != read)
+        .map(|(i, (sent, read))| (i, *sent, *read))
+        .collect();
+
+    let mut shown: Vec<String> = diffs
+        .iter()
+        .take(LOOPBACK_DIFFS_SHOWN)
+        .map(|(i, sent, read)| format!("at {} sent 0x{:02X} read 0x{:02X}", i, sent, read))
+        .collect();
+    if diffs.len() > LOOPBACK_DIFFS_SHOWN {
+        shown.push("...".to_string());
+    }
+
+    format!(
+        "{} of {} bytes differ: {}",
+        diffs.len(),
+        sent.len(),
+        shown.join(", ")
+    )
+}
+
+impl SerialPort {
+    /// Writes `payload`, then reads back as many bytes, expecting the same ones,
+    /// the TX line being wired to the RX line.
+    ///
+    /// The bytes received before are discarded first. The payload is written and
+    /// read as is, without escapes nor codecs, each read waiting for the read timeout.
+    ///
+    /// # Paramters
+    ///
+    /// - `payload`: The bytes to loop back, not empty.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message, with the bytes that differ on mismatch.
+    /// - `success`: if the payload has been read back unchanged.
+    pub async fn loopback_test(&mut self, payload: &[u8]) -> SerialPortResponse {
+        if payload.is_empty() {
+            return SerialPortResponse {
+                success: false,
+                content: "The loopback payload can not be empty".to_string(),
+            };
+        }
+
+        // A port not supporting it (a network port for ex.) keeps its stale bytes.
+        let cleared = self.clear_input();
+        if !cleared.success && !self.is_open() {
+            return cleared;
+        }
+
+        if let Err(e) = self.try_write_encoded(payload).await {
+            return e.into();
+        }
+
+        let (read, result) = match self.read_exact_bytes(payload.len()).await {
+            Some(read) => read,
+            None => {
+                return SerialPortResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                }
+            }
+        };
+
+        let content = match result {
+            Ok(_) if read == payload => {
+                return SerialPortResponse {
+                    success: true,
+                    content: format!("Loopback OK, {} bytes read back", payload.len()),
+                }
+            }
+            Ok(_) => format!("Loopback mismatch, {}", diff_summary(payload, &read)),
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => format!(
+                "Loopback timed out after {} of {} bytes",
+                read.len(),
+                payload.len()
+            ),
+            Err(e) => format!("Serial read error: {}", e),
+        };
+
+        SerialPortResponse {
+            success: false,
+            content,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarize_diffs() {
+        assert_eq!(
+            "2 of 5 bytes differ: at 1 sent 0x62 read 0x78, at 3 sent 0x64 read 0x00",
+            diff_summary(b"abcde", b"axc\0e")
+        );
+
+        let summary = diff_summary(&[0; 10], &[1; 10]);
+        assert!(summary.starts_with("10 of 10 bytes differ: at 0 sent 0x00 read 0x01"));
+        assert!(
+            summary.ends_with("at 7 sent 0x00 read 0x01, ..."),
+            "{}",
+            summary
+        );
+    }
+
+    #[tokio::test]
+    async fn loopback_without_port() {
+        let mut port = SerialPort::new();
+
+        assert!(!port.loopback_test(b"").await.success);
+        let resp = port.loopback_test(b"ping").await;
+        assert!(!resp.success);
+        assert_eq!("No port is currently open", resp.content);
+    }
+
+    /// Echoes what the port writes, through `change`, as a TX/RX jumper would.
+    #[cfg(unix)]
+    async fn jumpered_port(change: fn(u8) -> u8) -> SerialPort {
+        use std::io::{Read, Write};
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        port.set_read_timeout(std::time::Duration::from_millis(100));
+
+        // Until the port is dropped.
+        serialport::SerialPort::set_timeout(&mut master, std::time::Duration::from_secs(5))
+            .unwrap();
+        std::thread::spawn(move || {
+            let mut byte = [0; 1];
+            while master.read_exact(&mut byte).is_ok() {
+                if master.write_all(&[change(byte[0])]).is_err() {
+                    break;
+                }
+            }
+        });
+
+        port
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn loopback_through_jumper() {
+        let mut port = jumpered_port(|byte| byte).await;
+
+        let resp = port.loopback_test(b"\x00\x01loop\xFF").await;
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!("Loopback OK, 7 bytes read back", resp.content);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn loopback_mismatch() {
+        let mut port = jumpered_port(|byte| if byte == b'o' { b'0' } else { byte }).await;
+
+        let resp = port.loopback_test(b"loop").await;
+        assert!(!resp.success);
+        assert_eq!(
+            "Loopback mismatch, 2 of 4 bytes differ: at 1 sent 0x6F read 0x30, at 2 sent 0x6F read 0x30",
+            resp.content
+        );
+    }
+}
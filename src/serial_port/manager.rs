@@ -0,0 +1,244 @@
+//! Several ports driven at once, each one by its handle.
+//!
+//! Each handle owns a whole `SerialPort`, for every port to keep its own settings,
+//! codecs, read-ahead buffer and stats.
+
+use std::collections::HashMap;
+
+use super::{SerialPort, SerialPortResponse};
+
+/// Represents a response given by `SerialPortManager::open_port`.
+#[derive(Debug)]
+pub struct OpenHandleResponse {
+    pub success: bool,
+    /// Informative message.
+    pub content: String,
+    /// The handle of the open port, empty on failure.
+    pub handle: String,
+}
+
+/// A port of the manager, with the path it has been opened with.
+struct ManagedPort {
+    path: String,
+    port: SerialPort,
+}
+
+/// The ports open by handle.
+#[derive(Default)]
+pub struct SerialPortManager {
+    ports: HashMap<String, ManagedPort>,
+    /// Number of the next handle, handles not being reused.
+    next_handle: u64,
+}
+
+impl SerialPortManager {
+    pub fn new() -> SerialPortManager {
+        SerialPortManager::default()
+    }
+
+    /// Opens a serial port as `SerialPort::open_port()` does, alongside the ports
+    /// already open.
+    ///
+    /// A path already open is not opened twice: the open fails, returning the handle
+    /// it is open with.
+    ///
+    /// # Returns
+    ///
+    /// An `OpenHandleResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the port has been open correctly.
+    /// - `handle`: the handle to give to the other methods for this port, the
+    ///   existing one if the path was already open.
+    pub async fn open_port(&mut self, port_path: &str, baudrate: u32) -> OpenHandleResponse {
+        if let Some((handle, _)) = self
+            .ports
+            .iter()
+            .find(|(_, managed)| managed.path == port_path && managed.port.is_open())
+        {
+            return OpenHandleResponse {
+                success: false,
+                content: format!("{} is already open with the handle {}", port_path, handle),
+                handle: handle.clone(),
+            };
+        }
+
+        let mut port = SerialPort::new();
+        let resp = port.open_port(port_path, baudrate).await;
+        if !resp.success {
+            return OpenHandleResponse {
+                success: false,
+                content: resp.content,
+                handle: String::new(),
+            };
+        }
+
+        self.next_handle += 1;
+        let handle = format!("port{}", self.next_handle);
+        self.ports.insert(
+            handle.clone(),
+            ManagedPort {
+                path: port_path.to_string(),
+                port,
+            },
+        );
+
+        OpenHandleResponse {
+            success: true,
+            content: resp.content,
+            handle,
+        }
+    }
+
+    /// Closes the port of `handle`, as `SerialPort::close_port()` does. The handle
+    /// is no longer valid afterwards.
+    pub async fn close_port(&mut self, handle: &str) -> SerialPortResponse {
+        match self.ports.remove(handle) {
+            Some(mut managed) => managed.port.close_port().await,
+            None => unknown_handle(handle),
+        }
+    }
+
+    /// Returns the port of `handle`, for any `SerialPort` operation.
+    pub fn port_mut(&mut self, handle: &str) -> Option<&mut SerialPort> {
+        self.ports.get_mut(handle).map(|managed| &mut managed.port)
+    }
+
+    /// Returns the handles of the open ports, in the order they have been opened.
+    pub fn handles(&self) -> Vec<String> {
+        let mut handles: Vec<String> = self.ports.keys().cloned().collect();
+        handles.sort_by_key(|handle| handle_number(handle));
+        handles
+    }
+
+    /// Sends `message` to the port of `handle`, see `SerialPort::send_once()`.
+    pub async fn send_once(&mut self, handle: &str, message: &str) -> SerialPortResponse {
+        match self.port_mut(handle) {
+            Some(port) => port.send_once(message).await,
+            None => unknown_handle(handle),
+        }
+    }
+
+    /// Reads from the port of `handle`, see `SerialPort::read_once()`.
+    pub async fn read_once(&mut self, handle: &str) -> SerialPortResponse {
+        match self.port_mut(handle) {
+            Some(port) => port.read_once().await,
+            None => unknown_handle(handle),
+        }
+    }
+}
+
+/// The number of a `port{N}` handle, the handles being numbered in opening order.
+fn handle_number(handle: &str) -> u64 {
+    handle
+        .trim_start_matches("port")
+        .parse()
+        .unwrap_or(u64::MAX)
+}
+
+fn unknown_handle(handle: &str) -> SerialPortResponse {
+    SerialPortResponse {
+        success: false,
+        content: format!("No port is open with the handle {}", handle),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unknown_handles() {
+        let mut manager = SerialPortManager::new();
+
+        assert!(!manager.open_port("/dev/does-not-exist", 9600).await.success);
+        assert!(manager.handles().is_empty());
+        let resp = manager.read_once("port1").await;
+        assert!(!resp.success);
+        assert_eq!("No port is open with the handle port1", resp.content);
+        assert!(!manager.close_port("port1").await.success);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn route_by_handle() {
+        use std::io::{Read, Write};
+
+        let (mut master_a, slave_a) = serialport::TTYPort::pair().unwrap();
+        let (mut master_b, slave_b) = serialport::TTYPort::pair().unwrap();
+        let mut manager = SerialPortManager::new();
+
+        let a = manager
+            .open_port(&serialport::SerialPort::name(&slave_a).unwrap(), 9600)
+            .await
+            .handle;
+        let b = manager
+            .open_port(&serialport::SerialPort::name(&slave_b).unwrap(), 9600)
+            .await
+            .handle;
+        assert_ne!(a, b);
+        assert_eq!(vec![a.clone(), b.clone()], manager.handles());
+
+        assert!(manager.send_once(&a, "to a").await.success);
+        assert!(manager.send_once(&b, "to b").await.success);
+        let mut sent = [0; 4];
+        master_a.read_exact(&mut sent).unwrap();
+        assert_eq!(b"to a", &sent);
+        master_b.read_exact(&mut sent).unwrap();
+        assert_eq!(b"to b", &sent);
+
+        master_b.write_all(b"from b").unwrap();
+        assert!(!manager.read_once(&a).await.success);
+        assert_eq!("from b", manager.read_once(&b).await.content);
+
+        assert!(manager.close_port(&a).await.success);
+        assert_eq!(vec![b.clone()], manager.handles());
+        assert!(manager.port_mut(&b).unwrap().port_settings().is_some());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn handles_in_opening_order() {
+        let mut manager = SerialPortManager::new();
+        let mut ptys = vec![];
+        let mut opened = vec![];
+
+        for _ in 0..10 {
+            let (master, slave) = serialport::TTYPort::pair().unwrap();
+            let slave_path = serialport::SerialPort::name(&slave).unwrap();
+            opened.push(manager.open_port(&slave_path, 9600).await.handle);
+            ptys.push((master, slave));
+        }
+
+        assert_eq!("port10", opened[9]);
+        assert_eq!(opened, manager.handles());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn path_already_open() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut manager = SerialPortManager::new();
+
+        let first = manager.open_port(&slave_path, 9600).await;
+        assert!(first.success);
+
+        let second = manager.open_port(&slave_path, 9600).await;
+        assert!(!second.success);
+        assert_eq!(first.handle, second.handle);
+        assert_eq!(
+            format!(
+                "{} is already open with the handle {}",
+                slave_path, first.handle
+            ),
+            second.content
+        );
+        assert_eq!(vec![first.handle.clone()], manager.handles());
+
+        // Reopened once closed, with a new handle.
+        assert!(manager.close_port(&first.handle).await.success);
+        let third = manager.open_port(&slave_path, 9600).await;
+        assert!(third.success);
+        assert_ne!(first.handle, third.handle);
+    }
+}
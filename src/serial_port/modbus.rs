@@ -0,0 +1,343 @@
+//! Modbus RTU framing on top of `SerialPort`.
+
+use std::{io, time};
+
+use super::error::SerialError;
+use super::SerialPort;
+
+/// Above 19200 bauds, the spec fixes the inter-frame silence to 1.75ms.
+const MODBUS_FIXED_SILENCE_US: u64 = 1750;
+/// Bits needed to transmit one RTU char: start, 8 data, parity (or 2nd stop), stop.
+const MODBUS_BITS_PER_CHAR: u64 = 11;
+/// Slave address, function code and the 2 bytes of CRC.
+const MODBUS_MIN_FRAME_LEN: usize = 4;
+
+/// Outcome of a Modbus RTU exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModbusStatus {
+    Ok,
+    NoPortOpen,
+    InvalidFrame,
+    Timeout,
+    CrcMismatch,
+    IoError,
+}
+
+/// Represents a response given by `SerialPort::send_modbus_rtu`.
+#[derive(Debug)]
+pub struct ModbusResponse {
+    pub status: ModbusStatus,
+    /// The validated response, without its CRC. Empty on failure.
+    pub payload: Vec<u8>,
+    /// Informative message.
+    pub content: String,
+}
+
+impl ModbusResponse {
+    fn failure(status: ModbusStatus, content: String) -> ModbusResponse {
+        ModbusResponse {
+            status,
+            payload: vec![],
+            content,
+        }
+    }
+}
+
+/// Computes the CRC16-Modbus (poly 0xA001 reflected, init 0xFFFF) of `bytes`.
+///
+/// On the wire, the low byte is sent first.
+pub fn crc16_modbus(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for byte in bytes {
+        crc ^= *byte as u16;
+
+        for _ in 0..8 {
+            if crc & 0x0001 != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+
+    crc
+}
+
+/// Returns the 3.5 chars inter-frame silence for a given baudrate.
+pub fn inter_frame_silence(baudrate: u32) -> time::Duration {
+    if baudrate == 0 || baudrate > 19200 {
+        return time::Duration::from_micros(MODBUS_FIXED_SILENCE_US);
+    }
+
+    // 3.5 chars, computed in µs while staying in integers.
+    let micros = 7 * MODBUS_BITS_PER_CHAR * 1_000_000 / (2 * baudrate as u64);
+    time::Duration::from_micros(micros)
+}
+
+impl SerialPort {
+    /// Sends a Modbus RTU request and waits for the slave response, up to the read
+    /// timeout for its first byte, see `set_read_timeout()`.
+    ///
+    /// The bytes already received, left by a previous read, are taken as the start of
+    /// the response.
+    ///
+    /// # Paramters
+    ///
+    /// - `frame`: The request without its CRC: slave address, function code and data.
+    ///
+    /// # Returns
+    ///
+    /// A `ModbusResponse` containing:
+    /// - `status`: `Ok` only if a complete response with a valid CRC has been received.
+    /// - `payload`: The response without its CRC.
+    /// - `content`: informative message.
+    pub async fn send_modbus_rtu(&mut self, frame: &[u8]) -> ModbusResponse {
+        if !self.backend.is_open() {
+            return ModbusResponse::failure(
+                ModbusStatus::NoPortOpen,
+                "No port is currently open".to_string(),
+            );
+        }
+
+        if frame.len() < MODBUS_MIN_FRAME_LEN - 2 {
+            return ModbusResponse::failure(
+                ModbusStatus::InvalidFrame,
+                "A Modbus frame needs at least a slave address and a function code".to_string(),
+            );
+        }
+
+        let crc = crc16_modbus(frame);
+        let mut request = frame.to_vec();
+        request.extend_from_slice(&crc.to_le_bytes());
+
+        // Written right away, with the write timeout, the stats and the traces of
+        // the other writes.
+        match self.try_write_encoded(&request).await {
+            Ok(_) => {}
+            Err(SerialError::Timeout(message)) => {
+                return ModbusResponse::failure(ModbusStatus::Timeout, message)
+            }
+            Err(e) => return ModbusResponse::failure(ModbusStatus::IoError, e.to_string()),
+        }
+
+        let baudrate = match self.backend.if_open() {
+            Some(port) => port.baud_rate().unwrap_or(0),
+            None => {
+                return ModbusResponse::failure(
+                    ModbusStatus::NoPortOpen,
+                    "No port is currently open".to_string(),
+                )
+            }
+        };
+
+        let response = match self.read_rtu_frame(inter_frame_silence(baudrate)).await {
+            Ok(response) => response,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                return ModbusResponse::failure(
+                    ModbusStatus::Timeout,
+                    "Modbus response timed out".to_string(),
+                )
+            }
+            Err(e) => {
+                return ModbusResponse::failure(
+                    ModbusStatus::IoError,
+                    format!("Serial read error: {}", e),
+                )
+            }
+        };
+
+        if response.len() < MODBUS_MIN_FRAME_LEN {
+            return ModbusResponse::failure(
+                ModbusStatus::InvalidFrame,
+                format!("Modbus response too short: {} bytes", response.len()),
+            );
+        }
+
+        let (payload, received_crc) = response.split_at(response.len() - 2);
+        let received_crc = u16::from_le_bytes([received_crc[0], received_crc[1]]);
+        let expected_crc = crc16_modbus(payload);
+
+        if received_crc != expected_crc {
+            return ModbusResponse::failure(
+                ModbusStatus::CrcMismatch,
+                format!(
+                    "Modbus CRC mismatch: expected 0x{:04X}, received 0x{:04X}",
+                    expected_crc, received_crc
+                ),
+            );
+        }
+
+        ModbusResponse {
+            status: ModbusStatus::Ok,
+            payload: payload.to_vec(),
+            content: format!("Received a {} bytes Modbus response", payload.len()),
+        }
+    }
+
+    /// Reads one RTU frame: waits for its first byte, then reads until the line
+    /// stays silent for `silence`.
+    async fn read_rtu_frame(&mut self, silence: time::Duration) -> io::Result<Vec<u8>> {
+        let mut response = std::mem::take(&mut self.read_buffer);
+        let mut serial_buf = [0; 256];
+
+        loop {
+            let timeout = if response.is_empty() {
+                self.read_timeout
+            } else {
+                silence
+            };
+
+            match self.read_port_within(&mut serial_buf, timeout).await {
+                Ok(t) => response.extend_from_slice(&serial_buf[..t]),
+                // The end of the frame is the silence following the received bytes.
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut && !response.is_empty() => {
+                    return Ok(response);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_modbus_known_vector() {
+        // Read 10 holding registers from slave 1: 01 03 00 00 00 0A C5 CD.
+        let crc = crc16_modbus(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]);
+        assert_eq!([0xC5, 0xCD], crc.to_le_bytes());
+    }
+
+    #[test]
+    fn inter_frame_silence_per_baudrate() {
+        assert_eq!(time::Duration::from_micros(4010), inter_frame_silence(9600));
+        assert_eq!(
+            time::Duration::from_micros(1750),
+            inter_frame_silence(115200)
+        );
+    }
+
+    #[tokio::test]
+    async fn send_modbus_rtu_without_port() {
+        let mut port = SerialPort::new();
+        let resp = port.send_modbus_rtu(&[0x01, 0x03]).await;
+        assert_eq!(ModbusStatus::NoPortOpen, resp.status);
+    }
+
+    #[cfg(unix)]
+    mod pty {
+        use super::*;
+        use std::io::{Read, Write};
+        use std::thread;
+
+        /// Opens a `SerialPort` on a pty, the returned end playing the Modbus slave.
+        async fn open_pty_port() -> (SerialPort, serialport::TTYPort) {
+            let (master, slave) = serialport::TTYPort::pair().unwrap();
+            let slave_path = serialport::SerialPort::name(&slave).unwrap();
+            let mut port = SerialPort::new();
+            assert!(port.open_port(&slave_path, 9600).await.success);
+            (port, master)
+        }
+
+        fn reply_once(
+            mut slave: serialport::TTYPort,
+            reply: Vec<u8>,
+        ) -> thread::JoinHandle<Vec<u8>> {
+            thread::spawn(move || {
+                let mut request = [0; 8];
+                slave.read_exact(&mut request).unwrap();
+                slave.write_all(&reply).unwrap();
+                // Keeps the pty open until the response has been read.
+                thread::sleep(time::Duration::from_millis(100));
+                request.to_vec()
+            })
+        }
+
+        #[tokio::test]
+        async fn validated_payload() {
+            let (mut port, slave) = open_pty_port().await;
+            let mut reply = vec![0x01, 0x03, 0x02, 0x00, 0x2A];
+            reply.extend_from_slice(&crc16_modbus(&reply).to_le_bytes());
+            let slave = reply_once(slave, reply);
+
+            let resp = port
+                .send_modbus_rtu(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01])
+                .await;
+
+            assert_eq!(ModbusStatus::Ok, resp.status);
+            assert_eq!(vec![0x01, 0x03, 0x02, 0x00, 0x2A], resp.payload);
+            assert_eq!(
+                vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A],
+                slave.join().unwrap()
+            );
+        }
+
+        #[tokio::test]
+        async fn request_counted_in_the_stats() {
+            let (mut port, slave) = open_pty_port().await;
+            let mut reply = vec![0x01, 0x06, 0x00, 0x01, 0x00, 0x03];
+            reply.extend_from_slice(&crc16_modbus(&reply).to_le_bytes());
+            let slave = reply_once(slave, reply);
+
+            let resp = port
+                .send_modbus_rtu(&[0x01, 0x06, 0x00, 0x01, 0x00, 0x03])
+                .await;
+
+            assert_eq!(ModbusStatus::Ok, resp.status);
+            assert_eq!(8, port.stats().bytes_sent);
+            slave.join().unwrap();
+        }
+
+        #[tokio::test]
+        async fn buffered_start_of_response() {
+            let (mut port, slave) = open_pty_port().await;
+            let mut reply = vec![0x01, 0x03, 0x02, 0x00, 0x2A];
+            reply.extend_from_slice(&crc16_modbus(&reply).to_le_bytes());
+            port.read_buffer = reply[..3].to_vec();
+            let slave = reply_once(slave, reply[3..].to_vec());
+
+            let resp = port
+                .send_modbus_rtu(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01])
+                .await;
+
+            assert_eq!(ModbusStatus::Ok, resp.status);
+            assert_eq!(vec![0x01, 0x03, 0x02, 0x00, 0x2A], resp.payload);
+            assert!(port.read_buffer.is_empty());
+            slave.join().unwrap();
+        }
+
+        #[tokio::test]
+        async fn crc_mismatch() {
+            let (mut port, slave) = open_pty_port().await;
+            let slave = reply_once(slave, vec![0x01, 0x03, 0x02, 0x00, 0x2A, 0x00, 0x00]);
+
+            let resp = port
+                .send_modbus_rtu(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01])
+                .await;
+
+            assert_eq!(ModbusStatus::CrcMismatch, resp.status);
+            assert!(resp.payload.is_empty());
+            slave.join().unwrap();
+        }
+
+        #[tokio::test]
+        async fn timeout() {
+            let (mut port, _slave) = open_pty_port().await;
+            assert!(
+                port.set_read_timeout(time::Duration::from_millis(50))
+                    .success
+            );
+
+            let start = time::Instant::now();
+            let resp = port
+                .send_modbus_rtu(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01])
+                .await;
+
+            assert_eq!(ModbusStatus::Timeout, resp.status);
+            assert!(start.elapsed() < time::Duration::from_millis(500));
+        }
+    }
+}
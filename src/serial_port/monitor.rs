@@ -0,0 +1,129 @@
+//! Mirroring of the serial traffic to raw TCP observers, for live monitoring with
+//! `nc` or `telnet` alongside the RPC interface.
+
+use std::{
+    io::{self, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use super::{trace::Direction, SerialPort};
+
+/// Called with the bytes crossing the port, see `SerialPort::on_traffic()`.
+pub type TrafficHook = Box<dyn Fn(Direction, &[u8]) + Send>;
+
+pub(crate) fn tap(hooks: &[TrafficHook], direction: Direction, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+
+    for hook in hooks {
+        hook(direction, bytes);
+    }
+}
+
+impl SerialPort {
+    /// Registers `hook`, called with the bytes written by `send_once()` and
+    /// `send_slice()`, and read by `read_once()`, `read_into()`, `read_lines()`,
+    /// `read_until_with()`, `read_kv()` and `read_samples()`. The exchanges of the ack, Modbus and
+    /// autobaud methods, and of the repeater, are not reported.
+    ///
+    /// Same contract as the `on_event()` hooks: they are called synchronously, and
+    /// must be quick.
+    pub fn on_traffic(&mut self, hook: TrafficHook) {
+        self.traffic_hooks.push(hook);
+    }
+}
+
+/// A TCP listener mirroring the traffic of a port to every client connected to it.
+///
+/// Clients are read-only observers: what they send is ignored.
+pub struct TcpMonitor {
+    observers: Arc<Mutex<Vec<TcpStream>>>,
+    local_addr: SocketAddr,
+}
+
+impl TcpMonitor {
+    /// Listens on `addr`, observers being accepted by a background thread for the
+    /// lifetime of the process.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<TcpMonitor> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let observers = Arc::new(Mutex::new(vec![]));
+
+        let accepted = Arc::clone(&observers);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = stream.shutdown(Shutdown::Read);
+                if stream.set_nonblocking(true).is_ok() {
+                    accepted.lock().unwrap().push(stream);
+                }
+            }
+        });
+
+        Ok(TcpMonitor {
+            observers,
+            local_addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns a hook for `SerialPort::on_traffic()`, sending the traffic to the observers.
+    ///
+    /// Observers get the raw bytes of both directions, in the order they crossed the
+    /// port. A slow observer misses bytes rather than slowing down the port, and a
+    /// disconnected one is forgotten.
+    pub fn hook(&self) -> TrafficHook {
+        let observers = Arc::clone(&self.observers);
+
+        Box::new(move |_direction, bytes| {
+            observers
+                .lock()
+                .unwrap()
+                .retain(|mut observer| match observer.write(bytes) {
+                    Ok(_) => true,
+                    Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+                });
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn mirror_to_observer() {
+        use std::io::Read;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        let monitor = TcpMonitor::bind("127.0.0.1:0").unwrap();
+        port.on_traffic(monitor.hook());
+        let mut observer = TcpStream::connect(monitor.local_addr()).unwrap();
+        while monitor.observers.lock().unwrap().is_empty() {
+            thread::sleep(time::Duration::from_millis(1));
+        }
+
+        assert!(port.send_once("ok").await.success);
+        master.read_exact(&mut [0; 2]).unwrap();
+        master.write_all(b"hi").unwrap();
+        assert!(port.read_once().await.success);
+
+        let mut mirrored = [0; 4];
+        observer
+            .set_read_timeout(Some(time::Duration::from_secs(1)))
+            .unwrap();
+        observer.read_exact(&mut mirrored).unwrap();
+        assert_eq!(b"okhi", &mirrored);
+    }
+}
@@ -0,0 +1,639 @@
+//! Serial ports reached over the network, behind a serial-to-Ethernet adapter.
+//!
+//! Two kinds of paths are recognized by `SerialPort::open_port()`:
+//!
+//! - `tcp://host:port`: a raw TCP socket, the bytes being forwarded as is. The line
+//!   settings can not be changed from here: they are only stored, and have to match
+//!   the configuration of the adapter.
+//! - `rfc2217://host:port`: a Telnet connection using the COM-PORT-OPTION of RFC 2217
+//!   to set the baudrate, framing, flow control and control lines of the remote port.
+//!   Only the client-to-server commands are implemented: the notifications of the
+//!   server (modem state, ...) are ignored, so the input lines can not be read.
+//!
+//! The ports are read and written through `AsyncRead` and `AsyncWrite`. Their
+//! `io::Read` and `io::Write` do not wait: they fail with `WouldBlock` when the
+//! socket is not ready.
+
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, StopBits};
+
+/// How long to wait for the TCP connection to be established.
+pub const NETWORK_CONNECT_TIMEOUT_MS: u64 = 3000;
+
+pub(crate) const TCP_SCHEME: &str = "tcp://";
+pub(crate) const RFC2217_SCHEME: &str = "rfc2217://";
+
+// Telnet (RFC 854) commands and options.
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const BINARY: u8 = 0;
+const COM_PORT_OPTION: u8 = 44;
+
+// RFC 2217 client-to-server commands.
+const SET_BAUDRATE: u8 = 1;
+const SET_DATASIZE: u8 = 2;
+const SET_PARITY: u8 = 3;
+const SET_STOPSIZE: u8 = 4;
+const SET_CONTROL: u8 = 5;
+const PURGE_DATA: u8 = 12;
+
+/// Returns if `port_path` designates a network port.
+pub fn is_network_path(port_path: &str) -> bool {
+    port_path.starts_with(TCP_SCHEME) || port_path.starts_with(RFC2217_SCHEME)
+}
+
+/// Strips the Telnet commands out of the received bytes.
+#[derive(Debug, Default)]
+struct TelnetDecoder {
+    state: TelnetState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum TelnetState {
+    #[default]
+    Data,
+    Iac,
+    /// After `WILL`, `WONT`, `DO` or `DONT`, waiting for the option.
+    Negotiation,
+    Subnegotiation,
+    SubnegotiationIac,
+}
+
+impl TelnetDecoder {
+    /// Decodes `input` into `output`, which must be at least as long.
+    ///
+    /// # Returns
+    ///
+    /// The number of data bytes written to `output`.
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> usize {
+        let mut len = 0;
+
+        for byte in input {
+            self.state = match (self.state, *byte) {
+                (TelnetState::Data, IAC) => TelnetState::Iac,
+                (TelnetState::Data, byte) | (TelnetState::Iac, byte @ IAC) => {
+                    output[len] = byte;
+                    len += 1;
+                    TelnetState::Data
+                }
+                (TelnetState::Iac, WILL..=DONT) => TelnetState::Negotiation,
+                (TelnetState::Iac, SB) => TelnetState::Subnegotiation,
+                (TelnetState::Iac, _) | (TelnetState::Negotiation, _) => TelnetState::Data,
+                (TelnetState::Subnegotiation, IAC) => TelnetState::SubnegotiationIac,
+                (TelnetState::Subnegotiation, _) => TelnetState::Subnegotiation,
+                (TelnetState::SubnegotiationIac, SE) => TelnetState::Data,
+                (TelnetState::SubnegotiationIac, _) => TelnetState::Subnegotiation,
+            };
+        }
+
+        len
+    }
+}
+
+/// Doubles the `IAC` bytes, as required for data sent over Telnet.
+fn telnet_escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+
+    for byte in data {
+        escaped.push(*byte);
+        if *byte == IAC {
+            escaped.push(IAC);
+        }
+    }
+
+    escaped
+}
+
+fn unsupported(what: &str) -> serialport::Error {
+    serialport::Error::new(
+        serialport::ErrorKind::Unknown,
+        format!("{} is not supported by this network port", what),
+    )
+}
+
+/// A network port, usable wherever a `serialport::SerialPort` is.
+pub struct NetworkPort {
+    stream: TcpStream,
+    name: String,
+    rfc2217: bool,
+    decoder: TelnetDecoder,
+    /// Buffer the RFC 2217 reads receive the Telnet stream into, kept to avoid an
+    /// allocation per read.
+    raw_buf: Vec<u8>,
+    /// Telnet bytes not sent yet: the commands, and the end of an escaped write.
+    unsent: RefCell<Vec<u8>>,
+    baud_rate: u32,
+    data_bits: DataBits,
+    flow_control: FlowControl,
+    parity: Parity,
+    stop_bits: StopBits,
+    timeout: time::Duration,
+}
+
+impl NetworkPort {
+    /// Connects to a `tcp://` or `rfc2217://` port, in 8N1 without flow control.
+    pub async fn open(
+        port_path: &str,
+        baud_rate: u32,
+        timeout: time::Duration,
+    ) -> serialport::Result<NetworkPort> {
+        let (address, rfc2217) = if let Some(address) = port_path.strip_prefix(TCP_SCHEME) {
+            (address, false)
+        } else if let Some(address) = port_path.strip_prefix(RFC2217_SCHEME) {
+            (address, true)
+        } else {
+            return Err(serialport::Error::new(
+                serialport::ErrorKind::InvalidInput,
+                format!("{} is not a network port", port_path),
+            ));
+        };
+
+        let address = tokio::net::lookup_host(address)
+            .await?
+            .next()
+            .ok_or_else(|| {
+                serialport::Error::new(
+                    serialport::ErrorKind::InvalidInput,
+                    format!("Could not resolve {}", address),
+                )
+            })?;
+
+        let stream = tokio::time::timeout(
+            time::Duration::from_millis(NETWORK_CONNECT_TIMEOUT_MS),
+            TcpStream::connect(address),
+        )
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Connection timed out"))??;
+        stream.set_nodelay(true)?;
+
+        let mut port = NetworkPort {
+            stream,
+            name: port_path.to_string(),
+            rfc2217,
+            decoder: TelnetDecoder::default(),
+            raw_buf: vec![],
+            unsent: RefCell::new(vec![]),
+            baud_rate,
+            data_bits: DataBits::Eight,
+            flow_control: FlowControl::None,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            timeout,
+        };
+
+        if rfc2217 {
+            port.unsent.get_mut().extend_from_slice(&[
+                IAC,
+                WILL,
+                BINARY,
+                IAC,
+                DO,
+                BINARY,
+                IAC,
+                WILL,
+                COM_PORT_OPTION,
+            ]);
+
+            port.send_command(SET_BAUDRATE, &baud_rate.to_be_bytes())?;
+            port.send_command(SET_DATASIZE, &[8])?;
+            port.send_command(SET_PARITY, &[1])?;
+            port.send_command(SET_STOPSIZE, &[1])?;
+            port.send_command(SET_CONTROL, &[1])?;
+            let port = &mut port;
+            std::future::poll_fn(move |cx| port.poll_send_unsent(cx)).await?;
+        }
+
+        Ok(port)
+    }
+
+    /// Returns another port on the same connection.
+    pub fn try_clone_stream(&self) -> serialport::Result<NetworkPort> {
+        #[cfg(unix)]
+        let stream = {
+            use std::os::unix::io::AsFd;
+            std::net::TcpStream::from(self.stream.as_fd().try_clone_to_owned()?)
+        };
+        #[cfg(windows)]
+        let stream = {
+            use std::os::windows::io::AsSocket;
+            std::net::TcpStream::from(self.stream.as_socket().try_clone_to_owned()?)
+        };
+        stream.set_nonblocking(true)?;
+
+        Ok(NetworkPort {
+            stream: TcpStream::from_std(stream)?,
+            name: self.name.clone(),
+            rfc2217: self.rfc2217,
+            decoder: TelnetDecoder::default(),
+            raw_buf: vec![],
+            unsent: RefCell::new(vec![]),
+            baud_rate: self.baud_rate,
+            data_bits: self.data_bits,
+            flow_control: self.flow_control,
+            parity: self.parity,
+            stop_bits: self.stop_bits,
+            timeout: self.timeout,
+        })
+    }
+
+    /// Sends a COM-PORT-OPTION subnegotiation, or queues it until the socket is
+    /// ready.
+    fn send_command(&self, command: u8, value: &[u8]) -> io::Result<()> {
+        let mut frame = vec![IAC, SB, COM_PORT_OPTION, command];
+        frame.extend(telnet_escape(value));
+        frame.extend_from_slice(&[IAC, SE]);
+        self.unsent.borrow_mut().extend(frame);
+
+        match self.send_unsent() {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Sends the queued Telnet bytes, as long as the socket is ready.
+    fn send_unsent(&self) -> io::Result<()> {
+        let mut unsent = self.unsent.borrow_mut();
+
+        while !unsent.is_empty() {
+            let t = self.stream.try_write(&unsent)?;
+            if t == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            unsent.drain(..t);
+        }
+
+        Ok(())
+    }
+
+    fn poll_send_unsent(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            match self.send_unsent() {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    ready!(self.stream.poll_write_ready(cx))?
+                }
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    /// Sends a command if the line settings can be changed remotely.
+    fn configure(&self, command: u8, value: &[u8]) -> serialport::Result<()> {
+        if self.rfc2217 {
+            self.send_command(command, value)?;
+        }
+
+        Ok(())
+    }
+
+    fn control(&self, what: &str, value: u8) -> serialport::Result<()> {
+        if !self.rfc2217 {
+            return Err(unsupported(what));
+        }
+
+        Ok(self.send_command(SET_CONTROL, &[value])?)
+    }
+}
+
+impl Read for NetworkPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.rfc2217 {
+            return self.stream.try_read(buf);
+        }
+
+        // Reads no more than `buf` can hold once decoded, and keeps reading if
+        // only Telnet commands have been received.
+        if self.raw_buf.len() < buf.len() {
+            self.raw_buf.resize(buf.len(), 0);
+        }
+        let raw = &mut self.raw_buf[..buf.len()];
+        loop {
+            let t = self.stream.try_read(raw)?;
+            if t == 0 {
+                return Ok(0);
+            }
+
+            let len = self.decoder.decode(&raw[..t], buf);
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+impl Write for NetworkPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send_unsent()?;
+        if !self.rfc2217 {
+            return self.stream.try_write(buf);
+        }
+
+        // The escaped bytes not taken by the socket are sent before the next ones.
+        let escaped = telnet_escape(buf);
+        let t = self.stream.try_write(&escaped)?;
+        self.unsent.get_mut().extend_from_slice(&escaped[t..]);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_unsent()
+    }
+}
+
+impl AsyncRead for NetworkPort {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            match self.read(buf.initialize_unfilled()) {
+                Ok(t) => {
+                    buf.advance(t);
+                    return Poll::Ready(Ok(()));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    ready!(self.stream.poll_read_ready(cx))?
+                }
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+    }
+}
+
+impl AsyncWrite for NetworkPort {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            match self.write(buf) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    ready!(self.stream.poll_write_ready(cx))?
+                }
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_send_unsent(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_send_unsent(cx))?;
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+impl serialport::SerialPort for NetworkPort {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(self.baud_rate)
+    }
+
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(self.data_bits)
+    }
+
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(self.flow_control)
+    }
+
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(self.parity)
+    }
+
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(self.stop_bits)
+    }
+
+    fn timeout(&self) -> time::Duration {
+        self.timeout
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.configure(SET_BAUDRATE, &baud_rate.to_be_bytes())?;
+        self.baud_rate = baud_rate;
+        Ok(())
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        let size = match data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        self.configure(SET_DATASIZE, &[size])?;
+        self.data_bits = data_bits;
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        let control = match flow_control {
+            FlowControl::None => 1,
+            FlowControl::Software => 2,
+            FlowControl::Hardware => 3,
+        };
+        self.configure(SET_CONTROL, &[control])?;
+        self.flow_control = flow_control;
+        Ok(())
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        let value = match parity {
+            Parity::None => 1,
+            Parity::Odd => 2,
+            Parity::Even => 3,
+        };
+        self.configure(SET_PARITY, &[value])?;
+        self.parity = parity;
+        Ok(())
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        let size = match stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        self.configure(SET_STOPSIZE, &[size])?;
+        self.stop_bits = stop_bits;
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: time::Duration) -> serialport::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.control("RTS", if level { 11 } else { 12 })
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.control("DTR", if level { 8 } else { 9 })
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Err(unsupported("Reading CTS"))
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Err(unsupported("Reading DSR"))
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Err(unsupported("Reading RI"))
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Err(unsupported("Reading CD"))
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        let purge = match buffer_to_clear {
+            ClearBuffer::Input => 1,
+            ClearBuffer::Output => 2,
+            ClearBuffer::All => 3,
+        };
+        self.configure(PURGE_DATA, &[purge])?;
+
+        if buffer_to_clear != ClearBuffer::Output {
+            // Drops what has already been received by the socket.
+            let mut discarded = [0; 256];
+            while let Ok(t) = self.stream.try_read(&mut discarded) {
+                if t == 0 {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn serialport::SerialPort>> {
+        Ok(Box::new(self.try_clone_stream()?))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        self.control("Break", 5)
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.control("Break", 6)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial_port::SerialPort;
+    use std::net::TcpListener;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn telnet_decoder_strips_commands() {
+        let mut decoder = TelnetDecoder::default();
+        let mut output = [0; 16];
+
+        let input = [
+            b'a', IAC, IAC, IAC, DO, BINARY, b'b', IAC, SB, 44, 107, 0, IAC,
+        ];
+        let len = decoder.decode(&input, &mut output);
+        assert_eq!(&[b'a', IAC, b'b'], &output[..len]);
+
+        // The subnegotiation ends in the next chunk.
+        let len = decoder.decode(&[SE, b'c'], &mut output);
+        assert_eq!(b"c", &output[..len]);
+    }
+
+    #[test]
+    fn telnet_escape_doubles_iac() {
+        assert_eq!(vec![1, IAC, IAC, 2], telnet_escape(&[1, IAC, 2]));
+    }
+
+    #[tokio::test]
+    async fn raw_tcp_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let path = format!("tcp://{}", listener.local_addr().unwrap());
+
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&path, 9600).await.success);
+        let (mut device, _) = listener.accept().unwrap();
+
+        assert!(port.send_once("ok").await.success);
+        let mut received = [0; 2];
+        device.read_exact(&mut received).unwrap();
+        assert_eq!(b"ok", &received);
+
+        device.write_all(b"hi").unwrap();
+        let resp = port.read_once().await;
+        assert!(resp.success);
+        assert_eq!("hi", resp.content);
+
+        assert_eq!("Serial read timed out", port.read_once().await.content);
+    }
+
+    #[tokio::test]
+    async fn rfc2217_port_negotiates_and_escapes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let path = format!("rfc2217://{}", listener.local_addr().unwrap());
+
+        let mut port = NetworkPort::open(&path, 115200, time::Duration::from_millis(50))
+            .await
+            .unwrap();
+        let (mut device, _) = listener.accept().unwrap();
+
+        AsyncWriteExt::write_all(&mut port, &[IAC]).await.unwrap();
+        // The 3 negotiations, the 5 settings, then the escaped data.
+        let mut received = vec![0; 9 + 10 + 4 * 7 + 2];
+        device.read_exact(&mut received).unwrap();
+        assert_eq!(
+            &[
+                IAC,
+                SB,
+                COM_PORT_OPTION,
+                SET_BAUDRATE,
+                0x00,
+                0x01,
+                0xC2,
+                0x00,
+                IAC,
+                SE
+            ],
+            &received[9..19]
+        );
+        assert_eq!(&[IAC, IAC], &received[received.len() - 2..]);
+
+        device
+            .write_all(&[IAC, WILL, COM_PORT_OPTION, b'x'])
+            .unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(1, AsyncReadExt::read(&mut port, &mut buf).await.unwrap());
+        assert_eq!(b'x', buf[0]);
+    }
+}
@@ -0,0 +1,149 @@
+//! Best-effort lookup of the process holding a busy port.
+//!
+//! Two sources are tried, in order:
+//!
+//! - UUCP-style lock files (`/var/lock/LCK..ttyUSB0`, `/run/lock/...`), written by
+//!   tools such as `minicom` or `picocom`, containing the PID of their owner.
+//! - On Linux, the `/proc/<pid>/fd` links of every process we are allowed to inspect.
+//!   Processes of other users are usually hidden unless running as root.
+
+use std::{fs, path::Path};
+
+const LOCK_DIRS: [&str; 3] = ["/var/lock", "/run/lock", "/var/spool/lock"];
+
+/// A process found to hold a port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortOwner {
+    pub pid: u32,
+    /// The command name of the process, if it could be read.
+    pub name: Option<String>,
+}
+
+impl std::fmt::Display for PortOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "process {} ({})", self.pid, name),
+            None => write!(f, "process {}", self.pid),
+        }
+    }
+}
+
+/// Returns if opening a port failed because the device is busy.
+pub fn is_busy_error(e: &serialport::Error) -> bool {
+    e.kind() == serialport::ErrorKind::Unknown && e.description.to_lowercase().contains("busy")
+}
+
+/// Looks for the process holding `port_path`.
+///
+/// # Returns
+///
+/// The first owner found, if any. `None` does not mean the port is free, only that
+/// no owner could be identified.
+pub fn find_port_owner(port_path: &str) -> Option<PortOwner> {
+    find_lock_file_owner(port_path).or_else(|| find_fd_owner(port_path))
+}
+
+fn find_lock_file_owner(port_path: &str) -> Option<PortOwner> {
+    let device = Path::new(port_path).file_name()?.to_str()?;
+
+    LOCK_DIRS.iter().find_map(|dir| {
+        let contents = fs::read(Path::new(dir).join(format!("LCK..{}", device))).ok()?;
+        let pid = parse_lock_file(&contents)?;
+
+        // Stale lock files are left behind by crashed programs. Without `/proc`
+        // (for ex. on macOS) the lock file is trusted.
+        let proc_available = Path::new("/proc").exists();
+        if !proc_available || Path::new(&format!("/proc/{}", pid)).exists() {
+            Some(PortOwner {
+                pid,
+                name: process_name(pid),
+            })
+        } else {
+            None
+        }
+    })
+}
+
+/// Parses the PID of a lock file, stored either as ASCII (the usual format) or
+/// as a native 4-byte integer (the old binary format).
+fn parse_lock_file(contents: &[u8]) -> Option<u32> {
+    if let Ok(text) = std::str::from_utf8(contents) {
+        if let Ok(pid) = text.trim().parse() {
+            return Some(pid);
+        }
+    }
+
+    if contents.len() == 4 {
+        let pid = u32::from_ne_bytes([contents[0], contents[1], contents[2], contents[3]]);
+        if pid != 0 {
+            return Some(pid);
+        }
+    }
+
+    None
+}
+
+fn find_fd_owner(port_path: &str) -> Option<PortOwner> {
+    let port_path = fs::canonicalize(port_path).ok()?;
+
+    fs::read_dir("/proc").ok()?.flatten().find_map(|process| {
+        let pid: u32 = process.file_name().to_str()?.parse().ok()?;
+        let fds = fs::read_dir(process.path().join("fd")).ok()?;
+
+        fds.flatten()
+            .any(|fd| fs::read_link(fd.path()).is_ok_and(|target| target == port_path))
+            .then(|| PortOwner {
+                pid,
+                name: process_name(pid),
+            })
+    })
+}
+
+fn process_name(pid: u32) -> Option<String> {
+    let name = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(name.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ascii_and_binary_lock_files() {
+        assert_eq!(Some(1234), parse_lock_file(b"      1234\n"));
+        assert_eq!(Some(1234), parse_lock_file(&1234u32.to_ne_bytes()));
+        assert_eq!(None, parse_lock_file(b"garbage"));
+    }
+
+    #[test]
+    fn busy_error_detection() {
+        let busy =
+            serialport::Error::new(serialport::ErrorKind::Unknown, "Device or resource busy");
+        let not_found = serialport::Error::new(
+            serialport::ErrorKind::Io(std::io::ErrorKind::NotFound),
+            "No such file or directory",
+        );
+
+        assert!(is_busy_error(&busy));
+        assert!(!is_busy_error(&not_found));
+    }
+
+    #[test]
+    fn owner_display() {
+        let owner = PortOwner {
+            pid: 42,
+            name: Some("minicom".to_string()),
+        };
+        assert_eq!("process 42 (minicom)", owner.to_string());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn finds_this_process_holding_a_pty() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+
+        let owner = find_port_owner(&slave_path).unwrap();
+        assert_eq!(std::process::id(), owner.pid);
+    }
+}
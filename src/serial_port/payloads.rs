@@ -0,0 +1,100 @@
+//! The last payloads written to and read from the port, for a "replay the last
+//! command" or a quick "what did I just send/get", lighter than a byte trace.
+//!
+//! Every write and read of the port is kept: a chunk of a chunked send, the coalesced
+//! writes at once or a single acked byte being a write, and the last read of a
+//! `read_lines()` or the bytes read ahead returned by `read_once()` being a read.
+
+use std::time;
+
+use super::SerialPort;
+
+/// Number of bytes kept of a payload, the following ones being dropped.
+pub const LAST_PAYLOAD_MAX_LEN: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    /// At most `LAST_PAYLOAD_MAX_LEN` bytes.
+    pub bytes: Vec<u8>,
+    /// If bytes have been dropped.
+    pub truncated: bool,
+    /// When the payload crossed the port.
+    pub at: time::SystemTime,
+}
+
+/// Replaces `last` by `bytes`, reusing its allocation.
+pub(crate) fn record(last: &mut Option<Payload>, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+
+    let len = bytes.len().min(LAST_PAYLOAD_MAX_LEN);
+    let payload = last.get_or_insert_with(|| Payload {
+        bytes: Vec::with_capacity(len),
+        truncated: false,
+        at: time::SystemTime::now(),
+    });
+
+    payload.bytes.clear();
+    payload.bytes.extend_from_slice(&bytes[..len]);
+    payload.truncated = len < bytes.len();
+    payload.at = time::SystemTime::now();
+}
+
+impl SerialPort {
+    /// Returns the last payload successfully written, see `payloads`.
+    ///
+    /// Cleared when a port is opened.
+    pub fn last_sent(&self) -> Option<&Payload> {
+        self.last_sent.as_ref()
+    }
+
+    /// Returns the last non-empty payload successfully read, see `payloads`.
+    ///
+    /// Cleared when a port is opened.
+    pub fn last_received(&self) -> Option<&Payload> {
+        self.last_received.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_truncates() {
+        let mut last = None;
+        record(&mut last, b"");
+        assert_eq!(None, last);
+
+        record(&mut last, &[1; LAST_PAYLOAD_MAX_LEN + 1]);
+        let payload = last.as_ref().unwrap();
+        assert_eq!(LAST_PAYLOAD_MAX_LEN, payload.bytes.len());
+        assert!(payload.truncated);
+
+        record(&mut last, b"ok");
+        assert_eq!(b"ok".to_vec(), last.as_ref().unwrap().bytes);
+        assert!(!last.unwrap().truncated);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn last_sent_and_received() {
+        use std::io::{Read, Write};
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        assert!(port.last_sent().is_none());
+
+        assert!(port.send_once("one").await.success);
+        assert!(port.send_once("two").await.success);
+        master.read_exact(&mut [0; 6]).unwrap();
+        master.write_all(b"reply").unwrap();
+        assert!(port.read_once().await.success);
+
+        assert_eq!(b"two".to_vec(), port.last_sent().unwrap().bytes);
+        assert_eq!(b"reply".to_vec(), port.last_received().unwrap().bytes);
+    }
+}
@@ -0,0 +1,163 @@
+//! Reopening of a port whose device is gone, for the USB-serial adapters
+//! disconnecting on a cable jostle.
+
+use std::time;
+
+use super::config::SerialConfig;
+use super::SerialPort;
+
+/// How a disconnected port is reopened, see `SerialPort::enable_auto_reconnect()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoReconnect {
+    /// Number of attempts to reopen the port.
+    pub retries: u32,
+    /// Wait before the first attempt, doubled before each next one.
+    pub backoff: time::Duration,
+}
+
+/// What the open port has been opened with, for it to be reopened as is.
+#[derive(Debug, Clone)]
+pub(crate) struct LastOpen {
+    pub(crate) port_path: String,
+    pub(crate) config: SerialConfig,
+    pub(crate) post_open_delay: time::Duration,
+}
+
+impl SerialPort {
+    /// Reopens the port when a read or a write tells that its device is gone (see
+    /// `events::is_disconnect_error()`), with the same path and settings, up to
+    /// `retries` times.
+    ///
+    /// The failing read or write still returns its error, once the port has been
+    /// reopened or the attempts exhausted: the bytes it sent or read are unknown.
+    /// The caller waits meanwhile (in the server, the lock of the port is held).
+    /// The `Disconnected` event is followed by an `Opened` one on success.
+    ///
+    /// # Paramters
+    ///
+    /// - `retries`: The number of attempts. Zero never reopens.
+    /// - `backoff`: The wait before the first attempt, doubled before each next one.
+    pub fn enable_auto_reconnect(&mut self, retries: u32, backoff: time::Duration) {
+        self.auto_reconnect = Some(AutoReconnect { retries, backoff });
+    }
+
+    /// Leaves the disconnected ports closed, the default.
+    pub fn disable_auto_reconnect(&mut self) {
+        self.auto_reconnect = None;
+    }
+
+    pub fn auto_reconnect(&self) -> Option<AutoReconnect> {
+        self.auto_reconnect
+    }
+
+    /// Reopens the port last opened if the auto reconnect is enabled.
+    ///
+    /// # Returns
+    ///
+    /// If the port has been reopened.
+    pub(crate) async fn reconnect(&mut self) -> bool {
+        let (reconnect, last_open) = match (self.auto_reconnect, self.last_open.clone()) {
+            (Some(reconnect), Some(last_open)) => (reconnect, last_open),
+            _ => return false,
+        };
+
+        let mut backoff = reconnect.backoff;
+        for _ in 0..reconnect.retries {
+            tokio::time::sleep(backoff).await;
+            backoff = backoff.checked_mul(2).unwrap_or(backoff);
+
+            let reopened = self
+                .try_open_configured(
+                    &last_open.port_path,
+                    &last_open.config,
+                    last_open.post_open_delay,
+                )
+                .await;
+            if reopened.is_ok() {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::serial_port::backend::pty::{Masters, PtyBackend};
+    use crate::serial_port::events::PortEventKind;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    /// A pty port, failing the opens told to fail.
+    async fn flaky_port() -> (SerialPort, Masters, Arc<Mutex<u32>>) {
+        let backend = PtyBackend::default();
+        let masters = Arc::clone(&backend.masters);
+        let failures = Arc::clone(&backend.failures);
+        let mut port = SerialPort::with_backend(Box::new(backend));
+        assert!(port.open_port("pty://device", 9600).await.success);
+
+        (port, masters, failures)
+    }
+
+    #[tokio::test]
+    async fn reconnect_after_disconnect() {
+        let (mut port, masters, failures) = flaky_port().await;
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorded = Arc::clone(&events);
+        port.on_event(Box::new(move |event| {
+            recorded.lock().unwrap().push(event.kind)
+        }));
+        port.enable_auto_reconnect(3, time::Duration::from_millis(5));
+
+        // Unplugged, then back after a failed attempt.
+        *failures.lock().unwrap() = 1;
+        masters.lock().unwrap().clear();
+        assert!(!port.read_once().await.success);
+        assert!(port.is_open());
+        assert_eq!(0, *failures.lock().unwrap());
+        assert_eq!(
+            vec![PortEventKind::Disconnected, PortEventKind::Opened],
+            *events.lock().unwrap()
+        );
+
+        masters.lock().unwrap()[0].write_all(b"back").unwrap();
+        assert_eq!("back", port.read_once().await.content);
+    }
+
+    #[tokio::test]
+    async fn reconnect_while_reading_lines() {
+        let (mut port, masters, _failures) = flaky_port().await;
+        port.enable_auto_reconnect(1, time::Duration::from_millis(5));
+
+        masters.lock().unwrap().clear();
+        let resp = port.read_lines(1, time::Duration::from_millis(100)).await;
+        assert!(!resp.success);
+        assert!(port.is_open());
+
+        masters.lock().unwrap()[0].write_all(b"back\n").unwrap();
+        let resp = port.read_lines(1, time::Duration::from_millis(100)).await;
+        assert_eq!(vec!["back".to_string()], resp.lines);
+    }
+
+    #[tokio::test]
+    async fn retries_exhausted() {
+        let (mut port, masters, failures) = flaky_port().await;
+        port.enable_auto_reconnect(2, time::Duration::from_millis(5));
+
+        *failures.lock().unwrap() = 5;
+        masters.lock().unwrap().clear();
+        assert!(!port.send_once("ok").await.success);
+        assert!(!port.is_open());
+        assert_eq!(3, *failures.lock().unwrap());
+
+        // Not reopened when disabled.
+        *failures.lock().unwrap() = 0;
+        assert!(port.open_port("pty://device", 9600).await.success);
+        port.disable_auto_reconnect();
+        masters.lock().unwrap().clear();
+        assert!(!port.read_once().await.success);
+        assert!(!port.is_open());
+    }
+}
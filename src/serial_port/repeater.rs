@@ -0,0 +1,234 @@
+//! Loopback repeater: everything read from the port is written back to it.
+//!
+//! The repeater runs in a background task on a clone of the port handle, until
+//! it is disabled or the port is closed. If the remote end also echoes, both ends
+//! keep bouncing the same bytes forever: the byte rate is logged so this is easy
+//! to spot, and the repeater can be disabled at any time. A serial error stops the
+//! repeater, the error being logged through `tracing`.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::{io, time};
+
+use tokio::io::AsyncReadExt;
+
+use super::backend::SerialBackend;
+use super::rs485::{self, Rs485Mode};
+use super::{within, SerialPort, SerialPortResponse};
+
+const REPEATER_BUFFER_SIZE: usize = 256;
+/// Read timeout of the repeater handle, bounding how long disabling it takes.
+const REPEATER_READ_TIMEOUT_MS: u64 = 50;
+const REPEATER_RATE_LOG_PERIOD_MS: u64 = 1000;
+
+/// Handle on a running repeater task, stopping it when dropped.
+pub(super) struct Repeater {
+    running: Arc<AtomicBool>,
+    repeated: Arc<AtomicU64>,
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Repeater {
+    /// Starts repeating on `port`, in the RS-485 mode `rs485` if any.
+    fn start(mut port: Box<dyn SerialBackend>, rs485: Option<Rs485Mode>) -> io::Result<Repeater> {
+        port.set_timeout(time::Duration::from_millis(REPEATER_READ_TIMEOUT_MS))?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let repeated = Arc::new(AtomicU64::new(0));
+
+        let task = {
+            let running = Arc::clone(&running);
+            let repeated = Arc::clone(&repeated);
+            tokio::spawn(async move { repeat(port.as_mut(), rs485, &running, &repeated).await })
+        };
+
+        Ok(Repeater {
+            running,
+            repeated,
+            task: Some(task),
+        })
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// Stops the task, returning the total number of repeated bytes.
+    async fn stop(&mut self) -> u64 {
+        self.running.store(false, Ordering::Relaxed);
+
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+
+        self.repeated.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Repeater {
+    /// Cancels the task, which can not be waited for here.
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+async fn repeat(
+    port: &mut dyn SerialBackend,
+    rs485: Option<Rs485Mode>,
+    running: &AtomicBool,
+    repeated: &AtomicU64,
+) {
+    let mut serial_buf = [0; REPEATER_BUFFER_SIZE];
+    let rate_period = time::Duration::from_millis(REPEATER_RATE_LOG_PERIOD_MS);
+    let mut period_start = time::Instant::now();
+    let mut period_bytes = 0;
+
+    while running.load(Ordering::Relaxed) {
+        let timeout = port.timeout();
+        match within(timeout, port.read(&mut serial_buf)).await {
+            Ok(t) => {
+                let (_, written) =
+                    rs485::write_half_duplex(port, rs485, &serial_buf[..t], timeout).await;
+                if let Err(e) = written {
+                    tracing::error!("Repeater stopped, serial write error: {}", e);
+                    break;
+                }
+                repeated.fetch_add(t as u64, Ordering::Relaxed);
+                period_bytes += t;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {}
+            Err(e) => {
+                tracing::error!("Repeater stopped, serial read error: {}", e);
+                break;
+            }
+        }
+
+        let elapsed = period_start.elapsed();
+        if elapsed >= rate_period {
+            if period_bytes > 0 {
+                tracing::info!(
+                    "Repeater: {:.0} bytes/s",
+                    period_bytes as f64 / elapsed.as_secs_f64()
+                );
+            }
+            period_start = time::Instant::now();
+            period_bytes = 0;
+        }
+    }
+
+    running.store(false, Ordering::Relaxed);
+}
+
+impl SerialPort {
+    /// Enables or disables the repeater mode on the open port.
+    ///
+    /// While enabled, the repeater competes with `read_once` for the incoming bytes.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message, with the number of repeated bytes when disabling.
+    /// - `success`: if the repeater is in the requested state.
+    pub async fn set_repeater(&mut self, enabled: bool) -> SerialPortResponse {
+        let port = match self.backend.if_open() {
+            Some(port) => port,
+            None => {
+                return SerialPortResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                }
+            }
+        };
+
+        if !enabled {
+            return match self.repeater.take() {
+                Some(mut repeater) => SerialPortResponse {
+                    success: true,
+                    content: format!("Repeater disabled after {} bytes", repeater.stop().await),
+                },
+                None => SerialPortResponse {
+                    success: true,
+                    content: "Repeater already disabled".to_string(),
+                },
+            };
+        }
+
+        if self.is_repeating() {
+            return SerialPortResponse {
+                success: true,
+                content: "Repeater already enabled".to_string(),
+            };
+        }
+
+        let rs485 = self.rs485;
+        let repeater = port
+            .try_clone()
+            .map_err(io::Error::from)
+            .and_then(|port| Repeater::start(port, rs485));
+
+        match repeater {
+            Ok(repeater) => {
+                self.repeater = Some(repeater);
+
+                SerialPortResponse {
+                    success: true,
+                    content: "Repeater enabled".to_string(),
+                }
+            }
+            Err(e) => SerialPortResponse {
+                success: false,
+                content: format!("Could not start the repeater: {}", e),
+            },
+        }
+    }
+
+    /// Returns if the repeater is currently running.
+    ///
+    /// It can stop by itself on a serial error.
+    pub fn is_repeating(&self) -> bool {
+        match &self.repeater {
+            Some(repeater) => repeater.is_running(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn set_repeater_without_port() {
+        let mut port = SerialPort::new();
+        assert!(!port.set_repeater(true).await.success);
+        assert!(!port.is_repeating());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn repeats_until_disabled() {
+        use std::io::{Read, Write};
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        assert!(port.set_repeater(true).await.success);
+        assert!(port.is_repeating());
+
+        master.write_all(b"abc").unwrap();
+        let mut echoed = [0; 3];
+        master.read_exact(&mut echoed).unwrap();
+        assert_eq!(b"abc", &echoed);
+
+        let resp = port.set_repeater(false).await;
+        assert!(resp.success);
+        assert_eq!("Repeater disabled after 3 bytes", resp.content);
+        assert!(!port.is_repeating());
+    }
+}
@@ -0,0 +1,440 @@
+//! RS-485 half-duplex mode, for the transceivers whose driver is enabled by RTS:
+//! RTS is asserted while sending, and deasserted to receive.
+
+use std::{io, time};
+
+use super::backend::SerialBackend;
+use super::{write_all_to, SerialPort, SerialPortResponse};
+
+/// The RTS direction control of `SerialPort::set_rs485_mode()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rs485Mode {
+    /// If RTS is high while sending, low otherwise.
+    pub rts_active_high: bool,
+    /// Wait between the end of a write and the deassertion of RTS.
+    pub turnaround: time::Duration,
+}
+
+impl Rs485Mode {
+    /// Sets RTS to its level while sending, or while receiving.
+    fn set_rts(self, port: &mut dyn SerialBackend, sending: bool) -> io::Result<()> {
+        port.write_request_to_send(sending == self.rts_active_high)
+            .map_err(|e| {
+                let kind = match e.kind() {
+                    serialport::ErrorKind::Io(kind) => kind,
+                    _ => io::ErrorKind::Other,
+                };
+                let direction = if sending { "sending" } else { "receiving" };
+                io::Error::new(kind, format!("Could not set RTS for {}: {}", direction, e))
+            })
+    }
+}
+
+/// Writes the whole of `output` as `write_all_to()` does, RTS being asserted
+/// before and deasserted after as `mode` tells, once the bytes sent and the
+/// turnaround elapsed. Writes as is without mode.
+///
+/// RTS is deasserted even if the write failed, for the bus to be released.
+///
+/// # Returns
+///
+/// The number of bytes written, also on failure, and the error if any.
+pub(crate) async fn write_half_duplex(
+    port: &mut dyn SerialBackend,
+    mode: Option<Rs485Mode>,
+    output: &[u8],
+    timeout: time::Duration,
+) -> (usize, io::Result<()>) {
+    let mode = match mode {
+        Some(mode) => mode,
+        None => return write_all_to(port, output, timeout).await,
+    };
+
+    if let Err(e) = mode.set_rts(port, true) {
+        return (0, Err(e));
+    }
+
+    // The write flushes, waiting for the bytes to be sent.
+    let (written, result) = write_all_to(port, output, timeout).await;
+    tokio::time::sleep(mode.turnaround).await;
+    let released = mode.set_rts(port, false);
+
+    (written, result.and(released))
+}
+
+impl SerialPort {
+    /// Enables or disables the RS-485 half-duplex mode.
+    ///
+    /// When enabled, each write asserts RTS, writes and flushes the bytes, waits
+    /// for `turnaround`, then deasserts RTS before returning, for the next read to
+    /// get the answer. RTS is deasserted right away if a port is open, and on open.
+    /// Kept across port changes.
+    ///
+    /// # Paramters
+    ///
+    /// - `enabled`: If the RTS direction control is done.
+    /// - `rts_active_high`: If RTS is high while sending, the usual wiring. Low otherwise.
+    /// - `turnaround`: The wait after a write, before deasserting RTS, for the
+    ///   transceivers switching late.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the mode has been set, and RTS deasserted if a port is open.
+    pub fn set_rs485_mode(
+        &mut self,
+        enabled: bool,
+        rts_active_high: bool,
+        turnaround: time::Duration,
+    ) -> SerialPortResponse {
+        if !enabled {
+            self.rs485 = None;
+            return SerialPortResponse {
+                success: true,
+                content: "RS-485 mode disabled".to_string(),
+            };
+        }
+
+        let mode = Rs485Mode {
+            rts_active_high,
+            turnaround,
+        };
+        self.rs485 = Some(mode);
+
+        if let Some(port) = self.backend.if_open_mut() {
+            if let Err(e) = mode.set_rts(port, false) {
+                return SerialPortResponse {
+                    success: false,
+                    content: e.to_string(),
+                };
+            }
+        }
+
+        SerialPortResponse {
+            success: true,
+            content: format!(
+                "RS-485 mode enabled, RTS {} while sending, with a turnaround of {}us",
+                if rts_active_high { "high" } else { "low" },
+                turnaround.as_micros()
+            ),
+        }
+    }
+
+    pub fn rs485_mode(&self) -> Option<Rs485Mode> {
+        self.rs485
+    }
+
+    /// Deasserts RTS on open, the port being ready to receive.
+    pub(crate) fn release_rs485_bus(&mut self) {
+        if let (Some(mode), Some(port)) = (self.rs485, self.backend.if_open_mut()) {
+            // A port without RTS fails its writes, telling why.
+            let _ = mode.set_rts(port, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial_port::backend::OpenOptions;
+    use async_trait::async_trait;
+    use serialport::{ClearBuffer, DataBits, FlowControl, Parity, StopBits};
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    type Changes = Arc<Mutex<Vec<(String, time::Instant)>>>;
+
+    /// Records the RTS changes, the writes, the flushes and the reads, with their
+    /// time. Answers each write with `reply`.
+    struct RecordingPort {
+        changes: Changes,
+        reply: Vec<u8>,
+        pending: Vec<u8>,
+        open: bool,
+    }
+
+    impl RecordingPort {
+        fn record(&self, change: String) {
+            self.changes
+                .lock()
+                .unwrap()
+                .push((change, time::Instant::now()));
+        }
+    }
+
+    impl AsyncRead for RecordingPort {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if self.pending.is_empty() {
+                return Poll::Ready(Err(io::ErrorKind::TimedOut.into()));
+            }
+
+            self.record("read".to_string());
+            let t = self.pending.len().min(buf.remaining());
+            buf.put_slice(&self.pending[..t]);
+            self.pending.drain(..t);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncWrite for RecordingPort {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.record(format!("write {}", String::from_utf8_lossy(buf)));
+            // The unread answer to the previous write is dropped.
+            self.pending = self.reply.clone();
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.record("flush".to_string());
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[async_trait]
+    impl SerialBackend for RecordingPort {
+        async fn open(
+            &mut self,
+            _port_path: &str,
+            _options: &OpenOptions,
+        ) -> serialport::Result<()> {
+            self.open = true;
+            Ok(())
+        }
+        fn close(&mut self) {
+            self.open = false;
+        }
+        fn is_open(&self) -> bool {
+            self.open
+        }
+        fn unopened(&self) -> Box<dyn SerialBackend> {
+            Box::new(RecordingPort {
+                changes: Arc::clone(&self.changes),
+                reply: self.reply.clone(),
+                pending: vec![],
+                open: false,
+            })
+        }
+        fn try_clone(&self) -> serialport::Result<Box<dyn SerialBackend>> {
+            Ok(Box::new(RecordingPort {
+                changes: Arc::clone(&self.changes),
+                reply: self.reply.clone(),
+                pending: vec![],
+                open: true,
+            }))
+        }
+        fn name(&self) -> Option<String> {
+            Some("recording".to_string())
+        }
+        fn baud_rate(&self) -> serialport::Result<u32> {
+            Ok(9600)
+        }
+        fn data_bits(&self) -> serialport::Result<DataBits> {
+            Ok(DataBits::Eight)
+        }
+        fn flow_control(&self) -> serialport::Result<FlowControl> {
+            Ok(FlowControl::None)
+        }
+        fn parity(&self) -> serialport::Result<Parity> {
+            Ok(Parity::None)
+        }
+        fn stop_bits(&self) -> serialport::Result<StopBits> {
+            Ok(StopBits::One)
+        }
+        fn timeout(&self) -> time::Duration {
+            time::Duration::from_millis(10)
+        }
+        fn set_baud_rate(&mut self, _baud_rate: u32) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn set_data_bits(&mut self, _data_bits: DataBits) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn set_flow_control(&mut self, _flow_control: FlowControl) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn set_parity(&mut self, _parity: Parity) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn set_stop_bits(&mut self, _stop_bits: StopBits) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn set_timeout(&mut self, _timeout: time::Duration) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+            self.record(format!("RTS={}", level as u8));
+            Ok(())
+        }
+        fn write_data_terminal_ready(&mut self, _level: bool) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+            Ok(false)
+        }
+        fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+            Ok(false)
+        }
+        fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+            Ok(false)
+        }
+        fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+            Ok(false)
+        }
+        fn bytes_to_read(&self) -> serialport::Result<u32> {
+            Ok(0)
+        }
+        fn clear(&self, _buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn recording_port() -> (SerialPort, Changes) {
+        recording_port_replying(vec![])
+    }
+
+    /// A recording port answering each write with `reply`.
+    fn recording_port_replying(reply: Vec<u8>) -> (SerialPort, Changes) {
+        let changes = Arc::new(Mutex::new(vec![]));
+        let port = SerialPort::with_backend(Box::new(RecordingPort {
+            changes: Arc::clone(&changes),
+            reply,
+            pending: vec![],
+            open: false,
+        }));
+
+        (port, changes)
+    }
+
+    fn names(changes: &Changes) -> Vec<String> {
+        let names = changes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+        changes.lock().unwrap().clear();
+        names
+    }
+
+    #[tokio::test]
+    async fn rts_toggled_around_a_send() {
+        let (mut port, changes) = recording_port();
+        let turnaround = time::Duration::from_millis(20);
+        assert!(port.set_rs485_mode(true, true, turnaround).success);
+        assert!(port.open_port("recording://", 9600).await.success);
+        assert_eq!(vec!["RTS=0"], names(&changes));
+
+        let resp = port.send_once("ping").await;
+        assert!(resp.success, "{}", resp.content);
+        {
+            let changes = changes.lock().unwrap();
+            let (flush, flushed) = &changes[2];
+            let (release, released) = &changes[3];
+            assert_eq!(("flush", "RTS=0"), (flush.as_str(), release.as_str()));
+            assert!(*released - *flushed >= turnaround);
+        }
+        assert_eq!(
+            vec!["RTS=1", "write ping", "flush", "RTS=0"],
+            names(&changes)
+        );
+
+        // Active low.
+        let resp = port.set_rs485_mode(true, false, time::Duration::from_millis(0));
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!(
+            "RS-485 mode enabled, RTS low while sending, with a turnaround of 0us",
+            resp.content
+        );
+        assert!(port.send_once("ok").await.success);
+        assert_eq!(
+            vec!["RTS=1", "RTS=0", "write ok", "flush", "RTS=1"],
+            names(&changes)
+        );
+
+        // Disabled, RTS left alone.
+        assert!(port.set_rs485_mode(false, true, turnaround).success);
+        assert_eq!(None, port.rs485_mode());
+        assert!(port.send_once("raw").await.success);
+        assert_eq!(vec!["write raw", "flush"], names(&changes));
+    }
+
+    /// The names of the changes, without the written bytes.
+    fn kinds(changes: &Changes) -> Vec<String> {
+        names(changes)
+            .iter()
+            .map(|name| name.split(' ').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn rts_toggled_around_every_write() {
+        use crate::serial_port::modbus::{crc16_modbus, ModbusStatus};
+
+        let mut reply = vec![0x01, 0x03, 0x02, 0x00, 0x2A];
+        reply.extend_from_slice(&crc16_modbus(&reply).to_le_bytes());
+        let (mut port, changes) = recording_port_replying(reply.clone());
+        assert!(
+            port.set_rs485_mode(true, true, time::Duration::from_millis(0))
+                .success
+        );
+        assert!(port.open_port("recording://", 9600).await.success);
+        names(&changes);
+
+        // The bus is released before the response is read.
+        let resp = port
+            .send_modbus_rtu(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01])
+            .await;
+        assert_eq!(ModbusStatus::Ok, resp.status, "{}", resp.content);
+        assert_eq!(
+            vec!["RTS=1", "write", "flush", "RTS=0", "read"],
+            kinds(&changes)
+        );
+
+        // Each acked byte, the ack being the first byte of the reply.
+        let resp = port
+            .send_with_acks(b"ab", 0x01, time::Duration::from_millis(0))
+            .await;
+        assert!(resp.success, "{}", resp.content);
+        let byte = ["RTS=1", "write", "flush", "RTS=0", "read"];
+        assert_eq!([&byte[..], &byte[..]].concat(), kinds(&changes));
+
+        // The writer half keeps the mode.
+        let (_, mut writer) = port.try_split().unwrap();
+        assert!(writer.send_once("ok").await.success);
+        assert_eq!(vec!["RTS=1", "write ok", "flush", "RTS=0"], names(&changes));
+    }
+
+    #[tokio::test]
+    async fn rs485_without_rts() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let path = format!("tcp://{}", listener.local_addr().unwrap());
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&path, 9600).await.success);
+
+        // A raw TCP port has no RTS.
+        let resp = port.set_rs485_mode(true, true, time::Duration::from_millis(0));
+        assert!(!resp.success);
+        assert!(
+            resp.content
+                .starts_with("Could not set RTS for receiving: "),
+            "{}",
+            resp.content
+        );
+        assert!(!port.send_once("ping").await.success);
+    }
+}
@@ -0,0 +1,233 @@
+//! Reads of fixed-size integer samples sent back-to-back, as streamed by ADCs and
+//! other telemetry devices.
+
+use std::{io, time};
+
+use super::SerialPort;
+
+/// How long `read_samples` waits when no timeout is given.
+pub const DEFAULT_READ_SAMPLES_TIMEOUT_MS: u64 = 1000;
+
+const READ_SAMPLES_BUFFER_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+}
+
+impl SampleType {
+    /// Returns the size of a sample, in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            SampleType::U8 | SampleType::I8 => 1,
+            SampleType::U16 | SampleType::I16 => 2,
+            SampleType::U32 | SampleType::I32 => 4,
+        }
+    }
+
+    /// Decodes a sample of `size()` bytes.
+    fn decode(self, bytes: &[u8], endianness: Endianness) -> i64 {
+        let mut raw = [0; 4];
+        match endianness {
+            Endianness::Little => raw[..bytes.len()].copy_from_slice(bytes),
+            Endianness::Big => {
+                for (i, byte) in bytes.iter().rev().enumerate() {
+                    raw[i] = *byte;
+                }
+            }
+        }
+        let value = u32::from_le_bytes(raw);
+
+        match self {
+            SampleType::U8 => value as u8 as i64,
+            SampleType::I8 => value as u8 as i8 as i64,
+            SampleType::U16 => value as u16 as i64,
+            SampleType::I16 => value as u16 as i16 as i64,
+            SampleType::U32 => value as i64,
+            SampleType::I32 => value as i32 as i64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// Represents a response given by `SerialPort::read_samples`.
+#[derive(Debug)]
+pub struct ReadSamplesResponse {
+    pub success: bool,
+    /// Informative message.
+    pub content: String,
+    /// The samples read, wide enough for every `SampleType`.
+    pub samples: Vec<i64>,
+    /// If the start of a sample remains buffered for the next call.
+    pub partial_sample: bool,
+}
+
+impl SerialPort {
+    /// Reads `count` samples, or the samples received until `timeout`.
+    ///
+    /// Bytes following the last complete sample, as well as complete samples above
+    /// `count`, are kept for the next read, for a sample split by the read boundary
+    /// to be decoded by the next call.
+    ///
+    /// # Paramters
+    ///
+    /// - `sample_type`: The type of the samples.
+    /// - `endianness`: The byte order of the samples.
+    /// - `count`: The number of samples to read. Zero means every sample received
+    ///   until the timeout.
+    /// - `timeout`: How long to wait for the samples. Zero means `DEFAULT_READ_SAMPLES_TIMEOUT_MS`.
+    ///
+    /// # Returns
+    ///
+    /// A `ReadSamplesResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if no error happened. Timing out is not an error, fewer samples
+    ///   being returned.
+    /// - `samples`: the samples read.
+    /// - `partial_sample`: if an incomplete sample remains buffered.
+    pub async fn read_samples(
+        &mut self,
+        sample_type: SampleType,
+        endianness: Endianness,
+        count: usize,
+        timeout: time::Duration,
+    ) -> ReadSamplesResponse {
+        if !self.backend.is_open() {
+            return ReadSamplesResponse {
+                success: false,
+                content: "No port is currently open".to_string(),
+                samples: vec![],
+                partial_sample: false,
+            };
+        }
+
+        let timeout = if timeout.as_nanos() == 0 {
+            time::Duration::from_millis(DEFAULT_READ_SAMPLES_TIMEOUT_MS)
+        } else {
+            timeout
+        };
+
+        let size = sample_type.size();
+        let deadline = time::Instant::now() + timeout;
+        let mut serial_buf = [0; READ_SAMPLES_BUFFER_SIZE];
+        let mut error = None;
+
+        while count == 0 || self.read_buffer.len() < count * size {
+            let now = time::Instant::now();
+            if now >= deadline {
+                break;
+            }
+
+            match self.read_port_within(&mut serial_buf, deadline - now).await {
+                Ok(t) => self.read_buffer.extend_from_slice(&serial_buf[..t]),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => break,
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let mut available = self.read_buffer.len() / size;
+        if count != 0 {
+            available = available.min(count);
+        }
+        let samples: Vec<i64> = self
+            .read_buffer
+            .drain(..available * size)
+            .collect::<Vec<u8>>()
+            .chunks(size)
+            .map(|sample| sample_type.decode(sample, endianness))
+            .collect();
+        let partial_sample = !self.read_buffer.len().is_multiple_of(size);
+
+        match error {
+            None => ReadSamplesResponse {
+                success: true,
+                content: format!("Read {} samples", samples.len()),
+                samples,
+                partial_sample,
+            },
+            Some(e) => ReadSamplesResponse {
+                success: false,
+                content: format!("Serial read error: {}", e),
+                samples,
+                partial_sample,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_samples() {
+        assert_eq!(
+            -2,
+            SampleType::I16.decode(&[0xfe, 0xff], Endianness::Little)
+        );
+        assert_eq!(
+            0xfffe,
+            SampleType::U16.decode(&[0xff, 0xfe], Endianness::Big)
+        );
+        assert_eq!(-128, SampleType::I8.decode(&[0x80], Endianness::Big));
+        assert_eq!(
+            0x01020304,
+            SampleType::I32.decode(&[1, 2, 3, 4], Endianness::Big)
+        );
+        assert_eq!(
+            0xffffffff,
+            SampleType::U32.decode(&[0xff; 4], Endianness::Little)
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn partial_sample_kept_for_next_read() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        // -1, 2, then the first byte of 3.
+        master.write_all(&[0xff, 0xff, 0x02, 0x00, 0x03]).unwrap();
+        let resp = port
+            .read_samples(
+                SampleType::I16,
+                Endianness::Little,
+                0,
+                time::Duration::from_millis(50),
+            )
+            .await;
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!(vec![-1, 2], resp.samples);
+        assert!(resp.partial_sample);
+
+        master.write_all(&[0x00, 0x04, 0x00]).unwrap();
+        let resp = port
+            .read_samples(
+                SampleType::I16,
+                Endianness::Little,
+                1,
+                time::Duration::from_millis(50),
+            )
+            .await;
+        assert_eq!(vec![3], resp.samples);
+        assert!(!resp.partial_sample);
+        assert_eq!(vec![0x04, 0x00], port.read_buffer);
+    }
+}
@@ -0,0 +1,508 @@
+//! The settings of the open port as a single object, to read them all, change some,
+//! and apply them back at once, and the setters of the line settings one by one.
+
+use std::time;
+
+use super::backend::SerialBackend;
+use super::{config, SerialPort, SerialPortResponse};
+
+/// The settings of the open port, see `SerialPort::port_settings()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortSettings {
+    pub port_path: String,
+    pub baudrate: u32,
+    pub data_bits: serialport::DataBits,
+    pub parity: serialport::Parity,
+    pub stop_bits: serialport::StopBits,
+    pub flow_control: serialport::FlowControl,
+    pub read_timeout: time::Duration,
+    pub write_timeout: time::Duration,
+    /// See `SerialPort::set_exclusive()`.
+    pub exclusive: bool,
+    /// See `SerialPort::set_byte_escapes()`.
+    pub byte_escapes: bool,
+}
+
+/// A setting changed by `SerialPort::apply_port_settings()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingChange {
+    pub field: &'static str,
+    pub from: String,
+    pub to: String,
+}
+
+/// The line settings to change with `SerialPort::set_config()`, `None` for the ones
+/// to leave as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConfigChange {
+    pub baudrate: Option<u32>,
+    pub data_bits: Option<serialport::DataBits>,
+    pub parity: Option<serialport::Parity>,
+    pub stop_bits: Option<serialport::StopBits>,
+    pub flow_control: Option<serialport::FlowControl>,
+}
+
+/// Represents a response given by `SerialPort::apply_port_settings`.
+#[derive(Debug)]
+pub struct SettingsResponse {
+    pub success: bool,
+    /// Informative message.
+    pub content: String,
+    /// The settings changed, in the order they have been applied.
+    pub changes: Vec<SettingChange>,
+}
+
+impl SerialPort {
+    /// Returns the settings of the open port, read from the port itself where it
+    /// can tell them.
+    pub fn port_settings(&self) -> Option<PortSettings> {
+        let port = self.backend.if_open()?;
+
+        Some(PortSettings {
+            port_path: port.name().unwrap_or_else(|| "default".to_string()),
+            baudrate: port.baud_rate().unwrap_or(0),
+            data_bits: port.data_bits().unwrap_or(serialport::DataBits::Eight),
+            parity: port.parity().unwrap_or(serialport::Parity::None),
+            stop_bits: port.stop_bits().unwrap_or(serialport::StopBits::One),
+            flow_control: port.flow_control().unwrap_or(serialport::FlowControl::None),
+            read_timeout: self.read_timeout,
+            write_timeout: self.write_timeout,
+            exclusive: self.is_exclusive(),
+            byte_escapes: self.byte_escapes,
+        })
+    }
+
+    /// Changes the baudrate of the open port, without reopening it: the bytes read
+    /// ahead and the rest of the session are kept.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the baudrate has been changed.
+    pub fn set_baud_rate(&mut self, baudrate: u32) -> SerialPortResponse {
+        if let Err(e) = config::SerialConfig::new(baudrate).validate() {
+            return SerialPortResponse {
+                success: false,
+                content: e,
+            };
+        }
+
+        let port = match self.backend.if_open_mut() {
+            Some(port) => port,
+            None => {
+                return SerialPortResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                }
+            }
+        };
+
+        match port.set_baud_rate(baudrate) {
+            Ok(_) => SerialPortResponse {
+                success: true,
+                content: format!("Baudrate set to {}", baudrate),
+            },
+            Err(e) => SerialPortResponse {
+                success: false,
+                content: format!("Could not change the baudrate: {}", e),
+            },
+        }
+    }
+
+    /// Changes the data bits of the open port, see `set_baud_rate()`.
+    pub fn set_data_bits(&mut self, data_bits: serialport::DataBits) -> SerialPortResponse {
+        self.set_line_setting("data bits", format!("{:?}", data_bits), |port| {
+            port.set_data_bits(data_bits)
+        })
+    }
+
+    /// Changes the parity of the open port, see `set_baud_rate()`.
+    pub fn set_parity(&mut self, parity: serialport::Parity) -> SerialPortResponse {
+        self.set_line_setting("parity", format!("{:?}", parity), |port| {
+            port.set_parity(parity)
+        })
+    }
+
+    /// Changes the stop bits of the open port, see `set_baud_rate()`.
+    pub fn set_stop_bits(&mut self, stop_bits: serialport::StopBits) -> SerialPortResponse {
+        self.set_line_setting("stop bits", format!("{:?}", stop_bits), |port| {
+            port.set_stop_bits(stop_bits)
+        })
+    }
+
+    /// Changes the flow control of the open port, see `set_baud_rate()`.
+    pub fn set_flow_control(
+        &mut self,
+        flow_control: serialport::FlowControl,
+    ) -> SerialPortResponse {
+        self.set_line_setting("flow control", format!("{:?}", flow_control), |port| {
+            port.set_flow_control(flow_control)
+        })
+    }
+
+    fn set_line_setting(
+        &mut self,
+        name: &str,
+        value: String,
+        set: impl FnOnce(&mut dyn SerialBackend) -> serialport::Result<()>,
+    ) -> SerialPortResponse {
+        let port = match self.backend.if_open_mut() {
+            Some(port) => port,
+            None => {
+                return SerialPortResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                }
+            }
+        };
+
+        match set(port) {
+            Ok(_) => SerialPortResponse {
+                success: true,
+                content: format!(
+                    "{}{} set to {}",
+                    name[..1].to_uppercase(),
+                    &name[1..],
+                    value
+                ),
+            },
+            Err(e) => SerialPortResponse {
+                success: false,
+                content: format!("Could not change the {}: {}", name, e),
+            },
+        }
+    }
+
+    /// Changes the line settings given by `change` on the open port, through
+    /// `set_baud_rate()` and the other setters, leaving the others as is.
+    ///
+    /// The resulting settings are validated as a whole before any change. A failing
+    /// change stops the others, the ones applied before it not being rolled back.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: the messages of the changes made, then of the failing one if any.
+    /// - `success`: if every change has been made.
+    pub fn set_config(&mut self, change: &ConfigChange) -> SerialPortResponse {
+        let current = match self.current_config() {
+            Some(current) => current,
+            None => {
+                return SerialPortResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                }
+            }
+        };
+
+        let config = config::SerialConfig {
+            baudrate: change.baudrate.unwrap_or(current.baudrate),
+            data_bits: change.data_bits.unwrap_or(current.data_bits),
+            parity: change.parity.unwrap_or(current.parity),
+            stop_bits: change.stop_bits.unwrap_or(current.stop_bits),
+            flow_control: change.flow_control.unwrap_or(current.flow_control),
+        };
+        if let Err(e) = config.validate() {
+            return SerialPortResponse {
+                success: false,
+                content: format!("Invalid port config: {}", e),
+            };
+        }
+
+        type Setter = Box<dyn FnOnce(&mut SerialPort) -> SerialPortResponse>;
+        let mut setters: Vec<Setter> = vec![];
+        if let Some(baudrate) = change.baudrate {
+            setters.push(Box::new(move |port| port.set_baud_rate(baudrate)));
+        }
+        if let Some(data_bits) = change.data_bits {
+            setters.push(Box::new(move |port| port.set_data_bits(data_bits)));
+        }
+        if let Some(parity) = change.parity {
+            setters.push(Box::new(move |port| port.set_parity(parity)));
+        }
+        if let Some(stop_bits) = change.stop_bits {
+            setters.push(Box::new(move |port| port.set_stop_bits(stop_bits)));
+        }
+        if let Some(flow_control) = change.flow_control {
+            setters.push(Box::new(move |port| port.set_flow_control(flow_control)));
+        }
+
+        if setters.is_empty() {
+            return SerialPortResponse {
+                success: true,
+                content: "Nothing to change".to_string(),
+            };
+        }
+
+        let mut contents = vec![];
+        for set in setters {
+            let resp = set(self);
+            contents.push(resp.content);
+            if !resp.success {
+                return SerialPortResponse {
+                    success: false,
+                    content: contents.join(", "),
+                };
+            }
+        }
+
+        SerialPortResponse {
+            success: true,
+            content: contents.join(", "),
+        }
+    }
+
+    /// Applies the settings differing from the ones of the open port.
+    ///
+    /// A new `port_path` switches to that port through `swap_port()`, keeping the
+    /// session, before the other settings are applied to it. The other settings
+    /// are applied to the open port as is.
+    ///
+    /// The exchanges of other callers sharing the port can not be interleaved, but
+    /// the changes are not rolled back on failure: the settings applied before the
+    /// failing one stay in effect, and are listed by the response.
+    ///
+    /// # Returns
+    ///
+    /// A `SettingsResponse` containing:
+    /// - `content`: informative message, naming the failing setting on failure.
+    /// - `success`: if every setting has been applied.
+    /// - `changes`: the settings changed.
+    pub async fn apply_port_settings(&mut self, settings: &PortSettings) -> SettingsResponse {
+        let current = match self.port_settings() {
+            Some(current) => current,
+            None => {
+                return SettingsResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                    changes: vec![],
+                }
+            }
+        };
+
+        let mut changes = vec![];
+        let result = self.apply_changes(&current, settings, &mut changes).await;
+
+        SettingsResponse {
+            success: result.is_ok(),
+            content: match result {
+                Ok(_) => format!("{} settings changed", changes.len()),
+                Err(e) => e,
+            },
+            changes,
+        }
+    }
+
+    async fn apply_changes(
+        &mut self,
+        current: &PortSettings,
+        settings: &PortSettings,
+        changes: &mut Vec<SettingChange>,
+    ) -> Result<(), String> {
+        let mut changed =
+            |field, from: String, to: String| changes.push(SettingChange { field, from, to });
+
+        if settings.port_path != current.port_path {
+            self.swap_port(&settings.port_path, settings.baudrate)
+                .await
+                .into_result()
+                .map_err(|e| format!("Could not change the port: {}", e))?;
+            changed(
+                "port_path",
+                current.port_path.clone(),
+                settings.port_path.clone(),
+            );
+        }
+
+        let port = self
+            .backend
+            .if_open_mut()
+            .ok_or_else(|| "No port is currently open".to_string())?;
+
+        if settings.baudrate != port.baud_rate().unwrap_or(current.baudrate) {
+            port.set_baud_rate(settings.baudrate)
+                .map_err(|e| format!("Could not change the baudrate: {}", e))?;
+            changed(
+                "baudrate",
+                current.baudrate.to_string(),
+                settings.baudrate.to_string(),
+            );
+        }
+        if settings.data_bits != current.data_bits {
+            port.set_data_bits(settings.data_bits)
+                .map_err(|e| format!("Could not change the data bits: {}", e))?;
+            changed(
+                "data_bits",
+                format!("{:?}", current.data_bits),
+                format!("{:?}", settings.data_bits),
+            );
+        }
+        if settings.parity != current.parity {
+            port.set_parity(settings.parity)
+                .map_err(|e| format!("Could not change the parity: {}", e))?;
+            changed(
+                "parity",
+                format!("{:?}", current.parity),
+                format!("{:?}", settings.parity),
+            );
+        }
+        if settings.stop_bits != current.stop_bits {
+            port.set_stop_bits(settings.stop_bits)
+                .map_err(|e| format!("Could not change the stop bits: {}", e))?;
+            changed(
+                "stop_bits",
+                format!("{:?}", current.stop_bits),
+                format!("{:?}", settings.stop_bits),
+            );
+        }
+        if settings.flow_control != current.flow_control {
+            port.set_flow_control(settings.flow_control)
+                .map_err(|e| format!("Could not change the flow control: {}", e))?;
+            changed(
+                "flow_control",
+                format!("{:?}", current.flow_control),
+                format!("{:?}", settings.flow_control),
+            );
+        }
+
+        if settings.read_timeout != current.read_timeout {
+            self.set_read_timeout(settings.read_timeout).into_result()?;
+            changed(
+                "read_timeout",
+                format!("{}ms", current.read_timeout.as_millis()),
+                format!("{}ms", settings.read_timeout.as_millis()),
+            );
+        }
+        if settings.write_timeout != current.write_timeout {
+            self.set_write_timeout(settings.write_timeout);
+            changed(
+                "write_timeout",
+                format!("{}ms", current.write_timeout.as_millis()),
+                format!("{}ms", settings.write_timeout.as_millis()),
+            );
+        }
+        if settings.exclusive != current.exclusive {
+            self.set_exclusive(settings.exclusive).into_result()?;
+            changed(
+                "exclusive",
+                current.exclusive.to_string(),
+                settings.exclusive.to_string(),
+            );
+        }
+        if settings.byte_escapes != current.byte_escapes {
+            self.set_byte_escapes(settings.byte_escapes);
+            changed(
+                "byte_escapes",
+                current.byte_escapes.to_string(),
+                settings.byte_escapes.to_string(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_baud_rate_without_port() {
+        let mut port = SerialPort::new();
+
+        let resp = port.set_baud_rate(115200);
+        assert!(!resp.success);
+        assert_eq!("No port is currently open", resp.content);
+        assert_eq!("The baudrate can not be 0", port.set_baud_rate(0).content);
+    }
+
+    #[test]
+    fn set_config_without_port() {
+        let mut port = SerialPort::new();
+
+        let change = ConfigChange {
+            parity: Some(serialport::Parity::Even),
+            ..ConfigChange::default()
+        };
+        assert_eq!(
+            "No port is currently open",
+            port.set_config(&change).content
+        );
+        assert_eq!(
+            "No port is currently open",
+            port.set_stop_bits(serialport::StopBits::Two).content
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn set_some_of_the_config() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        let resp = port.set_config(&ConfigChange::default());
+        assert!(resp.success);
+        assert_eq!("Nothing to change", resp.content);
+
+        // A pty keeps its baudrate and input flags.
+        let change = ConfigChange {
+            baudrate: Some(19200),
+            flow_control: Some(serialport::FlowControl::Software),
+            ..ConfigChange::default()
+        };
+        let resp = port.set_config(&change);
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!(
+            "Baudrate set to 19200, Flow control set to Software",
+            resp.content
+        );
+        let config = port.current_config().unwrap();
+        assert_eq!(19200, config.baudrate);
+        assert_eq!(serialport::FlowControl::Software, config.flow_control);
+
+        // Checked against the current settings before any change.
+        let change = ConfigChange {
+            baudrate: Some(9600),
+            data_bits: Some(serialport::DataBits::Five),
+            stop_bits: Some(serialport::StopBits::Two),
+            ..ConfigChange::default()
+        };
+        let resp = port.set_config(&change);
+        assert!(!resp.success);
+        assert_eq!(
+            "Invalid port config: 2 stop bits are not supported with 5 data bits",
+            resp.content
+        );
+        assert_eq!(19200, port.current_config().unwrap().baudrate);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn apply_the_diff() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.port_settings().is_none());
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        let mut settings = port.port_settings().unwrap();
+        assert_eq!(slave_path, settings.port_path);
+        // A pty keeps its input flags, unlike the control flags of the parity.
+        settings.flow_control = serialport::FlowControl::Software;
+        settings.read_timeout = time::Duration::from_millis(20);
+
+        let resp = port.apply_port_settings(&settings).await;
+        assert!(resp.success, "{}", resp.content);
+        let fields: Vec<&str> = resp.changes.iter().map(|change| change.field).collect();
+        assert_eq!(vec!["flow_control", "read_timeout"], fields);
+        assert_eq!("None", resp.changes[0].from);
+        assert_eq!("Software", resp.changes[0].to);
+        assert_eq!(settings, port.port_settings().unwrap());
+
+        // Nothing left to change.
+        assert!(port.apply_port_settings(&settings).await.changes.is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+//! Independent reader and writer halves of a `SerialPort`.
+//!
+//! The halves are backed by `SerialBackend::try_clone`, so a long read on
+//! the reader half doesn't block the writer half, each one being owned separately.
+//!
+//! # Platform support
+//!
+//! - Unix: the file descriptor is duplicated (`fcntl(F_DUPFD)`). Timeouts are stored
+//!   per handle, while line settings (baudrate, parity, ...) are shared by the device.
+//! - Windows: only the network ports can be split, their socket being duplicated.
+//!   The local ports, opened as overlapped pipes by `tokio-serial`, can not be cloned.
+//!
+//! Closing the `SerialPort` doesn't close the halves: the device stays open until
+//! every handle has been dropped.
+
+use super::backend::SerialBackend;
+use super::rs485::Rs485Mode;
+use super::{read_once_from, send_once_to, SerialPort, SerialPortResponse};
+
+/// Reading half of a split `SerialPort`.
+pub struct SerialPortReader {
+    port: Box<dyn SerialBackend>,
+}
+
+/// Writing half of a split `SerialPort`.
+pub struct SerialPortWriter {
+    port: Box<dyn SerialBackend>,
+    /// The RS-485 mode of the port when it has been split.
+    rs485: Option<Rs485Mode>,
+}
+
+impl SerialPort {
+    /// Splits the open port into a reader half and a writer half.
+    ///
+    /// The halves write and read the port handle directly: the codec pipeline,
+    /// the byte escapes, the write coalescing, the write timeout, the stats, the
+    /// byte trace and the traffic monitor of the `SerialPort` do not apply to them,
+    /// and the bytes read ahead by its delimited reads are not returned by the
+    /// reader half. The writer half keeps the RS-485 mode the port had.
+    ///
+    /// # Returns
+    ///
+    /// The two halves, or an informative message if no port is open or if the
+    /// platform could not clone the port handle.
+    pub fn try_split(&self) -> Result<(SerialPortReader, SerialPortWriter), String> {
+        let port = match self.backend.if_open() {
+            Some(port) => port,
+            None => return Err("No port is currently open".to_string()),
+        };
+
+        let reader = port
+            .try_clone()
+            .map_err(|e| format!("Could not clone the port: {}", e))?;
+        let writer = port
+            .try_clone()
+            .map_err(|e| format!("Could not clone the port: {}", e))?;
+
+        Ok((
+            SerialPortReader { port: reader },
+            SerialPortWriter {
+                port: writer,
+                rs485: self.rs485,
+            },
+        ))
+    }
+}
+
+impl SerialPortReader {
+    /// Same as `SerialPort::read_once`.
+    pub async fn read_once(&mut self) -> SerialPortResponse {
+        read_once_from(self.port.as_mut()).await
+    }
+}
+
+impl SerialPortWriter {
+    /// Same as `SerialPort::send_once`.
+    pub async fn send_once(&mut self, message: &str) -> SerialPortResponse {
+        send_once_to(self.port.as_mut(), self.rs485, message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_split_without_port() {
+        let port = SerialPort::new();
+        assert!(port.try_split().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_read_and_write() {
+        use std::io::{Read, Write};
+        use std::time;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        let (mut reader, mut writer) = port.try_split().unwrap();
+        let reading = tokio::spawn(async move {
+            let mut resp = reader.read_once().await;
+            // The port is opened with a short timeout, wait for the device.
+            while !resp.success {
+                resp = reader.read_once().await;
+            }
+            resp.content
+        });
+
+        assert!(writer.send_once("ok").await.success);
+        let mut sent = [0; 2];
+        master.read_exact(&mut sent).unwrap();
+        assert_eq!(b"ok", &sent);
+
+        tokio::time::sleep(time::Duration::from_millis(20)).await;
+        master.write_all(b"pong").unwrap();
+        assert_eq!("pong", reading.await.unwrap());
+    }
+}
@@ -0,0 +1,216 @@
+//! Traffic and error counters of a `SerialPort`.
+
+use std::{io, time};
+
+use super::SerialPort;
+
+/// Number of error messages kept in `PortStats::errors`.
+pub const ERROR_HISTORY_LEN: usize = 16;
+
+/// Counters of the traffic of the port, every write and read of it counted
+/// whatever the method, the bytes read ahead once, when they are read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Read failures, timeouts excluded.
+    pub read_errors: u64,
+    /// Write failures, timeouts excluded.
+    pub write_errors: u64,
+    /// Read and write timeouts.
+    pub timeouts: u64,
+    /// The last `ERROR_HISTORY_LEN` errors, oldest first.
+    pub errors: Vec<String>,
+    /// When the counting started, the byte totals giving the throughput over
+    /// `since.elapsed()`.
+    pub since: time::Instant,
+}
+
+impl Default for PortStats {
+    fn default() -> PortStats {
+        PortStats {
+            bytes_sent: 0,
+            bytes_received: 0,
+            read_errors: 0,
+            write_errors: 0,
+            timeouts: 0,
+            errors: vec![],
+            since: time::Instant::now(),
+        }
+    }
+}
+
+impl PortStats {
+    pub(crate) fn record_write(&mut self, written: &io::Result<usize>) {
+        match written {
+            Ok(t) => self.bytes_sent += *t as u64,
+            Err(e) => {
+                if e.kind() == io::ErrorKind::TimedOut {
+                    self.timeouts += 1;
+                } else {
+                    self.write_errors += 1;
+                }
+                self.push_error(format!("write: {}", e));
+            }
+        }
+    }
+
+    pub(crate) fn record_read(&mut self, read: &io::Result<usize>) {
+        match read {
+            Ok(t) => self.bytes_received += *t as u64,
+            // Waiting for data that does not come is not worth the history.
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => self.timeouts += 1,
+            Err(e) => {
+                self.read_errors += 1;
+                self.push_error(format!("read: {}", e));
+            }
+        }
+    }
+
+    fn push_error(&mut self, error: String) {
+        if self.errors.len() == ERROR_HISTORY_LEN {
+            self.errors.remove(0);
+        }
+        self.errors.push(error);
+    }
+}
+
+impl SerialPort {
+    /// Returns the time the port I/O of the last `send_encoded()` (so `send_once()`)
+    /// or `read_once()` took: the read or write call alone, without the parsing,
+    /// the codecs or the locking around it. Zero if the call needed no I/O (bytes
+    /// read ahead, coalesced write, no port open).
+    pub fn last_io_elapsed(&self) -> time::Duration {
+        self.last_io_elapsed
+    }
+
+    /// Returns the counters since the port has been created, since the last
+    /// `close_port()` or since the last `reset_stats()`.
+    pub fn stats(&self) -> &PortStats {
+        &self.stats
+    }
+
+    /// Zeroes every counter, clears the error history and restarts the throughput window.
+    ///
+    /// # Returns
+    ///
+    /// The counters as they were just before being reset.
+    pub fn reset_stats(&mut self) -> PortStats {
+        std::mem::take(&mut self.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_reset() {
+        let mut port = SerialPort::new();
+        port.stats.record_write(&Ok(3));
+        port.stats.record_read(&Ok(5));
+        port.stats
+            .record_read(&Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")));
+        port.stats
+            .record_write(&Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken")));
+
+        let cleared = port.reset_stats();
+        assert_eq!(3, cleared.bytes_sent);
+        assert_eq!(5, cleared.bytes_received);
+        assert_eq!(1, cleared.timeouts);
+        assert_eq!(1, cleared.write_errors);
+        assert_eq!(vec!["write: broken"], cleared.errors);
+
+        assert_eq!(0, port.stats().bytes_sent);
+        assert!(port.stats().errors.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn count_sends_and_reads() {
+        use std::io::{Read, Write};
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        port.set_read_timeout(time::Duration::from_millis(20));
+
+        assert!(port.send_once("abc").await.success);
+        assert!(port.send_once("de").await.success);
+        let mut sent = [0; 5];
+        master.read_exact(&mut sent).unwrap();
+        master.write_all(b"hello").unwrap();
+        assert_eq!("hello", port.read_once().await.content);
+        assert!(!port.read_once().await.success);
+
+        let stats = port.stats();
+        assert_eq!(5, stats.bytes_sent);
+        assert_eq!(5, stats.bytes_received);
+        assert_eq!(1, stats.timeouts);
+        assert_eq!(0, stats.read_errors + stats.write_errors);
+
+        assert!(port.close_port().await.success);
+        assert_eq!(0, port.stats().bytes_sent);
+        assert_eq!(0, port.stats().timeouts);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn count_delimited_reads() {
+        use crate::serial_port::delimited::OverflowPolicy;
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        port.set_read_timeout(time::Duration::from_millis(20));
+
+        master.write_all(b"one\ntw").unwrap();
+        let resp = port
+            .read_until_with(b'\n', 0, OverflowPolicy::default())
+            .await;
+        assert_eq!("one\n", resp.content);
+        assert_eq!(6, port.stats().bytes_received);
+
+        // The bytes read ahead are not counted again, the timeout is.
+        let resp = port
+            .read_until_with(b'\n', 0, OverflowPolicy::default())
+            .await;
+        assert!(!resp.success);
+        assert_eq!("tw", resp.content);
+        let stats = port.stats();
+        assert_eq!(6, stats.bytes_received);
+        assert_eq!(1, stats.timeouts);
+        assert_eq!(0, stats.read_errors);
+    }
+
+    #[test]
+    fn error_history_is_bounded() {
+        let mut stats = PortStats::default();
+        for i in 0..ERROR_HISTORY_LEN + 2 {
+            stats.record_read(&Err(io::Error::other(i.to_string())));
+        }
+
+        assert_eq!(ERROR_HISTORY_LEN, stats.errors.len());
+        assert_eq!("read: 2", stats.errors[0]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn elapsed_of_timed_out_read() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        port.set_read_timeout(time::Duration::from_millis(20));
+
+        assert!(!port.read_once().await.success);
+        assert!(port.last_io_elapsed() >= time::Duration::from_millis(15));
+
+        assert!(port.close_port().await.success);
+        port.read_once().await;
+        assert_eq!(time::Duration::from_millis(0), port.last_io_elapsed());
+    }
+}
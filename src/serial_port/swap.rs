@@ -0,0 +1,196 @@
+//! Switching the open port to another device, for failover between adapters.
+
+use std::time;
+
+use super::backend::SerialBackend;
+use super::{config, events::PortEventKind, open_error_response, SerialPort, SerialPortResponse};
+
+/// Line settings carried over from the old port to the new one.
+struct LineSettings {
+    data_bits: serialport::DataBits,
+    parity: serialport::Parity,
+    stop_bits: serialport::StopBits,
+    flow_control: serialport::FlowControl,
+    timeout: time::Duration,
+}
+
+impl LineSettings {
+    fn of(port: &dyn SerialBackend) -> serialport::Result<LineSettings> {
+        Ok(LineSettings {
+            data_bits: port.data_bits()?,
+            parity: port.parity()?,
+            stop_bits: port.stop_bits()?,
+            flow_control: port.flow_control()?,
+            timeout: port.timeout(),
+        })
+    }
+
+    fn apply(&self, port: &mut dyn SerialBackend) -> serialport::Result<()> {
+        port.set_data_bits(self.data_bits)?;
+        port.set_parity(self.parity)?;
+        port.set_stop_bits(self.stop_bits)?;
+        port.set_flow_control(self.flow_control)?;
+        port.set_timeout(self.timeout)
+    }
+}
+
+impl SerialPort {
+    /// Replaces the open port by `port_path`, keeping the session as is: the bytes
+    /// read ahead, the codec pipeline, the statistics, the byte trace, and the line
+    /// settings. The repeater, if running, is restarted on the new port. A `Closed`
+    /// then an `Opened` event are emitted.
+    ///
+    /// The old port is only closed once the new one is open and configured: on
+    /// failure, the old port stays in use as if nothing happened.
+    ///
+    /// # Paramters
+    ///
+    /// - `port_path`: The port to switch to, as given to `open_port()`.
+    /// - `baudrate`: The baudrate of the new port. Zero means the one of the old port.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the new port is now in use.
+    pub async fn swap_port(&mut self, port_path: &str, baudrate: u32) -> SerialPortResponse {
+        let (settings, old_baudrate) = match self.backend.if_open() {
+            Some(port) => (LineSettings::of(port), port.baud_rate()),
+            None => {
+                return SerialPortResponse {
+                    success: false,
+                    content: "No port is currently open".to_string(),
+                }
+            }
+        };
+
+        let settings = match settings {
+            Ok(settings) => settings,
+            Err(e) => {
+                return SerialPortResponse {
+                    success: false,
+                    content: format!("Could not read the settings of the current port: {}", e),
+                }
+            }
+        };
+
+        let baudrate = match (baudrate, old_baudrate) {
+            (0, Ok(old_baudrate)) => old_baudrate,
+            (0, Err(e)) => {
+                return SerialPortResponse {
+                    success: false,
+                    content: format!("Could not read the baudrate of the current port: {}", e),
+                }
+            }
+            (baudrate, _) => baudrate,
+        };
+
+        let config = config::SerialConfig::new(baudrate);
+        let new_port = self
+            .open_path(port_path, &config)
+            .await
+            .and_then(|mut port| {
+                settings.apply(port.as_mut())?;
+                Ok(port)
+            });
+
+        let new_port = match new_port {
+            Ok(port) => port,
+            Err(e) => {
+                let resp = open_error_response(port_path, e);
+                return SerialPortResponse {
+                    success: false,
+                    content: format!("{}, still using the current port", resp.content),
+                };
+            }
+        };
+
+        // The repeater works on a clone of the old port.
+        let was_repeating = self.is_repeating();
+        self.repeater = None;
+
+        let old_port = std::mem::replace(&mut self.backend, new_port);
+        let old_path = old_port.name().unwrap_or_else(|| "default".to_string());
+
+        let mut content = format!("Swapped port {} for {}", old_path, port_path);
+        self.emit(PortEventKind::Closed, &old_path, content.clone());
+        self.emit(PortEventKind::Opened, port_path, content.clone());
+
+        if was_repeating {
+            let resp = self.set_repeater(true).await;
+            if !resp.success {
+                content = format!("{}, but: {}", content, resp.content);
+            }
+        }
+
+        SerialPortResponse {
+            success: true,
+            content,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn swap_without_port() {
+        let mut port = SerialPort::new();
+        assert!(!port.swap_port("/dev/null", 9600).await.success);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn swap_keeps_session() {
+        use std::io::{Read, Write};
+
+        let (mut first, first_slave) = serialport::TTYPort::pair().unwrap();
+        let (mut second, second_slave) = serialport::TTYPort::pair().unwrap();
+        let first_path = serialport::SerialPort::name(&first_slave).unwrap();
+        let second_path = serialport::SerialPort::name(&second_slave).unwrap();
+
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&first_path, 9600).await.success);
+        // Leaves "two" read ahead.
+        first.write_all(b"one\ntwo").unwrap();
+        assert_eq!(
+            vec!["one"],
+            port.read_lines(1, time::Duration::from_millis(50))
+                .await
+                .lines
+        );
+
+        let resp = port.swap_port(&second_path, 0).await;
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!(Some(9600), port.backend.baud_rate().ok());
+
+        second.write_all(b"\n").unwrap();
+        assert_eq!(
+            vec!["two"],
+            port.read_lines(1, time::Duration::from_millis(50))
+                .await
+                .lines
+        );
+
+        assert!(port.send_once("ok").await.success);
+        let mut sent = [0; 2];
+        second.read_exact(&mut sent).unwrap();
+        assert_eq!(b"ok", &sent);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn failed_swap_keeps_old_port() {
+        let (_master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        let raw_fd = port.raw_fd();
+
+        let resp = port.swap_port("/dev/does-not-exist", 0).await;
+        assert!(!resp.success);
+        assert_eq!(raw_fd, port.raw_fd());
+        assert_eq!(Some(slave_path), port.backend.name());
+    }
+}
@@ -0,0 +1,302 @@
+//! Timeouts of a single send or read, for the commands answering slower (or faster)
+//! than the others, the timeouts of the port being put back afterwards, and reads
+//! bounded by a deadline.
+
+use std::{mem, time};
+
+use super::error::SerialError;
+use super::{SerialPort, SerialPortResponse};
+
+/// How long to wait before reading again after a read giving no byte, to not spin.
+const READ_DEADLINE_IDLE_WAIT_MS: u64 = 1;
+
+impl SerialPort {
+    /// Sends `message` as `send_once()` does, with `timeout` as write timeout.
+    ///
+    /// A write queued by the coalescing (see `set_write_coalescing()`) is written later
+    /// on, with the write timeout of the port.
+    ///
+    /// # Paramters
+    ///
+    /// - `message`: The string slice to send.
+    /// - `timeout`: The write timeout of this send. Zero means the one of the port.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message, see `send_once()`.
+    /// - `success`: if the message has been sent correctly.
+    pub async fn send_once_within(
+        &mut self,
+        message: &str,
+        timeout: time::Duration,
+    ) -> SerialPortResponse {
+        self.try_send_once_within(message, timeout).await.into()
+    }
+
+    /// Sends a message as `send_once_within()` does.
+    ///
+    /// # Returns
+    ///
+    /// As `try_send_once()`.
+    pub async fn try_send_once_within(
+        &mut self,
+        message: &str,
+        timeout: time::Duration,
+    ) -> Result<String, SerialError> {
+        let write_timeout = self.replace_write_timeout(timeout);
+        let sent = self.try_send_once(message).await;
+        self.write_timeout = write_timeout;
+
+        sent
+    }
+
+    /// Sends `output` as `send_encoded()` does, with `timeout` as write timeout,
+    /// see `send_once_within()`.
+    pub async fn send_encoded_within(
+        &mut self,
+        output: &[u8],
+        timeout: time::Duration,
+    ) -> SerialPortResponse {
+        self.try_send_encoded_within(output, timeout).await.into()
+    }
+
+    /// Sends `output` as `send_encoded_within()` does.
+    ///
+    /// # Returns
+    ///
+    /// As `try_send_once()`.
+    pub async fn try_send_encoded_within(
+        &mut self,
+        output: &[u8],
+        timeout: time::Duration,
+    ) -> Result<String, SerialError> {
+        let write_timeout = self.replace_write_timeout(timeout);
+        let sent = self.try_send_encoded(output).await;
+        self.write_timeout = write_timeout;
+
+        sent
+    }
+
+    /// Reads as `read_once()` does, with `timeout` as read timeout.
+    ///
+    /// # Paramters
+    ///
+    /// - `timeout`: The read timeout of this read. Zero means the one of the port.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: The characters read from the serial port, or an informative message.
+    /// - `success`: if the chars has been correctly read from the serial port.
+    pub async fn read_once_within(&mut self, timeout: time::Duration) -> SerialPortResponse {
+        let read = self
+            .try_read_n_bytes_within(self.read_buffer_size(), timeout)
+            .await
+            .map(|read| String::from_utf8_lossy(&read).to_string());
+        read.into()
+    }
+
+    /// Reads as `try_read_n_bytes()` does, with `timeout` as read timeout, see
+    /// `read_once_within()`.
+    pub async fn try_read_n_bytes_within(
+        &mut self,
+        max_bytes: usize,
+        timeout: time::Duration,
+    ) -> Result<Vec<u8>, SerialError> {
+        if timeout.as_nanos() == 0 {
+            return self.try_read_n_bytes(max_bytes).await;
+        }
+
+        if let Some(port) = self.backend.if_open_mut() {
+            port.set_timeout(timeout)
+                .map_err(|e| SerialError::io("Could not set the read timeout", e.into()))?;
+        }
+
+        let read = self.try_read_n_bytes(max_bytes).await;
+
+        // Puts back the read timeout in use, the read having failed or not.
+        if let Some(port) = self.backend.if_open_mut() {
+            let _ = port.set_timeout(self.read_timeout);
+        }
+
+        read
+    }
+
+    /// Reads all the bytes coming before `deadline` elapses, across as many reads
+    /// as needed, for the devices not framing their output.
+    ///
+    /// Unlike `read_until()` or `read_exact_n()`, reaching the deadline is not a
+    /// failure: the bytes received so far are returned.
+    ///
+    /// # Paramters
+    ///
+    /// - `deadline`: How long to read for, from the call.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: The characters read, or an informative message.
+    /// - `success`: if some bytes have been read, the failure telling nothing came.
+    pub async fn read_with_deadline(&mut self, deadline: time::Duration) -> SerialPortResponse {
+        let started = time::Instant::now();
+        let mut read = vec![];
+
+        loop {
+            let remaining = match deadline.checked_sub(started.elapsed()) {
+                Some(remaining) if remaining.as_nanos() > 0 => remaining,
+                _ => break,
+            };
+
+            match self
+                .try_read_n_bytes_within(self.read_buffer_size(), remaining)
+                .await
+            {
+                Ok(bytes) if bytes.is_empty() => {
+                    tokio::time::sleep(time::Duration::from_millis(READ_DEADLINE_IDLE_WAIT_MS))
+                        .await
+                }
+                Ok(bytes) => read.extend(bytes),
+                // Reads again for what the read timeout may have left.
+                Err(SerialError::Timeout(_)) => {}
+                Err(e) => return e.into(),
+            }
+        }
+
+        if read.is_empty() {
+            return SerialPortResponse {
+                success: false,
+                content: format!("No data received within {}ms", deadline.as_millis()),
+            };
+        }
+
+        SerialPortResponse {
+            success: true,
+            content: String::from_utf8_lossy(&read).to_string(),
+        }
+    }
+
+    /// Sets `timeout` as write timeout unless it is zero, giving the one to put back.
+    fn replace_write_timeout(&mut self, timeout: time::Duration) -> time::Duration {
+        if timeout.as_nanos() == 0 {
+            return self.write_timeout;
+        }
+
+        mem::replace(&mut self.write_timeout, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial_port::SERIAL_OPEN_TIMEOUT_MS;
+
+    #[tokio::test]
+    async fn timeouts_without_port() {
+        let mut port = SerialPort::new();
+
+        let resp = port.read_once_within(time::Duration::from_millis(10)).await;
+        assert!(!resp.success);
+        assert_eq!("No port is currently open", resp.content);
+        assert!(matches!(
+            port.try_send_once_within("ok", time::Duration::from_millis(10))
+                .await,
+            Err(SerialError::NoPortOpen)
+        ));
+        let resp = port
+            .read_with_deadline(time::Duration::from_millis(10))
+            .await;
+        assert!(!resp.success);
+        assert_eq!("No port is currently open", resp.content);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn short_and_long_read_timeouts() {
+        use std::io::{Read, Write};
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        let default_timeout = time::Duration::from_millis(SERIAL_OPEN_TIMEOUT_MS);
+
+        // A device answering after 100ms.
+        let device = std::thread::spawn(move || {
+            std::thread::sleep(time::Duration::from_millis(100));
+            master.write_all(b"late").unwrap();
+            master
+        });
+
+        let started = time::Instant::now();
+        let resp = port.read_once_within(time::Duration::from_millis(20)).await;
+        assert!(!resp.success);
+        assert_eq!("Serial read timed out", resp.content);
+        assert!(started.elapsed() < time::Duration::from_millis(100));
+        assert_eq!(default_timeout, port.backend.timeout());
+
+        let resp = port
+            .read_once_within(time::Duration::from_millis(500))
+            .await;
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!("late", resp.content);
+        assert_eq!(default_timeout, port.backend.timeout());
+        let mut master = device.join().unwrap();
+
+        let resp = port
+            .send_once_within("ok", time::Duration::from_millis(50))
+            .await;
+        assert!(resp.success, "{}", resp.content);
+        let mut sent = [0; 2];
+        master.read_exact(&mut sent).unwrap();
+        assert_eq!(b"ok", &sent);
+        assert_eq!(default_timeout, port.timeouts().unwrap().write);
+        assert_eq!(default_timeout, port.backend.timeout());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn read_until_the_deadline() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        // A device trickling its output, the last chunk past the deadline.
+        let device = std::thread::spawn(move || {
+            for (delay, chunk) in [(0, "on"), (40, "e, "), (40, "two"), (400, "late")].iter() {
+                std::thread::sleep(time::Duration::from_millis(*delay));
+                master.write_all(chunk.as_bytes()).unwrap();
+            }
+            master
+        });
+
+        let started = time::Instant::now();
+        let resp = port
+            .read_with_deadline(time::Duration::from_millis(250))
+            .await;
+        assert!(resp.success, "{}", resp.content);
+        assert_eq!("one, two", resp.content);
+        assert!(started.elapsed() >= time::Duration::from_millis(250));
+        assert!(started.elapsed() < time::Duration::from_millis(400));
+        assert_eq!(
+            time::Duration::from_millis(SERIAL_OPEN_TIMEOUT_MS),
+            port.backend.timeout()
+        );
+        let _master = device.join().unwrap();
+
+        assert_eq!(
+            "late",
+            port.read_with_deadline(time::Duration::from_millis(50))
+                .await
+                .content
+        );
+        let resp = port
+            .read_with_deadline(time::Duration::from_millis(50))
+            .await;
+        assert!(!resp.success);
+        assert_eq!("No data received within 50ms", resp.content);
+    }
+}
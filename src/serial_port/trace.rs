@@ -0,0 +1,192 @@
+//! Per-byte capture of the traffic, for protocol timing analysis.
+//!
+//! Every byte written to or read from the port is recorded, whatever the method
+//! (`send_once()`, `read_once()`, `read_lines()`, `send_modbus_rtu()`, ...), with
+//! its direction and the time it crossed the port, relative to the start of the
+//! capture.
+//! Bytes written or read by the same call share the same timestamp: the precision is
+//! bounded by the read timeout and by the buffering of the driver (and of USB adapters,
+//! which usually poll every 1ms).
+
+use std::{collections::VecDeque, time};
+
+use super::SerialPort;
+
+/// Number of bytes kept when no capacity is given.
+pub const DEFAULT_BYTE_TRACE_CAPACITY: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TracedByte {
+    /// Since the start of the capture.
+    pub timestamp: time::Duration,
+    pub byte: u8,
+    pub direction: Direction,
+}
+
+/// A bounded capture, dropping the oldest bytes once full.
+#[derive(Debug)]
+pub(crate) struct ByteTrace {
+    started: time::Instant,
+    capacity: usize,
+    bytes: VecDeque<TracedByte>,
+    /// Number of bytes dropped to make room.
+    dropped: u64,
+}
+
+impl ByteTrace {
+    fn new(capacity: usize) -> ByteTrace {
+        ByteTrace {
+            started: time::Instant::now(),
+            capacity,
+            bytes: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    pub(crate) fn record(&mut self, bytes: &[u8], direction: Direction) {
+        let timestamp = self.started.elapsed();
+
+        for byte in bytes {
+            if self.bytes.len() == self.capacity {
+                self.bytes.pop_front();
+                self.dropped += 1;
+            }
+
+            self.bytes.push_back(TracedByte {
+                timestamp,
+                byte: *byte,
+                direction,
+            });
+        }
+    }
+}
+
+impl SerialPort {
+    /// Starts a new capture, replacing the current one if any.
+    ///
+    /// # Paramters
+    ///
+    /// - `capacity`: The maximum number of bytes kept. Zero means `DEFAULT_BYTE_TRACE_CAPACITY`.
+    pub fn start_byte_trace(&mut self, capacity: usize) {
+        let capacity = if capacity == 0 {
+            DEFAULT_BYTE_TRACE_CAPACITY
+        } else {
+            capacity
+        };
+
+        self.byte_trace = Some(ByteTrace::new(capacity));
+    }
+
+    /// Stops the capture, dropping the recorded bytes.
+    pub fn stop_byte_trace(&mut self) {
+        self.byte_trace = None;
+    }
+
+    pub fn is_tracing_bytes(&self) -> bool {
+        self.byte_trace.is_some()
+    }
+
+    /// Returns the recorded bytes, oldest first, and how many have been dropped
+    /// because the capture was full.
+    ///
+    /// # Paramters
+    ///
+    /// - `clear`: If the returned bytes are removed from the capture, which keeps going.
+    pub fn byte_trace(&mut self, clear: bool) -> (Vec<TracedByte>, u64) {
+        match self.byte_trace.as_mut() {
+            Some(trace) if clear => (
+                trace.bytes.drain(..).collect(),
+                std::mem::take(&mut trace.dropped),
+            ),
+            Some(trace) => (trace.bytes.iter().copied().collect(), trace.dropped),
+            None => (vec![], 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_is_bounded() {
+        let mut trace = ByteTrace::new(3);
+        trace.record(b"ab", Direction::Sent);
+        trace.record(b"cd", Direction::Received);
+
+        let bytes: Vec<(u8, Direction)> = trace
+            .bytes
+            .iter()
+            .map(|traced| (traced.byte, traced.direction))
+            .collect();
+        assert_eq!(
+            vec![
+                (b'b', Direction::Sent),
+                (b'c', Direction::Received),
+                (b'd', Direction::Received)
+            ],
+            bytes
+        );
+        assert_eq!(1, trace.dropped);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn trace_send_and_read() {
+        use std::io::{Read, Write};
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        port.start_byte_trace(0);
+
+        assert!(port.send_once("ok").await.success);
+        master.read_exact(&mut [0; 2]).unwrap();
+        master.write_all(b"hi").unwrap();
+        assert!(port.read_once().await.success);
+
+        let (bytes, dropped) = port.byte_trace(true);
+        assert_eq!(0, dropped);
+        assert_eq!(
+            b"okhi".to_vec(),
+            bytes.iter().map(|t| t.byte).collect::<Vec<u8>>()
+        );
+        assert_eq!(Direction::Sent, bytes[1].direction);
+        assert_eq!(Direction::Received, bytes[2].direction);
+        assert!(bytes[1].timestamp <= bytes[2].timestamp);
+
+        assert!(port.byte_trace(false).0.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn trace_delimited_read() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        port.start_byte_trace(0);
+
+        master.write_all(b"one\ntwo").unwrap();
+        assert_eq!("one\n", port.read_until(b'\n').await.content);
+
+        // The bytes read ahead are traced once, when they crossed the port.
+        let (bytes, _) = port.byte_trace(true);
+        assert_eq!(
+            b"one\ntwo".to_vec(),
+            bytes.iter().map(|t| t.byte).collect::<Vec<u8>>()
+        );
+        assert!(bytes.iter().all(|t| t.direction == Direction::Received));
+        assert_eq!("two", port.read_once().await.content);
+        assert!(port.byte_trace(true).0.is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+//! Raw transfers from and into buffers owned by the caller, for large payloads
+//! (firmware images for ex.) where `send_once()` and `read_once()` would build a
+//! `String` per chunk.
+//!
+//! # Zero-copy contract
+//!
+//! `send_slice()` hands the caller's slice to the OS write, and `read_into()` gives
+//! the caller's slice to the OS read: neither allocates nor goes through an
+//! intermediate buffer. In exchange the bytes are raw, untouched by
+//! `parse_str_to_serial()` and by the codec pipeline.
+//!
+//! The only copies made are:
+//! - bytes read ahead by `read_lines()` or `read_until()`, which `read_into()`
+//!   returns first, out of the internal buffer holding them.
+//! - the byte trace, when one is running, recording every byte transferred.
+//!
+//! `send_file()` streams a file the same way, a chunk at a time, the whole file
+//! never being in memory.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use super::error::SerialError;
+use super::{monitor, no_port_error, trace, write_with_timeout, SerialPort, SerialPortResponse};
+
+/// `e`, telling how much of the file had been sent before the failing chunk.
+fn file_write_error(e: SerialError, sent: u64, len: u64) -> SerialError {
+    let context = format!("{} of the {} bytes of the file sent before", sent, len);
+
+    match e {
+        SerialError::Timeout(message) => SerialError::Timeout(format!("{}, {}", message, context)),
+        SerialError::Io(e) => {
+            SerialError::Io(io::Error::new(e.kind(), format!("{}, {}", e, context)))
+        }
+        e => e,
+    }
+}
+
+impl SerialPort {
+    /// Writes from `bytes`, as `std::io::Write::write()` does: some of the bytes
+    /// may not be written, to be given again by the caller.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes written, or the error of the write.
+    pub async fn send_slice(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        if !self.backend.is_open() {
+            return Err(no_port_error());
+        }
+        // The writes queued by the coalescing go out first.
+        if let Some(Err(e)) = self.flush_pending().await {
+            return Err(e.into());
+        }
+        let port = self.backend.if_open_mut().ok_or_else(no_port_error)?;
+
+        let written = write_with_timeout(port, bytes, self.write_timeout).await;
+        self.stats.record_write(&written);
+        match written {
+            Ok(t) => {
+                if let Some(trace) = self.byte_trace.as_mut() {
+                    trace.record(&bytes[..t], trace::Direction::Sent);
+                }
+                monitor::tap(&self.traffic_hooks, trace::Direction::Sent, &bytes[..t]);
+                Ok(t)
+            }
+            Err(e) => {
+                self.check_disconnect(&e).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Reads into `buf`, as `std::io::Read::read()` does, the bytes read ahead by
+    /// the other read methods coming first.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes read, or the error of the read (`TimedOut` when
+    /// nothing came within the timeout).
+    pub async fn read_into(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.backend.is_open() {
+            return Err(no_port_error());
+        }
+
+        if !self.read_buffer.is_empty() {
+            let len = self.read_buffer.len().min(buf.len());
+            buf[..len].copy_from_slice(&self.read_buffer[..len]);
+            self.read_buffer.drain(..len);
+            return Ok(len);
+        }
+
+        self.read_port(buf).await
+    }
+
+    /// Sends the content of the file at `path` as is, read and written a chunk at a
+    /// time, each chunk being written whole as `send_once()` does.
+    ///
+    /// # Paramters
+    ///
+    /// - `path`: The file to send.
+    /// - `chunk_size`: The number of bytes read from the file, then written, at once.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message, with the number of bytes sent.
+    /// - `success`: if the whole file has been sent.
+    pub async fn send_file(&mut self, path: &Path, chunk_size: usize) -> SerialPortResponse {
+        match self.try_send_file(path, chunk_size, |_, _| {}).await {
+            Ok(sent) => SerialPortResponse {
+                success: true,
+                content: format!("Sent {} bytes of {}", sent, path.display()),
+            },
+            Err(e) => e.into(),
+        }
+    }
+
+    /// Sends a file as `send_file()` does, calling `progress` after each chunk with
+    /// the number of bytes sent so far and the size of the file.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes sent, or the error: `NoPortOpen`, `InvalidInput` for a
+    /// chunk size of 0, `Io` if the file could not be read (`NotFound` if it does
+    /// not exist), or the `Timeout` or `Io` of a write.
+    pub async fn try_send_file(
+        &mut self,
+        path: &Path,
+        chunk_size: usize,
+        mut progress: impl FnMut(u64, u64),
+    ) -> Result<u64, SerialError> {
+        if !self.backend.is_open() {
+            return Err(SerialError::NoPortOpen);
+        }
+        if chunk_size == 0 {
+            return Err(SerialError::InvalidInput(
+                "The chunk size can not be 0".to_string(),
+            ));
+        }
+
+        let read_error = |e| SerialError::io(&format!("Could not read {}", path.display()), e);
+        let mut file = fs::File::open(path).map_err(read_error)?;
+        let len = file.metadata().map_err(read_error)?.len();
+
+        let mut chunk = vec![0; chunk_size];
+        let mut sent = 0;
+        loop {
+            let t = match file.read(&mut chunk) {
+                Ok(0) => return Ok(sent),
+                Ok(t) => t,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(read_error(e)),
+            };
+
+            self.try_write_encoded(&chunk[..t])
+                .await
+                .map_err(|e| file_write_error(e, sent, len))?;
+            sent += t as u64;
+            progress(sent, len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn without_port() {
+        let mut port = SerialPort::new();
+        assert_eq!(
+            io::ErrorKind::NotConnected,
+            port.send_slice(b"ok").await.unwrap_err().kind()
+        );
+        assert_eq!(
+            io::ErrorKind::NotConnected,
+            port.read_into(&mut [0; 2]).await.unwrap_err().kind()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn raw_transfers() {
+        use std::io::Write;
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+        port.set_codecs(&["crc16".to_string()]);
+
+        // Neither escaped nor encoded.
+        assert_eq!(4, port.send_slice(br"\x00").await.unwrap());
+        let mut sent = [0; 4];
+        master.read_exact(&mut sent).unwrap();
+        assert_eq!(br"\x00", &sent);
+
+        master.write_all(b"one\ntwo").unwrap();
+        port.read_lines(1, std::time::Duration::from_millis(50))
+            .await;
+        let mut read = [0; 8];
+        assert_eq!(3, port.read_into(&mut read).await.unwrap());
+        assert_eq!(b"two", &read[..3]);
+
+        assert_eq!(4, port.stats().bytes_sent);
+    }
+
+    #[tokio::test]
+    async fn send_file_errors() {
+        let missing = std::env::temp_dir().join("serial-term-rpc-missing-file");
+        let mut port = SerialPort::new();
+        assert!(matches!(
+            port.try_send_file(&missing, 64, |_, _| {}).await,
+            Err(SerialError::NoPortOpen)
+        ));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let path = format!("tcp://{}", listener.local_addr().unwrap());
+        assert!(port.open_port(&path, 9600).await.success);
+        match port.try_send_file(&missing, 64, |_, _| {}).await {
+            Err(SerialError::Io(e)) => assert_eq!(io::ErrorKind::NotFound, e.kind()),
+            other => panic!("{:?}", other),
+        }
+        assert!(port
+            .send_file(&missing, 64)
+            .await
+            .content
+            .starts_with("Could not read "));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn send_file_in_chunks() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let path =
+            std::env::temp_dir().join(format!("serial-term-rpc-send-file-{}", std::process::id()));
+        fs::write(&path, &data).unwrap();
+
+        let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+        let slave_path = serialport::SerialPort::name(&slave).unwrap();
+        let mut port = SerialPort::new();
+        assert!(port.open_port(&slave_path, 9600).await.success);
+
+        let device = std::thread::spawn(move || {
+            let mut received = vec![0; 1000];
+            master.read_exact(&mut received).unwrap();
+            // Kept open for the flush of the last chunk.
+            (received, master)
+        });
+
+        let mut progress = vec![];
+        let sent = port
+            .try_send_file(&path, 256, |sent, len| progress.push((sent, len)))
+            .await;
+        fs::remove_file(&path).unwrap();
+        assert_eq!(1000, sent.unwrap());
+        assert_eq!(
+            vec![(256, 1000), (512, 1000), (768, 1000), (1000, 1000)],
+            progress
+        );
+        assert_eq!(data, device.join().unwrap().0);
+        assert_eq!(1000, port.stats().bytes_sent);
+    }
+}
@@ -0,0 +1,391 @@
+//! Addressing the USB adapters by their IDs or serial number rather than by path, and
+//! identical adapters, without unique serial number, by their position.
+
+use super::{SerialPort, SerialPortResponse};
+
+/// A port as listed by `SerialPort::get_available_ports_detailed()`, the USB
+/// fields being `None` for the other ports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortDescription {
+    pub name: String,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
+
+impl From<serialport::SerialPortInfo> for PortDescription {
+    fn from(info: serialport::SerialPortInfo) -> PortDescription {
+        match info.port_type {
+            serialport::SerialPortType::UsbPort(usb) => PortDescription {
+                name: info.port_name,
+                vid: Some(usb.vid),
+                pid: Some(usb.pid),
+                serial_number: usb.serial_number,
+                manufacturer: usb.manufacturer,
+                product: usb.product,
+            },
+            _ => PortDescription {
+                name: info.port_name,
+                vid: None,
+                pid: None,
+                serial_number: None,
+                manufacturer: None,
+                product: None,
+            },
+        }
+    }
+}
+
+/// Returns the key ordering the USB ports: the physical path of the device in the
+/// USB topology (`.../usb1/1-2/1-2.3/1-2.3:1.0` for ex.) on Linux, stable as long
+/// as the adapters stay plugged in the same sockets, the port name elsewhere.
+fn topology_key(port_name: &str) -> String {
+    #[cfg(target_os = "linux")]
+    {
+        let tty = port_name.rsplit('/').next().unwrap_or(port_name);
+        let device = std::path::Path::new("/sys/class/tty")
+            .join(tty)
+            .join("device");
+
+        if let Ok(path) = std::fs::canonicalize(device) {
+            return path.to_string_lossy().to_string();
+        }
+    }
+
+    port_name.to_string()
+}
+
+/// Returns the ports of the USB devices `vid:pid`, sorted by `topology_key()`.
+pub fn usb_ports(vid: u16, pid: u16) -> Vec<String> {
+    let ports = serialport::available_ports().unwrap_or_default();
+
+    let mut matching: Vec<(String, String)> = ports
+        .into_iter()
+        .filter(|port| match &port.port_type {
+            serialport::SerialPortType::UsbPort(usb) => usb.vid == vid && usb.pid == pid,
+            _ => false,
+        })
+        .map(|port| (topology_key(&port.port_name), port.port_name))
+        .collect();
+    matching.sort();
+
+    matching
+        .into_iter()
+        .map(|(_, port_name)| port_name)
+        .collect()
+}
+
+/// Returns the single port of `ports` matching `what`, or an error message
+/// listing the candidates, with their serial numbers, if several match.
+fn single_port(
+    ports: Vec<PortDescription>,
+    what: &str,
+    matches: impl Fn(&PortDescription) -> bool,
+) -> Result<String, String> {
+    let mut matching: Vec<PortDescription> =
+        ports.into_iter().filter(|port| matches(port)).collect();
+
+    match matching.len() {
+        0 => Err(format!("No port matches {}", what)),
+        1 => Ok(matching.swap_remove(0).name),
+        n => {
+            let candidates: Vec<String> = matching
+                .iter()
+                .map(|port| match &port.serial_number {
+                    Some(serial) => format!("{} (serial number {})", port.name, serial),
+                    None => format!("{} (no serial number)", port.name),
+                })
+                .collect();
+            Err(format!(
+                "{} ports match {}: {}",
+                n,
+                what,
+                candidates.join(", ")
+            ))
+        }
+    }
+}
+
+/// Returns the single port of the USB devices `vid:pid` among `ports`.
+fn usb_port_in(ports: Vec<PortDescription>, vid: u16, pid: u16) -> Result<String, String> {
+    single_port(ports, &format!("{:04x}:{:04x}", vid, pid), |port| {
+        port.vid == Some(vid) && port.pid == Some(pid)
+    })
+}
+
+/// Returns the single port of the USB device with the serial number `serial`
+/// among `ports`.
+fn serial_number_port_in(ports: Vec<PortDescription>, serial: &str) -> Result<String, String> {
+    single_port(ports, &format!("the serial number {}", serial), |port| {
+        port.serial_number.as_deref() == Some(serial)
+    })
+}
+
+/// Returns the `index`-th port of `usb_ports()`, or an error message if `index`
+/// is out of range.
+pub fn usb_port_at(vid: u16, pid: u16, index: usize) -> Result<String, String> {
+    let mut ports = usb_ports(vid, pid);
+
+    if index < ports.len() {
+        Ok(ports.swap_remove(index))
+    } else {
+        Err(format!(
+            "No port at index {}, {} ports match {:04x}:{:04x}",
+            index,
+            ports.len(),
+            vid,
+            pid
+        ))
+    }
+}
+
+/// `SerialPort::get_available_ports_detailed()`, enumerated on a blocking thread.
+async fn available_ports_detailed() -> Vec<PortDescription> {
+    tokio::task::spawn_blocking(SerialPort::get_available_ports_detailed)
+        .await
+        .unwrap_or_default()
+}
+
+impl SerialPort {
+    /// Returns the available ports, with the IDs and strings of the USB ones, to
+    /// tell several `/dev/ttyUSB*` apart. Empty if none is available.
+    pub fn get_available_ports_detailed() -> Vec<PortDescription> {
+        serialport::available_ports()
+            .unwrap_or_default()
+            .into_iter()
+            .map(PortDescription::from)
+            .collect()
+    }
+
+    /// Opens the port of the USB device `vid:pid`, the IDs staying the same across
+    /// reboots and platforms unlike the port paths.
+    ///
+    /// # Paramters
+    ///
+    /// - `vid`: The USB vendor ID.
+    /// - `pid`: The USB product ID.
+    /// - `baudrate`: As given to `open_port()`.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message, listing the matching ports with their serial
+    ///   numbers if several match, see `open_by_serial_number()` to tell them apart.
+    /// - `success`: if the port has been open correctly. It fails if no port or several
+    ///   ports match.
+    pub async fn open_by_usb(&mut self, vid: u16, pid: u16, baudrate: u32) -> SerialPortResponse {
+        let ports = available_ports_detailed().await;
+        self.open_resolved(usb_port_in(ports, vid, pid), baudrate)
+            .await
+    }
+
+    /// Opens the port of the USB device with the serial number `serial`.
+    ///
+    /// # Paramters
+    ///
+    /// - `serial`: The serial number, as given by `get_available_ports_detailed()`.
+    /// - `baudrate`: As given to `open_port()`.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the port has been open correctly. It fails if no port or several
+    ///   ports match.
+    pub async fn open_by_serial_number(
+        &mut self,
+        serial: &str,
+        baudrate: u32,
+    ) -> SerialPortResponse {
+        let ports = available_ports_detailed().await;
+        self.open_resolved(serial_number_port_in(ports, serial), baudrate)
+            .await
+    }
+
+    async fn open_resolved(
+        &mut self,
+        port_path: Result<String, String>,
+        baudrate: u32,
+    ) -> SerialPortResponse {
+        match port_path {
+            Ok(port_path) => self.open_port(&port_path, baudrate).await,
+            Err(content) => SerialPortResponse {
+                success: false,
+                content,
+            },
+        }
+    }
+
+    /// Opens the `index`-th port, in a stable order, of the USB devices `vid:pid`.
+    ///
+    /// The order is the one of the physical USB topology on Linux (see `usb_ports()`),
+    /// of the port names elsewhere. It only changes when adapters are moved to other
+    /// sockets, added or removed.
+    ///
+    /// # Paramters
+    ///
+    /// - `vid`: The USB vendor ID.
+    /// - `pid`: The USB product ID.
+    /// - `index`: The position of the port among the matching ones, from 0.
+    /// - `baudrate`: As given to `open_port()`.
+    ///
+    /// # Returns
+    ///
+    /// A `SerialPortResponse` containing:
+    /// - `content`: informative message.
+    /// - `success`: if the port has been open correctly. It fails if `index` is out of range.
+    pub async fn open_by_usb_index(
+        &mut self,
+        vid: u16,
+        pid: u16,
+        index: usize,
+        baudrate: u32,
+    ) -> SerialPortResponse {
+        let port_path = tokio::task::spawn_blocking(move || usb_port_at(vid, pid, index))
+            .await
+            .unwrap_or_else(|e| Err(format!("Could not list the ports: {}", e)));
+        self.open_resolved(port_path, baudrate).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn index_out_of_range() {
+        let mut port = SerialPort::new();
+        let resp = port.open_by_usb_index(0xffff, 0xffff, 0, 9600).await;
+        assert!(!resp.success);
+        assert_eq!("No port at index 0, 0 ports match ffff:ffff", resp.content);
+    }
+
+    #[test]
+    fn describe_usb_port() {
+        let info = serialport::SerialPortInfo {
+            port_name: "/dev/ttyUSB0".to_string(),
+            port_type: serialport::SerialPortType::UsbPort(serialport::UsbPortInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial_number: Some("A10K4X2B".to_string()),
+                manufacturer: Some("FTDI".to_string()),
+                product: None,
+            }),
+        };
+
+        assert_eq!(
+            PortDescription {
+                name: "/dev/ttyUSB0".to_string(),
+                vid: Some(0x0403),
+                pid: Some(0x6001),
+                serial_number: Some("A10K4X2B".to_string()),
+                manufacturer: Some("FTDI".to_string()),
+                product: None,
+            },
+            PortDescription::from(info)
+        );
+    }
+
+    #[test]
+    fn describe_other_ports() {
+        for port_type in [
+            serialport::SerialPortType::PciPort,
+            serialport::SerialPortType::BluetoothPort,
+            serialport::SerialPortType::Unknown,
+        ]
+        .iter()
+        {
+            let info = serialport::SerialPortInfo {
+                port_name: "/dev/ttyS0".to_string(),
+                port_type: port_type.clone(),
+            };
+
+            let description = PortDescription::from(info);
+            assert_eq!("/dev/ttyS0", description.name);
+            assert_eq!(None, description.vid);
+            assert_eq!(None, description.pid);
+            assert_eq!(None, description.serial_number);
+            assert_eq!(None, description.manufacturer);
+            assert_eq!(None, description.product);
+        }
+    }
+
+    fn usb(name: &str, vid: u16, pid: u16, serial: Option<&str>) -> PortDescription {
+        PortDescription {
+            name: name.to_string(),
+            vid: Some(vid),
+            pid: Some(pid),
+            serial_number: serial.map(str::to_string),
+            manufacturer: None,
+            product: None,
+        }
+    }
+
+    fn ports() -> Vec<PortDescription> {
+        vec![
+            PortDescription::from(serialport::SerialPortInfo {
+                port_name: "/dev/ttyS0".to_string(),
+                port_type: serialport::SerialPortType::PciPort,
+            }),
+            usb("/dev/ttyUSB0", 0x0403, 0x6001, Some("A10K4X2B")),
+            usb("/dev/ttyUSB1", 0x0403, 0x6001, None),
+            usb("/dev/ttyACM0", 0x2341, 0x0043, Some("7573")),
+        ]
+    }
+
+    #[test]
+    fn find_by_usb_ids() {
+        assert_eq!(
+            Ok("/dev/ttyACM0".to_string()),
+            usb_port_in(ports(), 0x2341, 0x0043)
+        );
+        assert_eq!(
+            Err("No port matches 2341:0001".to_string()),
+            usb_port_in(ports(), 0x2341, 0x0001)
+        );
+        assert_eq!(
+            Err("2 ports match 0403:6001: /dev/ttyUSB0 (serial number A10K4X2B), /dev/ttyUSB1 (no serial number)".to_string()),
+            usb_port_in(ports(), 0x0403, 0x6001)
+        );
+    }
+
+    #[test]
+    fn find_by_serial_number() {
+        assert_eq!(
+            Ok("/dev/ttyUSB0".to_string()),
+            serial_number_port_in(ports(), "A10K4X2B")
+        );
+        assert_eq!(
+            Err("No port matches the serial number A10K".to_string()),
+            serial_number_port_in(ports(), "A10K")
+        );
+
+        let mut ports = ports();
+        ports.push(usb("/dev/ttyUSB2", 0x10c4, 0xea60, Some("7573")));
+        assert!(serial_number_port_in(ports, "7573")
+            .unwrap_err()
+            .starts_with("2 ports match the serial number 7573: /dev/ttyACM0"));
+    }
+
+    #[tokio::test]
+    async fn open_unknown_usb_device() {
+        let mut port = SerialPort::new();
+
+        let resp = port.open_by_usb(0xffff, 0xffff, 9600).await;
+        assert!(!resp.success);
+        assert_eq!("No port matches ffff:ffff", resp.content);
+        assert!(
+            !port
+                .open_by_serial_number("does-not-exist", 9600)
+                .await
+                .success
+        );
+    }
+
+    #[test]
+    fn key_of_unknown_port() {
+        assert_eq!("/dev/does-not-exist", topology_key("/dev/does-not-exist"));
+    }
+}
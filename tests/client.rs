@@ -0,0 +1,151 @@
+//! Runs the client binary against the server binary.
+
+use std::net::{SocketAddr, TcpListener};
+use std::process::{Child, Command, Output};
+use std::{thread, time};
+
+/// Stops the server when the test ends, even on failure.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Starts the server binary with `args` on a free port.
+fn spawn_server(args: &[&str]) -> (Server, SocketAddr) {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let server = Server(
+        Command::new(env!("CARGO_BIN_EXE_server"))
+            .arg(addr.to_string())
+            .arg("--quiet")
+            .args(args)
+            .spawn()
+            .unwrap(),
+    );
+
+    (server, addr)
+}
+
+fn client(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_client"))
+        .env_remove("SERIAL_RPC_AUTH_TOKEN")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Runs the client with `args` until the server listens.
+fn client_once_listening(args: &[&str]) -> Output {
+    let started = time::Instant::now();
+    loop {
+        let output = client(args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.starts_with("Could not connect")
+            || started.elapsed() > time::Duration::from_secs(5)
+        {
+            break output;
+        }
+        thread::sleep(time::Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn ping_the_server() {
+    let (_server, addr) = spawn_server(&[]);
+    let url = format!("http://{}", addr);
+
+    let output = client_once_listening(&["--addr", &url, "ping"]);
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!String::from_utf8_lossy(&output.stdout).trim().is_empty());
+
+    let output = client(&["--addr", &url, "close"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        "No port is currently open",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn ping_during_a_long_read() {
+    let (_master, slave) = serialport::TTYPort::pair().unwrap();
+    let slave_path = serialport::SerialPort::name(&slave).unwrap();
+    let (_server, addr) = spawn_server(&["--open", &slave_path, "--port-timeout-ms", "3000"]);
+    let url = format!("http://{}", addr);
+    assert!(client_once_listening(&["--addr", &url, "ping"])
+        .status
+        .success());
+
+    // Nothing comes from the device, the read waits for the whole timeout.
+    let read_url = url.clone();
+    let reading = thread::spawn(move || client(&["--addr", &read_url, "read"]));
+    thread::sleep(time::Duration::from_millis(500));
+
+    let started = time::Instant::now();
+    assert!(client(&["--addr", &url, "ping"]).status.success());
+    assert!(
+        started.elapsed() < time::Duration::from_secs(1),
+        "blocked for {:?}",
+        started.elapsed()
+    );
+    assert!(
+        !reading.is_finished(),
+        "the read should still wait on the device"
+    );
+
+    let output = reading.join().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        "Serial read timed out",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+}
+
+#[test]
+fn ping_with_a_token() {
+    let (_server, addr) = spawn_server(&["--auth-token", "s3cret"]);
+    let url = format!("http://{}", addr);
+
+    let output = client_once_listening(&["--addr", &url, "ping"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Missing authorization metadata"));
+
+    let output = client(&["--addr", &url, "--auth-token", "s3cret", "ping"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = client(&["--addr", &url, "--auth-token", "wrong", "ping"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid authorization token"));
+}
+
+#[test]
+fn ping_over_tls() {
+    let fixture = |name: &str| format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let (cert, key) = (fixture("localhost.crt"), fixture("localhost.key"));
+    let (_server, addr) = spawn_server(&["--tls-cert", &cert, "--tls-key", &key]);
+    let url = format!("https://localhost:{}", addr.port());
+
+    let output = client_once_listening(&["--addr", &url, "--tls-ca", &cert, "ping"]);
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}